@@ -0,0 +1,236 @@
+use acs::sparse::{self, FreeColumns};
+use acs::{ConstraintSolver, ConstraintType, GeometrySystem, ParameterManager, Point};
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use nalgebra::{DMatrix, DVector};
+use nalgebra_sparse::CsrMatrix;
+
+/// The points and constraints of a staircase polyline of `size` points with
+/// alternating horizontal and vertical segments, pinned at the first point
+/// and dimensioned every tenth step. Points start slightly off their solved
+/// positions.
+fn staircase_parts(size: usize) -> (Vec<Point>, Vec<ConstraintType>) {
+    let mut points = Vec::with_capacity(size);
+    for i in 0..size {
+        let step = (i / 2) as f64;
+        let offset = (i as f64).sin() * 0.3;
+        let x = step + (i % 2) as f64 + offset;
+        let y = step - offset;
+        points.push(Point::new(format!("p{i}"), x, y, i == 0));
+    }
+
+    let mut constraints = Vec::new();
+    for i in 1..size {
+        let a = format!("p{}", i - 1);
+        let b = format!("p{i}");
+        constraints.push(if i % 2 == 1 {
+            ConstraintType::Horizontal(a, b.clone())
+        } else {
+            ConstraintType::Vertical(a, b.clone())
+        });
+        if i % 10 == 0 {
+            constraints.push(ConstraintType::EqualX(b, (i / 2) as f64));
+        }
+    }
+
+    (points, constraints)
+}
+
+fn staircase(size: usize) -> ConstraintSolver {
+    let mut solver = ConstraintSolver::new();
+    let (points, constraints) = staircase_parts(size);
+    for point in points {
+        solver.add_point(point);
+    }
+    for constraint in constraints {
+        solver
+            .add_constraint(constraint)
+            .expect("Constraint should be added");
+    }
+    solver
+}
+
+/// Jacobian and gradient `Jᵀr` of the staircase at its starting position,
+/// i.e. the input of the first solver step
+fn staircase_step(size: usize) -> (CsrMatrix<f64>, DVector<f64>) {
+    let mut geometry = GeometrySystem::new();
+    let (points, constraints) = staircase_parts(size);
+    for point in points {
+        geometry.add_point(point);
+    }
+    let param_manager = ParameterManager::from_geometry(&geometry);
+    let constraints: Vec<_> = constraints
+        .into_iter()
+        .map(|constraint| acs::create_constraint(constraint).expect("Constraint should be built"))
+        .collect();
+    let bound =
+        sparse::bind_constraints(&param_manager, &constraints).expect("Constraints should bind");
+
+    let params = param_manager.get_parameters();
+    let jacobian = sparse::assemble_jacobian(params, &bound, &FreeColumns::new(&param_manager));
+    let gradient = jacobian.transpose() * sparse::evaluate_residuals(params, &bound);
+    (jacobian, gradient)
+}
+
+/// The normal equations `JᵀJ x = Jᵀr` solved with dense matrices, as every
+/// solver step did before Jacobians were sparse
+fn solve_dense(jacobian: &DMatrix<f64>, gradient: &DVector<f64>) -> DVector<f64> {
+    let mut jtj = jacobian.transpose() * jacobian;
+    let damping = 1e-12 * jtj.diagonal().amax().max(1.0);
+    for i in 0..jtj.nrows() {
+        jtj[(i, i)] += damping;
+    }
+    jtj.cholesky()
+        .expect("Normal equations should be positive definite")
+        .solve(gradient)
+}
+
+/// A grid of rectangular rooms sharing walls, about 700 constraints for a
+/// 10 x 10 grid. Corners are shared between neighbouring rooms through
+/// coincident constraints, and each room is dimensioned along the edges.
+fn floor_plan(rooms_per_side: usize) -> ConstraintSolver {
+    let mut solver = ConstraintSolver::new();
+    let corner = |room: usize, k: usize| format!("r{room}_{k}");
+
+    for row in 0..rooms_per_side {
+        for col in 0..rooms_per_side {
+            let room = row * rooms_per_side + col;
+            let jitter = ((room * 7) as f64).sin() * 0.2;
+            let (x0, y0) = (col as f64 * 4.0, row as f64 * 3.0);
+            let corners = [
+                (x0, y0),
+                (x0 + 4.0, y0),
+                (x0 + 4.0, y0 + 3.0),
+                (x0, y0 + 3.0),
+            ];
+            for (k, (x, y)) in corners.into_iter().enumerate() {
+                let fixed = room == 0 && k == 0;
+                solver.add_point(Point::new(corner(room, k), x + jitter, y - jitter, fixed));
+            }
+
+            let walls = [
+                ConstraintType::Horizontal(corner(room, 0), corner(room, 1)),
+                ConstraintType::Vertical(corner(room, 1), corner(room, 2)),
+                ConstraintType::Horizontal(corner(room, 2), corner(room, 3)),
+                ConstraintType::Vertical(corner(room, 3), corner(room, 0)),
+            ];
+            for wall in walls {
+                solver
+                    .add_constraint(wall)
+                    .expect("Constraint should be added");
+            }
+
+            if col > 0 {
+                let left = room - 1;
+                for (a, b) in [(0, 1), (3, 2)] {
+                    solver
+                        .add_constraint(ConstraintType::Coincident(
+                            corner(room, a),
+                            corner(left, b),
+                        ))
+                        .expect("Constraint should be added");
+                }
+            } else {
+                solver
+                    .add_constraint(ConstraintType::EqualX(corner(room, 0), 0.0))
+                    .expect("Constraint should be added");
+            }
+            if row > 0 {
+                let below = room - rooms_per_side;
+                solver
+                    .add_constraint(ConstraintType::Coincident(
+                        corner(room, 1),
+                        corner(below, 2),
+                    ))
+                    .expect("Constraint should be added");
+            } else {
+                solver
+                    .add_constraint(ConstraintType::EqualX(
+                        corner(room, 1),
+                        (col + 1) as f64 * 4.0,
+                    ))
+                    .expect("Constraint should be added");
+            }
+            if col == 0 {
+                solver
+                    .add_constraint(ConstraintType::EqualY(
+                        corner(room, 3),
+                        (row + 1) as f64 * 3.0,
+                    ))
+                    .expect("Constraint should be added");
+            }
+        }
+    }
+
+    solver
+}
+
+fn bench_floor_plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("floor_plan");
+    group.sample_size(10);
+
+    for rooms_per_side in [5, 10, 20] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(rooms_per_side * rooms_per_side),
+            &rooms_per_side,
+            |b, &rooms_per_side| {
+                b.iter_batched(
+                    || floor_plan(rooms_per_side),
+                    |mut solver| solver.solve().expect("Floor plan should solve"),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_staircase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("staircase");
+    group.sample_size(10);
+
+    for size in [100, 500, 2_000, 5_000] {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || staircase(size),
+                |mut solver| solver.solve().expect("Sketch should solve"),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+/// One solver step on the staircase, sparse against the dense baseline
+fn bench_normal_equations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("normal_equations");
+    group.sample_size(10);
+
+    for size in [100, 500, 2_000, 5_000] {
+        let (jacobian, gradient) = staircase_step(size);
+        group.bench_with_input(BenchmarkId::new("sparse", size), &size, |b, _| {
+            b.iter(|| {
+                sparse::solve_normal_equations(&jacobian, &gradient).expect("Step should solve")
+            });
+        });
+
+        // A dense 10,000 x 10,000 JᵀJ takes 800 MB and minutes to factor
+        if size <= 2_000 {
+            let dense = DMatrix::from(&jacobian);
+            group.bench_with_input(BenchmarkId::new("dense", size), &size, |b, _| {
+                b.iter(|| solve_dense(&dense, &gradient));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_staircase,
+    bench_floor_plan,
+    bench_normal_equations
+);
+criterion_main!(benches);
@@ -0,0 +1,585 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::constraints::base::finite_difference_columns;
+use crate::{
+    AcsError, BoundConstraint, Constraint, ConstraintGraph, EntityType, JacobianEntry,
+    ParameterManager, decomposition, sparse,
+};
+
+/// Singular values below this fraction of the largest one count as zero
+const RANK_TOLERANCE: f64 = 1e-9;
+
+/// Residuals of dependent equations that disagree by more than this are in
+/// conflict. Matches the solver's convergence tolerance.
+const CONSISTENCY_TOLERANCE: f64 = 1e-6;
+
+/// Jacobian entries whose relative error exceeds this are reported by
+/// `check_jacobian`
+const JACOBIAN_TOLERANCE: f64 = 1e-5;
+
+/// How much freedom an entity has left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DofStatus {
+    /// No constraint restricts the entity's unlocked parameters
+    Free,
+    /// Some, but not all, of the entity's freedom is taken away
+    PartiallyConstrained,
+    /// The entity cannot move
+    FullyConstrained,
+}
+
+/// Remaining degrees of freedom of a single entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDof {
+    pub id: String,
+    pub entity_type: EntityType,
+    /// Number of parameters of the entity, fixed or not
+    pub parameters: usize,
+    /// Number of independent ways the entity can still move
+    pub dof: usize,
+    pub status: DofStatus,
+}
+
+/// Degrees-of-freedom analysis of a sketch at its current state
+#[derive(Debug, Clone, PartialEq)]
+pub struct DofAnalysis {
+    /// Remaining degrees of freedom of the whole sketch
+    pub dof: usize,
+    /// Rank of the constraint Jacobian over the free parameters
+    pub rank: usize,
+    /// Number of non-fixed parameters
+    pub free_parameters: usize,
+    /// Per-entity results, sorted by ID
+    pub entities: Vec<EntityDof>,
+}
+
+impl DofAnalysis {
+    pub fn is_fully_constrained(&self) -> bool {
+        self.dof == 0
+    }
+
+    pub fn entity(&self, id: &str) -> Option<&EntityDof> {
+        self.entities.iter().find(|entity| entity.id == id)
+    }
+}
+
+/// Compute the rank of the constraint Jacobian at the current parameter
+/// values, and from its null space the freedom left to each entity.
+///
+/// The Jacobian is analyzed per cluster (see `decomposition`), so the dense
+/// factorizations stay as small as the independent parts of the sketch.
+pub fn analyze_dof(
+    param_manager: &ParameterManager,
+    constraints: &[Box<dyn Constraint>],
+) -> Result<DofAnalysis, AcsError> {
+    let bound = sparse::bind_constraints(param_manager, constraints)?;
+    Ok(analyze_bound_dof(param_manager, &bound))
+}
+
+/// `analyze_dof` for constraints that are already bound
+pub(crate) fn analyze_bound_dof(
+    param_manager: &ParameterManager,
+    bound: &[Box<dyn BoundConstraint>],
+) -> DofAnalysis {
+    let info = param_manager.get_parameter_info();
+    let params = param_manager.get_parameters();
+    let is_free: Vec<bool> = info.iter().map(|info| !info.is_fixed).collect();
+
+    // Freedom of each entity, stored at the entity's first global index
+    let mut entity_dof = vec![0; info.len()];
+    let mut in_cluster = vec![false; info.len()];
+    let mut rank = 0;
+
+    for cluster in decomposition::find_clusters(bound, &is_free) {
+        if cluster.parameters.is_empty() {
+            continue;
+        }
+        for &index in &cluster.parameters {
+            in_cluster[index] = true;
+        }
+
+        let constraints: Vec<&dyn BoundConstraint> = cluster
+            .constraints
+            .iter()
+            .map(|&i| bound[i].as_ref())
+            .collect();
+        let jacobian = dense_jacobian(
+            params,
+            &constraints,
+            &cluster.parameters,
+            cluster.parameters.len(),
+        );
+        let (cluster_rank, null_space) = null_space(jacobian);
+        rank += cluster_rank;
+
+        // The freedom of an entity is the dimension of the null space
+        // restricted to the entity's parameters
+        let mut start = 0;
+        while start < cluster.parameters.len() {
+            let entity_id = &info[cluster.parameters[start]].entity_id;
+            let end = (start..cluster.parameters.len())
+                .find(|&k| &info[cluster.parameters[k]].entity_id != entity_id)
+                .unwrap_or(cluster.parameters.len());
+
+            let block = null_space.rows(start, end - start).into_owned();
+            let first = cluster.parameters[start] - info[cluster.parameters[start]].param_index;
+            entity_dof[first] += matrix_rank(block);
+            start = end;
+        }
+    }
+
+    // Free parameters no constraint touches keep their freedom
+    for (index, parameter) in info.iter().enumerate() {
+        if parameter.is_fixed || in_cluster[index] {
+            continue;
+        }
+        let first = index - parameter.param_index;
+        entity_dof[first] += 1;
+    }
+
+    let mut entities: Vec<EntityDof> = info
+        .iter()
+        .filter(|parameter| parameter.param_index == 0)
+        .map(|parameter| {
+            let indices = param_manager
+                .get_entity_indices(&parameter.entity_id)
+                .unwrap_or_default();
+            let parameters = indices.len();
+            // Locked parameters never count as freedom, so compare against
+            // the unlocked ones
+            let unlocked = indices.iter().filter(|&&i| !info[i].is_fixed).count();
+            let dof = entity_dof[parameter.global_index];
+            let status = if dof == 0 {
+                DofStatus::FullyConstrained
+            } else if dof == unlocked {
+                DofStatus::Free
+            } else {
+                DofStatus::PartiallyConstrained
+            };
+
+            EntityDof {
+                id: parameter.entity_id.clone(),
+                entity_type: parameter.entity_type.clone(),
+                parameters,
+                dof,
+                status,
+            }
+        })
+        .collect();
+    entities.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let free_parameters = is_free.iter().filter(|&&free| free).count();
+    DofAnalysis {
+        dof: free_parameters - rank,
+        rank,
+        free_parameters,
+        entities,
+    }
+}
+
+/// Residual of one constraint at the current parameter values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintResidual {
+    /// ID of the constraint
+    pub constraint: String,
+    /// Position of the constraint in the constraint list
+    pub index: usize,
+    /// Name of the constraint type, e.g. `"Parallel"`
+    pub constraint_type: String,
+    /// One value per equation of the constraint
+    pub residuals: Vec<f64>,
+    /// Euclidean norm of `residuals`
+    pub norm: f64,
+    /// Priority of a soft constraint, which may be left violated by design
+    pub priority: Option<u32>,
+}
+
+impl ConstraintResidual {
+    /// Whether the constraint is violated by more than `tolerance`
+    pub fn is_violated(&self, tolerance: f64) -> bool {
+        self.norm > tolerance
+    }
+}
+
+/// Residuals of every constraint, in constraint order
+pub fn constraint_residuals(
+    param_manager: &ParameterManager,
+    constraint_graph: &ConstraintGraph,
+) -> Result<Vec<ConstraintResidual>, AcsError> {
+    let bound = sparse::bind_constraints(param_manager, constraint_graph.get_constraints())?;
+    let params = param_manager.get_parameters();
+
+    Ok(constraint_graph
+        .get_entries()
+        .iter()
+        .zip(&bound)
+        .enumerate()
+        .map(|(index, (entry, constraint))| {
+            let mut residuals = vec![0.0; constraint.num_residuals()];
+            constraint.residual(params, &mut residuals);
+            ConstraintResidual {
+                constraint: entry.id.clone(),
+                index,
+                constraint_type: entry.constraint_type.type_name().to_string(),
+                norm: DVector::from_column_slice(&residuals).norm(),
+                residuals,
+                priority: entry.priority,
+            }
+        })
+        .collect())
+}
+
+/// A Jacobian entry that disagrees with its finite-difference estimate
+#[derive(Debug, Clone, PartialEq)]
+pub struct JacobianMismatch {
+    /// Residual index within the constraint
+    pub row: usize,
+    /// Global index of the parameter
+    pub index: usize,
+    /// Name of the parameter, e.g. `"p1.x"`
+    pub parameter: String,
+    /// Value from `jacobian_entries`
+    pub analytic: f64,
+    /// Central-difference estimate
+    pub numeric: f64,
+    /// `|analytic - numeric|`, relative to the larger of the two when above one
+    pub error: f64,
+}
+
+/// Result of comparing a constraint's Jacobian with finite differences
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JacobianCheck {
+    /// Largest error over all checked entries
+    pub max_error: f64,
+    /// Entries with an error above the tolerance, worst first
+    pub mismatches: Vec<JacobianMismatch>,
+}
+
+impl JacobianCheck {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Compare the Jacobian of a constraint at the current parameter values with
+/// central finite differences of its residuals. Meant for validating
+/// hand-written Jacobians of custom constraints.
+///
+/// Every parameter the constraint reports, and every column it writes
+/// entries to, is checked. Since finite differences are only accurate to
+/// about 1e-8, tiny mismatches are not reported.
+pub fn check_jacobian(
+    constraint: &dyn Constraint,
+    param_manager: &ParameterManager,
+) -> Result<JacobianCheck, AcsError> {
+    let bound = constraint.bind(param_manager)?;
+    let info = param_manager.get_parameter_info();
+    let params = param_manager.get_parameters();
+    let rows = bound.num_residuals();
+
+    // Out-of-range parameters would make the constraint index past the end
+    // of `params` when evaluated
+    let out_of_range = |index: usize| AcsError::InvalidParameter {
+        message: format!(
+            "Jacobian column {index} is out of range for {} parameters",
+            params.len()
+        ),
+    };
+    let mut columns = bound.parameters();
+    if let Some(&index) = columns.iter().find(|&&index| index >= params.len()) {
+        return Err(out_of_range(index));
+    }
+
+    let mut entries = Vec::new();
+    bound.jacobian_entries(params, &mut entries);
+    columns.extend(entries.iter().map(|&(_, column, _)| column));
+    columns.sort_unstable();
+    columns.dedup();
+    if let Some(&index) = columns.iter().find(|&&index| index >= params.len()) {
+        return Err(out_of_range(index));
+    }
+
+    let mut numeric_entries = Vec::new();
+    finite_difference_columns(bound.as_ref(), params, &columns, &mut numeric_entries);
+    let sum = |entries: &[JacobianEntry], row: usize, index: usize| -> f64 {
+        entries
+            .iter()
+            .filter(|&&(r, column, _)| r == row && column == index)
+            .map(|&(.., value)| value)
+            .sum()
+    };
+
+    let mut check = JacobianCheck::default();
+    for index in columns {
+        for row in 0..rows {
+            let analytic = sum(&entries, row, index);
+            let numeric = sum(&numeric_entries, row, index);
+            let error = (analytic - numeric).abs() / analytic.abs().max(numeric.abs()).max(1.0);
+
+            check.max_error = check.max_error.max(error);
+            if error > JACOBIAN_TOLERANCE {
+                check.mismatches.push(JacobianMismatch {
+                    row,
+                    index,
+                    parameter: info[index].name.clone(),
+                    analytic,
+                    numeric,
+                    error,
+                });
+            }
+        }
+    }
+    check.mismatches.sort_by(|a, b| b.error.total_cmp(&a.error));
+
+    Ok(check)
+}
+
+/// A constraint whose equations follow from constraints added before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintDependency {
+    /// ID of the dependent constraint
+    pub constraint: String,
+    /// Position of the dependent constraint in the constraint list
+    pub index: usize,
+    /// IDs of the earlier constraints it follows from, in constraint order.
+    /// Together with `constraint` they form a minimal dependent set.
+    pub depends_on: Vec<String>,
+}
+
+impl ConstraintDependency {
+    /// The dependent constraint followed by the ones it depends on
+    pub fn constraints(&self) -> Vec<String> {
+        let mut ids = vec![self.constraint.clone()];
+        ids.extend(self.depends_on.iter().cloned());
+        ids
+    }
+}
+
+/// Redundant and conflicting constraints of a sketch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConstraintDiagnosis {
+    /// Constraints implied by earlier ones. Each can be removed without
+    /// changing the solution.
+    pub redundant: Vec<ConstraintDependency>,
+    /// Constraints that contradict earlier ones. Each dependency is a
+    /// minimal set that cannot be satisfied together.
+    pub conflicts: Vec<ConstraintDependency>,
+}
+
+impl ConstraintDiagnosis {
+    /// Whether no constraints are in conflict
+    pub fn is_consistent(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// IDs of every constraint taking part in a conflict, sorted
+    pub fn conflicting_constraints(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .conflicts
+            .iter()
+            .flat_map(ConstraintDependency::constraints)
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Find the constraints whose Jacobian rows are linearly dependent on the
+/// rows of the constraints before them, at the current parameter values.
+///
+/// Constraints are taken in order, so the later constraint of a dependent set
+/// is reported. A dependent constraint is redundant when its residual agrees
+/// with the combination of residuals it follows from, and conflicting
+/// otherwise. Since the check is linearized, it is most reliable after a
+/// solve, when the parameters are at a least-squares minimum. Soft
+/// constraints are left out, as they are expected to give way.
+pub fn diagnose_constraints(
+    param_manager: &ParameterManager,
+    constraint_graph: &ConstraintGraph,
+) -> Result<ConstraintDiagnosis, AcsError> {
+    let entries = constraint_graph.get_entries();
+    let bound = constraint_graph.bind_hard_constraints(param_manager)?;
+    let params = param_manager.get_parameters();
+    let is_free: Vec<bool> = param_manager
+        .get_parameter_info()
+        .iter()
+        .map(|info| !info.is_fixed)
+        .collect();
+
+    let mut dependencies = Vec::new();
+    for cluster in decomposition::find_clusters(&bound, &is_free) {
+        dependencies.extend(cluster_dependencies(
+            params,
+            &bound,
+            &cluster.constraints,
+            &cluster.parameters,
+        ));
+    }
+    dependencies.sort_by_key(|(index, ..)| *index);
+
+    let mut diagnosis = ConstraintDiagnosis::default();
+    for (index, depends_on, conflicting) in dependencies {
+        let dependency = ConstraintDependency {
+            constraint: entries[index].id.clone(),
+            index,
+            depends_on: depends_on
+                .into_iter()
+                .map(|i| entries[i].id.clone())
+                .collect(),
+        };
+        if conflicting {
+            diagnosis.conflicts.push(dependency);
+        } else {
+            diagnosis.redundant.push(dependency);
+        }
+    }
+
+    Ok(diagnosis)
+}
+
+/// Incremental rank test over the rows of one cluster. Returns, for each
+/// dependent constraint, its index, the sorted indices of the constraints
+/// its rows are combinations of, and whether its residuals disagree with
+/// theirs. Rows that are all zero are skipped rather than reported.
+fn cluster_dependencies(
+    params: &[f64],
+    bound: &[Box<dyn BoundConstraint>],
+    constraints: &[usize],
+    parameters: &[usize],
+) -> Vec<(usize, Vec<usize>, bool)> {
+    // Orthonormal basis of the accepted rows, used for the rank test
+    let mut basis: Vec<DVector<f64>> = Vec::new();
+    // The accepted rows themselves with their constraint and residual
+    let mut rows: Vec<DVector<f64>> = Vec::new();
+    let mut owners: Vec<usize> = Vec::new();
+    let mut residuals: Vec<f64> = Vec::new();
+
+    let mut dependencies = Vec::new();
+    for &index in constraints {
+        let constraint = bound[index].as_ref();
+        let num_residuals = constraint.num_residuals();
+        let jacobian = dense_jacobian(params, &[constraint], parameters, 0);
+        let mut residual = vec![0.0; num_residuals];
+        constraint.residual(params, &mut residual);
+
+        let mut depends_on = Vec::new();
+        let mut dependent = false;
+        let mut conflicting = false;
+
+        for (k, &value) in residual.iter().enumerate() {
+            let row = jacobian.row(k).transpose();
+            // A row without derivatives says nothing about dependence, e.g.
+            // at a singular point of the residual, so it is left undecided
+            if row.amax() <= RANK_TOLERANCE {
+                continue;
+            }
+
+            let mut remainder = row.clone();
+            // Project twice to keep the basis orthogonal in finite precision
+            for _ in 0..2 {
+                for q in &basis {
+                    remainder -= q * q.dot(&remainder);
+                }
+            }
+
+            let norm = remainder.norm();
+            if norm > RANK_TOLERANCE * row.norm().max(1.0) {
+                basis.push(remainder / norm);
+                rows.push(row);
+                owners.push(index);
+                residuals.push(value);
+                continue;
+            }
+
+            dependent = true;
+            let coefficients = combination(&rows, &row);
+            let predicted: f64 = coefficients
+                .iter()
+                .zip(&residuals)
+                .map(|(alpha, r)| alpha * r)
+                .sum();
+            if (value - predicted).abs() > CONSISTENCY_TOLERANCE {
+                conflicting = true;
+            }
+
+            let scale = coefficients.amax().max(1.0);
+            for (alpha, &owner) in coefficients.iter().zip(&owners) {
+                if alpha.abs() > RANK_TOLERANCE * scale && owner != index {
+                    depends_on.push(owner);
+                }
+            }
+        }
+
+        if dependent {
+            depends_on.sort_unstable();
+            depends_on.dedup();
+            dependencies.push((index, depends_on, conflicting));
+        }
+    }
+
+    dependencies
+}
+
+/// Coefficients expressing `row` as a combination of the linearly
+/// independent `rows`
+fn combination(rows: &[DVector<f64>], row: &DVector<f64>) -> DVector<f64> {
+    if rows.is_empty() {
+        return DVector::zeros(0);
+    }
+    let matrix = DMatrix::from_columns(rows);
+    matrix
+        .svd(true, true)
+        .solve(row, RANK_TOLERANCE)
+        .expect("U and V were requested from the SVD")
+}
+
+/// Dense Jacobian of some constraints with respect to the given parameters,
+/// padded with zero rows to at least `min_rows` rows
+pub(crate) fn dense_jacobian(
+    params: &[f64],
+    constraints: &[&dyn BoundConstraint],
+    parameters: &[usize],
+    min_rows: usize,
+) -> DMatrix<f64> {
+    let rows: usize = constraints.iter().map(|c| c.num_residuals()).sum();
+    let mut jacobian = DMatrix::<f64>::zeros(rows.max(min_rows), parameters.len());
+    let mut entries = Vec::new();
+
+    let mut row_offset = 0;
+    for c in constraints {
+        entries.clear();
+        c.jacobian_entries(params, &mut entries);
+        for &(row, col, value) in &entries {
+            if let Ok(column) = parameters.binary_search(&col) {
+                jacobian[(row_offset + row, column)] += value;
+            }
+        }
+        row_offset += c.num_residuals();
+    }
+
+    jacobian
+}
+
+/// Rank of a matrix with at least as many rows as columns, and an orthonormal
+/// basis of its null space as columns
+pub(crate) fn null_space(matrix: DMatrix<f64>) -> (usize, DMatrix<f64>) {
+    let n = matrix.ncols();
+    let svd = matrix.svd(false, true);
+    let threshold = RANK_TOLERANCE * svd.singular_values.max().max(1.0);
+    let v_t = svd.v_t.expect("V was requested from the SVD");
+
+    let null: Vec<usize> = (0..n)
+        .filter(|&k| svd.singular_values[k] <= threshold)
+        .collect();
+    let basis = DMatrix::from_fn(n, null.len(), |row, col| v_t[(null[col], row)]);
+
+    (n - null.len(), basis)
+}
+
+fn matrix_rank(matrix: DMatrix<f64>) -> usize {
+    if matrix.is_empty() {
+        return 0;
+    }
+    matrix.rank(1e-8)
+}
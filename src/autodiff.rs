@@ -0,0 +1,224 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::constraints::{BoundConstraint, JacobianEntry};
+
+/// Number type that residuals are written against, so the same code
+/// evaluates plain values with `f64` and derivatives with `Dual`
+pub trait Scalar:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// A value that does not depend on any parameter
+    fn constant(value: f64) -> Self;
+
+    /// The plain value, e.g. for branching
+    fn value(self) -> f64;
+
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn atan2(self, x: Self) -> Self;
+
+    fn powi(self, n: i32) -> Self;
+
+    fn abs(self) -> Self {
+        if self.value() < 0.0 { -self } else { self }
+    }
+
+    /// Clamp to `[min, max]`. The derivative is zero where clamped.
+    fn clamp(self, min: f64, max: f64) -> Self {
+        if self.value() < min {
+            Self::constant(min)
+        } else if self.value() > max {
+            Self::constant(max)
+        } else {
+            self
+        }
+    }
+}
+
+impl Scalar for f64 {
+    fn constant(value: f64) -> Self {
+        value
+    }
+
+    fn value(self) -> f64 {
+        self
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        f64::atan2(self, x)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        f64::powi(self, n)
+    }
+}
+
+/// Dual number `value + derivative·ε` with `ε² = 0`. Evaluating a function on
+/// `x + ε` gives `f(x) + f'(x)·ε`, i.e. the exact derivative along with the value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    pub fn new(value: f64, derivative: f64) -> Self {
+        Self { value, derivative }
+    }
+
+    /// The parameter being differentiated for
+    pub fn variable(value: f64) -> Self {
+        Self::new(value, 1.0)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Dual::new(self.value + rhs.value, self.derivative + rhs.derivative)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Dual::new(self.value - rhs.value, self.derivative - rhs.derivative)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Dual::new(
+            self.value * rhs.value,
+            self.derivative * rhs.value + self.value * rhs.derivative,
+        )
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Dual::new(
+            self.value / rhs.value,
+            (self.derivative * rhs.value - self.value * rhs.derivative) / (rhs.value * rhs.value),
+        )
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Dual::new(-self.value, -self.derivative)
+    }
+}
+
+impl Scalar for Dual {
+    fn constant(value: f64) -> Self {
+        Dual::new(value, 0.0)
+    }
+
+    fn value(self) -> f64 {
+        self.value
+    }
+
+    fn sqrt(self) -> Self {
+        let root = self.value.sqrt();
+        Dual::new(root, self.derivative / (2.0 * root))
+    }
+
+    fn sin(self) -> Self {
+        Dual::new(self.value.sin(), self.derivative * self.value.cos())
+    }
+
+    fn cos(self) -> Self {
+        Dual::new(self.value.cos(), -self.derivative * self.value.sin())
+    }
+
+    fn atan2(self, x: Self) -> Self {
+        let squared_norm = self.value * self.value + x.value * x.value;
+        Dual::new(
+            self.value.atan2(x.value),
+            (x.value * self.derivative - self.value * x.derivative) / squared_norm,
+        )
+    }
+
+    fn powi(self, n: i32) -> Self {
+        Dual::new(
+            self.value.powi(n),
+            self.derivative * f64::from(n) * self.value.powi(n - 1),
+        )
+    }
+}
+
+/// A bound constraint that only defines its residuals. The Jacobian is
+/// computed exactly with dual numbers, one pass per parameter, so a
+/// hand-written `BoundConstraint` is only worth it where speed matters.
+pub trait AutodiffConstraint {
+    fn num_residuals(&self) -> usize;
+
+    /// Global indices of the parameters, in the order `evaluate` gets them
+    fn parameters(&self) -> Vec<usize>;
+
+    /// Write the residuals for parameter values `x` into `out`
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]);
+}
+
+impl<T: AutodiffConstraint> BoundConstraint for T {
+    fn num_residuals(&self) -> usize {
+        AutodiffConstraint::num_residuals(self)
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        AutodiffConstraint::parameters(self)
+    }
+
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        let x: Vec<f64> = AutodiffConstraint::parameters(self)
+            .into_iter()
+            .map(|i| params[i])
+            .collect();
+        self.evaluate(&x, out);
+    }
+
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>) {
+        let indices = AutodiffConstraint::parameters(self);
+        let mut x: Vec<Dual> = indices.iter().map(|&i| Dual::constant(params[i])).collect();
+        let mut residuals = vec![Dual::constant(0.0); AutodiffConstraint::num_residuals(self)];
+
+        for (k, &index) in indices.iter().enumerate() {
+            x[k].derivative = 1.0;
+            self.evaluate(&x, &mut residuals);
+            x[k].derivative = 0.0;
+
+            for (row, residual) in residuals.iter().enumerate() {
+                if residual.derivative != 0.0 {
+                    out.push((row, index, residual.derivative));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::rc::Rc;
+
+use js_sys::{Array, Float64Array, Function, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::{
+    AcsError, BoundConstraint, Constraint, EntityRef, EntityType, JacobianEntry, ParameterManager,
+    finite_difference_jacobian,
+};
+
+/// Callbacks of a constraint type implemented in JavaScript
+pub struct JsConstraintType {
+    object: JsValue,
+    num_residuals: usize,
+    residual: Function,
+    jacobian: Option<Function>,
+}
+
+impl JsConstraintType {
+    /// Read `numResiduals`, `residual` and the optional `jacobian` from a JS object
+    pub fn from_object(object: JsValue) -> Result<Self, AcsError> {
+        let get = |key: &str| Reflect::get(&object, &JsValue::from_str(key)).ok();
+        let invalid = |message: &str| AcsError::ParseError {
+            message: format!("Invalid JavaScript constraint: {message}"),
+        };
+
+        let num_residuals = get("numResiduals")
+            .and_then(|value| value.as_f64())
+            .filter(|&n| n >= 0.0 && n.fract() == 0.0)
+            .ok_or_else(|| invalid("numResiduals must be a non-negative integer"))?
+            as usize;
+        let residual = get("residual")
+            .and_then(|value| value.dyn_into::<Function>().ok())
+            .ok_or_else(|| invalid("residual must be a function"))?;
+        let jacobian = match get("jacobian") {
+            Some(value) if value.is_function() => Some(value.unchecked_into::<Function>()),
+            Some(value) if value.is_undefined() || value.is_null() => None,
+            None => None,
+            Some(_) => return Err(invalid("jacobian must be a function when given")),
+        };
+
+        Ok(Self {
+            object,
+            num_residuals,
+            residual,
+            jacobian,
+        })
+    }
+
+    /// Call `function` as a method of the JS object with the entity
+    /// parameters and the constraint's JSON fields
+    fn call(&self, function: &Function, params: &[f64], data: &JsValue) -> Option<JsValue> {
+        let params = Float64Array::from(params);
+        match function.call2(&self.object, &params, data) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                log::error!("JavaScript constraint threw: {error:?}");
+                None
+            }
+        }
+    }
+}
+
+/// An instance of a JavaScript constraint type, as created from JSON like
+/// `{"type": "MyConstraint", "entities": ["p1", "c1"], ...}`. The callbacks
+/// get the parameters of the entities concatenated in the listed order, and
+/// the JSON fields of the constraint.
+pub struct JsConstraint {
+    type_name: String,
+    entities: Vec<String>,
+    data: serde_json::Value,
+    callbacks: Rc<JsConstraintType>,
+}
+
+impl JsConstraint {
+    pub fn new(
+        type_name: &str,
+        data: &serde_json::Value,
+        callbacks: Rc<JsConstraintType>,
+    ) -> Result<Self, AcsError> {
+        let entities = data
+            .get("entities")
+            .and_then(|entities| serde_json::from_value(entities.clone()).ok())
+            .ok_or_else(|| AcsError::ParseError {
+                message: format!("{type_name} constraint needs an \"entities\" list of IDs"),
+            })?;
+
+        Ok(Self {
+            type_name: type_name.to_string(),
+            entities,
+            data: data.clone(),
+            callbacks,
+        })
+    }
+}
+
+impl Constraint for JsConstraint {
+    fn num_residuals(&self) -> usize {
+        self.callbacks.num_residuals
+    }
+
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        let mut indices = Vec::new();
+        for entity in &self.entities {
+            indices.extend(
+                param_manager
+                    .get_entity_indices(entity)
+                    .ok_or_else(|| AcsError::unknown_entity(entity))?,
+            );
+        }
+        let data =
+            serde_wasm_bindgen::to_value(&self.data).map_err(|e| AcsError::SerializationError {
+                message: format!(
+                    "Failed to pass {} constraint to JavaScript: {e}",
+                    self.type_name
+                ),
+            })?;
+
+        Ok(Box::new(BoundJsConstraint {
+            indices,
+            data,
+            callbacks: Rc::clone(&self.callbacks),
+        }))
+    }
+
+    fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    fn references(&self) -> Vec<EntityRef> {
+        self.entities
+            .iter()
+            .map(|id| {
+                EntityRef::new(
+                    id,
+                    vec![
+                        EntityType::Point,
+                        EntityType::Circle,
+                        EntityType::Arc,
+                        EntityType::ScalarVariable,
+                    ],
+                )
+            })
+            .collect()
+    }
+}
+
+struct BoundJsConstraint {
+    indices: Vec<usize>,
+    data: JsValue,
+    callbacks: Rc<JsConstraintType>,
+}
+
+impl BoundJsConstraint {
+    fn local_params(&self, params: &[f64]) -> Vec<f64> {
+        self.indices.iter().map(|&i| params[i]).collect()
+    }
+}
+
+impl BoundConstraint for BoundJsConstraint {
+    fn num_residuals(&self) -> usize {
+        self.callbacks.num_residuals
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.clone()
+    }
+
+    /// A callback that throws or returns too few or non-numeric values
+    /// yields NaN residuals, which fail the solve with
+    /// `AcsError::NonFiniteResidual` naming the constraint
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        out.fill(f64::NAN);
+        let callbacks = &self.callbacks;
+        let Some(values) =
+            callbacks.call(&callbacks.residual, &self.local_params(params), &self.data)
+        else {
+            return;
+        };
+
+        let values = Array::from(&values);
+        for (row, slot) in out.iter_mut().enumerate() {
+            if let Some(value) = values.get(row as u32).as_f64() {
+                *slot = value;
+            }
+        }
+    }
+
+    /// Uses the `jacobian` callback, which returns one array per residual
+    /// with a derivative per parameter, or finite differences without it
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>) {
+        let callbacks = &self.callbacks;
+        let Some(jacobian) = &callbacks.jacobian else {
+            finite_difference_jacobian(self, params, out);
+            return;
+        };
+        let Some(rows) = callbacks.call(jacobian, &self.local_params(params), &self.data) else {
+            return;
+        };
+
+        let rows = Array::from(&rows);
+        for row in 0..callbacks.num_residuals {
+            let derivatives = Array::from(&rows.get(row as u32));
+            for (k, &index) in self.indices.iter().enumerate() {
+                if let Some(derivative) = derivatives.get(k as u32).as_f64()
+                    && derivative != 0.0
+                {
+                    out.push((row, index, derivative));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use wasm_bindgen::JsValue;
+use wasm_bindgen::prelude::wasm_bindgen;
+use web_sys::console;
+
+use crate::AcsError;
+
+/// Forwards log records to the browser console, at the matching console level
+struct ConsoleLogger;
+
+static CONSOLE_LOGGER: ConsoleLogger = ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = JsValue::from_str(&format!("[{}] {}", record.target(), record.args()));
+        match record.level() {
+            Level::Error => console::error_1(&message),
+            Level::Warn => console::warn_1(&message),
+            Level::Info => console::info_1(&message),
+            Level::Debug => console::log_1(&message),
+            Level::Trace => console::debug_1(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Send solver diagnostics up to `level` ("off", "error", "warn", "info",
+/// "debug" or "trace") to the browser console. Logging is off until this is
+/// called.
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), AcsError> {
+    let filter = LevelFilter::from_str(level).map_err(|_| AcsError::ParseError {
+        message: format!("Unknown log level: {level}"),
+    })?;
+
+    // Fails only when a logger is already installed, which is fine
+    let _ = log::set_logger(&CONSOLE_LOGGER);
+    log::set_max_level(filter);
+    Ok(())
+}
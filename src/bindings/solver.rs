@@ -1,15 +1,29 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use wasm_bindgen::JsValue;
 use wasm_bindgen::prelude::wasm_bindgen;
-use serde_json;
 
-use crate::ConstraintSolver;
+use crate::{
+    AcsError, Constraint, ConstraintRegistry, ConstraintSolver, Expression, ParametricDogLegSolver,
+    RemovalPolicy, SolveObserver, SolveProgress, Solver, SolverKind, SolverOptions, SolverResult,
+    TraceRecorder,
+};
+use crate::bindings::js_constraint::{JsConstraint, JsConstraintType};
 use crate::bindings::types::{
-    PrimitiveJson, ConstraintJson, SolverRequest, SolverResponse, SolverResultJson,
+    PrimitiveJson, ConstraintDiagnosisJson, ConstraintEntryJson, ConstraintResidualJson,
+    DofAnalysisJson, DragResponseJson, PointPositionJson, RemovedItemsJson, SolveProgressJson, SolveTraceJson, SolverKindJson,
+    SolverOptionsJson, SolverRequest, SolverResponse, SolverResultJson, VariableJson,
 };
 
 #[wasm_bindgen(js_name = ConstraintSolver)]
 
 pub struct WrappedConstraintSolver {
     inner: ConstraintSolver,
+    solver_kind: SolverKind,
+    options: SolverOptions,
+    trace: Option<TraceRecorder>,
+    registry: ConstraintRegistry,
 }
 
 impl Default for WrappedConstraintSolver {
@@ -24,9 +38,79 @@ impl WrappedConstraintSolver {
     pub fn new() -> Self {
         Self {
             inner: ConstraintSolver::new(),
+            solver_kind: SolverKind::default(),
+            options: SolverOptions::default(),
+            trace: None,
+            registry: ConstraintRegistry::new(),
         }
     }
 
+    /// Choose the solver implementation, `"DogLeg"` or `"LevenbergMarquardt"`.
+    /// The choice is kept across `reset` and `solve_from_json`.
+    pub fn set_solver(&mut self, kind: String) -> Result<(), AcsError> {
+        let kind: SolverKindJson = serde_json::from_value(serde_json::Value::String(kind))
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Unknown solver: {e}"),
+            })?;
+        self.solver_kind = kind.into();
+        self.inner.set_solver(self.create_solver());
+        Ok(())
+    }
+
+    /// Register a constraint type implemented in JavaScript, for prototyping.
+    /// `constraint` is an object with `numResiduals`, `residual(params, data)`
+    /// returning the residuals, and optionally `jacobian(params, data)`
+    /// returning one array of derivatives per residual; without it the
+    /// Jacobian comes from finite differences. Constraints of the type are
+    /// given as JSON with an `"entities"` list, e.g.
+    /// `{"type": "Gap", "entities": ["p1", "p2"], "gap": 2}`. `params` holds
+    /// the parameters of those entities in order, and `data` the JSON fields.
+    pub fn register_js_constraint(
+        &mut self,
+        type_name: String,
+        constraint: JsValue,
+    ) -> Result<(), AcsError> {
+        let callbacks = Rc::new(JsConstraintType::from_object(constraint)?);
+        let name = type_name.clone();
+        self.register_constraint_type(&type_name, move |data| {
+            let constraint = JsConstraint::new(&name, data, Rc::clone(&callbacks))?;
+            Ok(Box::new(constraint))
+        })
+    }
+
+    /// Record every iteration of later dog-leg solves, to be read with
+    /// `trace_json`. The Levenberg-Marquardt solver does not record traces.
+    pub fn set_trace_enabled(&mut self, enabled: bool) {
+        self.trace = enabled.then(TraceRecorder::new);
+        self.inner.set_solver(self.create_solver());
+    }
+
+    /// The trace of the last solve as JSON; empty when tracing is disabled
+    pub fn trace_json(&self) -> Result<String, AcsError> {
+        let trace = self
+            .trace
+            .as_ref()
+            .map(TraceRecorder::trace)
+            .unwrap_or_default();
+        serde_json::to_string(&SolveTraceJson::from(trace))
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize solve trace: {e}"),
+            })
+    }
+
+    /// Set solver options from JSON, e.g. `{"tolerance": 1e-8, "time_budget_ms": 16}`.
+    /// Omitted fields take their default values, and the options are kept
+    /// across `reset` and `solve_from_json`.
+    pub fn set_options_json(&mut self, json: String) -> Result<(), AcsError> {
+        let options: SolverOptionsJson = serde_json::from_str(&json)
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Failed to parse options JSON: {e}"),
+            })?;
+        self.options = options.try_into()?;
+        self.inner.set_options(self.options.clone());
+        Ok(())
+    }
+
     // TODO: Those methods are during development, they will change to be more generic
     pub fn add_point(&mut self, point: &crate::Point) -> String {
         self.inner.add_point(point.clone())
@@ -36,24 +120,45 @@ impl WrappedConstraintSolver {
         self.inner.add_circle(circle.clone())
     }
 
+    pub fn add_line(&mut self, line: &crate::Line) -> Result<String, AcsError> {
+        self.inner.add_line(line.clone())
+    }
+
+    pub fn add_scalar_variable(
+        &mut self,
+        variable: &crate::ScalarVariable,
+    ) -> Result<String, AcsError> {
+        self.inner.add_scalar_variable(variable.clone())
+    }
+
+    /// Lock or unlock one parameter of a point (`"x"`, `"y"`), circle
+    /// (`"radius"`), arc (`"radius"`, `"start_angle"`, `"end_angle"`) or
+    /// scalar variable (`"value"`)
+    pub fn set_parameter_locked(
+        &mut self,
+        id: &str,
+        parameter: &str,
+        locked: bool,
+    ) -> Result<(), AcsError> {
+        self.inner.set_parameter_locked(id, parameter, locked)
+    }
+
     pub fn add_vertical_constraint(
         &mut self,
         point_a_id: String,
         point_b_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<String, AcsError> {
         self.inner
             .add_constraint(crate::ConstraintType::Vertical(point_a_id, point_b_id))
-            .map_err(|e| e.to_string())
     }
 
     pub fn add_horizontal_constraint(
         &mut self,
         point_a_id: String,
         point_b_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<String, AcsError> {
         self.inner
             .add_constraint(crate::ConstraintType::Horizontal(point_a_id, point_b_id))
-            .map_err(|e| e.to_string())
     }
 
     pub fn add_parallel_constraint(
@@ -62,12 +167,11 @@ impl WrappedConstraintSolver {
         point_b_id: String,
         point_c_id: String,
         point_d_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<String, AcsError> {
         self.inner
             .add_constraint(crate::ConstraintType::Parallel(
                 point_a_id, point_b_id, point_c_id, point_d_id,
             ))
-            .map_err(|e| e.to_string())
     }
 
     pub fn add_point_on_line_constraint(
@@ -75,36 +179,144 @@ impl WrappedConstraintSolver {
         point_id: String,
         point_line_a_id: String,
         point_line_b_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<String, AcsError> {
         self.inner
             .add_constraint(crate::ConstraintType::PointOnLine(
                 point_id,
                 point_line_a_id,
                 point_line_b_id,
             ))
-            .map_err(|e| e.to_string())
     }
 
     pub fn add_equal_radius_constraint(
         &mut self,
         circle1_id: String,
         circle2_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<String, AcsError> {
         self.inner
             .add_constraint(crate::ConstraintType::EqualRadius(circle1_id, circle2_id))
-            .map_err(|e| e.to_string())
     }
 
-    pub fn reset(&mut self) -> Result<(), String> {
-        self.inner = ConstraintSolver::new();
+    pub fn add_vertical_line_constraint(&mut self, line_id: String) -> Result<String, AcsError> {
+        self.inner
+            .add_constraint(crate::ConstraintType::VerticalLine(line_id))
+    }
+
+    pub fn add_horizontal_line_constraint(&mut self, line_id: String) -> Result<String, AcsError> {
+        self.inner
+            .add_constraint(crate::ConstraintType::HorizontalLine(line_id))
+    }
+
+    pub fn add_parallel_lines_constraint(
+        &mut self,
+        line_a_id: String,
+        line_b_id: String,
+    ) -> Result<String, AcsError> {
+        self.inner
+            .add_constraint(crate::ConstraintType::ParallelLines(line_a_id, line_b_id))
+    }
+
+    pub fn add_point_on_line_entity_constraint(
+        &mut self,
+        point_id: String,
+        line_id: String,
+    ) -> Result<String, AcsError> {
+        self.inner
+            .add_constraint(crate::ConstraintType::PointOnLineEntity(point_id, line_id))
+    }
+
+    pub fn remove_constraint(&mut self, constraint_id: &str) -> Result<(), AcsError> {
+        self.inner.remove_constraint(constraint_id)
+    }
+
+    /// Remove a point; with `cascade` unset, fails if anything depends on it.
+    /// Returns the IDs of everything removed as JSON.
+    pub fn remove_point(&mut self, id: &str, cascade: bool) -> Result<String, AcsError> {
+        let removed = self.inner.remove_point(id, Self::removal_policy(cascade))?;
+        Self::removed_to_json(removed.into())
+    }
+
+    pub fn remove_line(&mut self, id: &str, cascade: bool) -> Result<String, AcsError> {
+        let removed = self.inner.remove_line(id, Self::removal_policy(cascade))?;
+        Self::removed_to_json(removed.into())
+    }
+
+    pub fn remove_circle(&mut self, id: &str, cascade: bool) -> Result<String, AcsError> {
+        let removed = self.inner.remove_circle(id, Self::removal_policy(cascade))?;
+        Self::removed_to_json(removed.into())
+    }
+
+    pub fn remove_arc(&mut self, id: &str, cascade: bool) -> Result<String, AcsError> {
+        let removed = self.inner.remove_arc(id, Self::removal_policy(cascade))?;
+        Self::removed_to_json(removed.into())
+    }
+
+    pub fn remove_scalar_variable(&mut self, id: &str, cascade: bool) -> Result<String, AcsError> {
+        let removed = self.inner.remove_scalar_variable(id, Self::removal_policy(cascade))?;
+        Self::removed_to_json(removed.into())
+    }
+
+    pub fn reset(&mut self) -> Result<(), AcsError> {
+        self.inner = self.empty_solver();
         Ok(())
     }
 
-    pub fn solve(&mut self) -> Result<String, String> {
-        match self.inner.solve() {
-            Ok(result) => Ok(format!("{result:?}")),
-            Err(e) => Err(e),
-        }
+    pub fn solve(&mut self) -> Result<String, AcsError> {
+        self.inner.solve().map(|result| format!("{result:?}"))
+    }
+
+    /// Drag a point to a target position, e.g. on pointer move, and return
+    /// the points that moved and the status of the re-solved clusters as
+    /// JSON. Diagnosis and residuals are left to `diagnose_constraints_json`
+    /// and `analyze_dof_json` to keep this cheap enough for every frame.
+    pub fn drag_point(&mut self, id: &str, x: f64, y: f64) -> Result<String, AcsError> {
+        let before: BTreeMap<String, (f64, f64)> = self
+            .inner
+            .get_all_points()
+            .iter()
+            .map(|(id, point)| (id.clone(), (point.x, point.y)))
+            .collect();
+
+        let solver_result = self.inner.drag_point(id, x, y)?;
+        let result = SolverResultJson::from(solver_result);
+
+        let mut points: Vec<PointPositionJson> = self
+            .inner
+            .get_all_points()
+            .iter()
+            .filter(|(id, point)| before.get(*id) != Some(&(point.x, point.y)))
+            .map(|(id, point)| PointPositionJson {
+                id: id.clone(),
+                x: point.x,
+                y: point.y,
+            })
+            .collect();
+        points.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let response = DragResponseJson {
+            points,
+            converged: result.converged,
+            clusters: result.clusters,
+        };
+        serde_json::to_string(&response).map_err(|e| AcsError::SerializationError {
+            message: format!("Failed to serialize drag response: {e}"),
+        })
+    }
+
+    /// Degrees-of-freedom analysis of the current state as JSON
+    pub fn analyze_dof_json(&self) -> Result<String, AcsError> {
+        let analysis = DofAnalysisJson::from(self.inner.analyze_dof()?);
+        serde_json::to_string(&analysis).map_err(|e| AcsError::SerializationError {
+            message: format!("Failed to serialize DOF analysis: {e}"),
+        })
+    }
+
+    /// Redundant and conflicting constraints at the current state as JSON
+    pub fn diagnose_constraints_json(&self) -> Result<String, AcsError> {
+        let diagnosis = ConstraintDiagnosisJson::from(self.inner.diagnose_constraints()?);
+        serde_json::to_string(&diagnosis).map_err(|e| AcsError::SerializationError {
+            message: format!("Failed to serialize constraint diagnosis: {e}"),
+        })
     }
 
     pub fn print_state(&self) -> String {
@@ -119,169 +331,239 @@ impl WrappedConstraintSolver {
         self.inner.get_circle(id.to_string()).cloned()
     }
 
+    pub fn get_line(&self, id: &str) -> Option<crate::Line> {
+        self.inner.get_line(id.to_string()).cloned()
+    }
+
+    /// A scalar variable, with its solved value after a solve
+    pub fn get_scalar_variable(&self, id: &str) -> Option<crate::ScalarVariable> {
+        self.inner.get_scalar_variable(id.to_string()).cloned()
+    }
+
     // JSON-based methods for generic frontend interface
-    pub fn add_primitives_json(&mut self, json: String) -> Result<String, String> {
+    pub fn add_primitives_json(&mut self, json: String) -> Result<String, AcsError> {
         let primitives: Vec<PrimitiveJson> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse primitives JSON: {e}"))?;
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Failed to parse primitives JSON: {e}"),
+            })?;
 
-        for primitive in primitives {
-            match primitive {
-                PrimitiveJson::Point { id, x, y, fixed } => {
-                    self.inner.add_point(crate::Point { id, x, y, fixed });
-                }
-                PrimitiveJson::Circle {
-                    id,
-                    center,
-                    radius,
-                    fixed,
-                } => {
-                    self.inner.add_circle(crate::Circle {
-                        id,
-                        center,
-                        radius,
-                        fixed,
-                    });
-                }
-                PrimitiveJson::Line { id, start, end } => {
-                    self.inner.add_line(crate::Line { id, start, end });
-                }
-                PrimitiveJson::Arc {
-                    id,
-                    center,
-                    radius,
-                    start_angle,
-                    end_angle,
-                    fixed,
-                } => {
-                    self.inner.add_arc(crate::Arc {
-                        id,
-                        center,
-                        radius,
-                        start_angle,
-                        end_angle,
-                        fixed,
-                    });
-                }
-            }
-        }
+        self.add_primitives(primitives)?;
 
         Ok("Primitives added successfully".to_string())
     }
 
-    pub fn add_constraints_json(&mut self, json: String) -> Result<String, String> {
-        let constraints: Vec<ConstraintJson> = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse constraints JSON: {e}"))?;
+    /// Add constraints from JSON and return their stored entries, including
+    /// the generated IDs, as JSON
+    pub fn add_constraints_json(&mut self, json: String) -> Result<String, AcsError> {
+        let constraints: Vec<ConstraintEntryJson> = serde_json::from_str(&json)
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Failed to parse constraints JSON: {e}"),
+            })?;
 
-        for constraint_json in constraints {
-            let constraint_type: crate::ConstraintType = constraint_json
-                .try_into()
-                .map_err(|e| format!("Failed to convert constraint: {e}"))?;
-            self.inner
-                .add_constraint(constraint_type)
-                .map_err(|e| format!("Failed to add constraint: {e}"))?;
-        }
+        let added = self.add_constraints(constraints)?;
+
+        serde_json::to_string(&added)
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize constraints: {e}"),
+            })
+    }
 
-        Ok("Constraints added successfully".to_string())
+    /// Define or change a design variable, e.g. `set_variable("width", "40")`,
+    /// re-solve with the updated dimensions and return the state as JSON
+    pub fn set_variable(&mut self, name: &str, expression: &str) -> Result<String, AcsError> {
+        let solver_result = self.inner.set_variable(name, Expression::parse(expression)?)?;
+        self.state_json(solver_result)
     }
 
-    pub fn solve_and_get_state_json(&mut self) -> Result<String, String> {
+    /// Design variables with their expressions and current values as JSON
+    pub fn get_variables_json(&self) -> Result<String, AcsError> {
+        serde_json::to_string(&self.variables()?)
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize variables: {e}"),
+            })
+    }
+
+    pub fn get_constraints_json(&self) -> Result<String, AcsError> {
+        let constraints: Vec<ConstraintEntryJson> = self
+            .inner
+            .get_constraint_entries()
+            .iter()
+            .cloned()
+            .map(ConstraintEntryJson::from)
+            .collect();
+
+        serde_json::to_string(&constraints)
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize constraints: {e}"),
+            })
+    }
+
+    /// Solve and return the state as JSON, calling `callback` once per
+    /// iteration with `{ cluster, iteration, residual_norm, trust_radius }`.
+    /// Returning `false` from the callback, or throwing, cancels the solve:
+    /// the geometry is left as it was and a `Cancelled` error is thrown.
+    pub fn solve_with_progress(&mut self, callback: &js_sys::Function) -> Result<String, AcsError> {
+        let mut observer = JsProgressObserver {
+            callback,
+            cancelled: false,
+        };
+        let solver_result = self.inner.solve_with_observer(&mut observer)?;
+
+        self.state_json(solver_result)
+    }
+
+    pub fn solve_and_get_state_json(&mut self) -> Result<String, AcsError> {
         // Solve first
-        let solver_result = self.inner.solve().map_err(|e| e.to_string())?;
+        let solver_result = self.inner.solve()?;
 
-        // Collect all primitives
-        let mut primitives = Vec::new();
+        self.state_json(solver_result)
+    }
 
-        // Add all points
-        for point in self.inner.get_all_points().values() {
-            primitives.push(PrimitiveJson::from(point.clone()));
-        }
+    pub fn solve_from_json(&mut self, json: String) -> Result<String, AcsError> {
+        let request: SolverRequest = serde_json::from_str(&json)
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Failed to parse request JSON: {e}"),
+            })?;
 
-        // Add all circles
-        for circle in self.inner.get_all_circles().values() {
-            primitives.push(PrimitiveJson::from(circle.clone()));
+        // Reset solver
+        let options = request.options.map(SolverOptions::try_from).transpose()?;
+        if let Some(options) = options {
+            self.options = options;
         }
-
-        // Add all lines
-        for line in self.inner.get_all_lines().values() {
-            primitives.push(PrimitiveJson::from(line.clone()));
+        if let Some(kind) = request.solver {
+            self.solver_kind = kind.into();
         }
+        self.inner = self.empty_solver();
 
-        // Add all arcs
-        for arc in self.inner.get_all_arcs().values() {
-            primitives.push(PrimitiveJson::from(arc.clone()));
+        // Define the variables before the constraints that use them
+        for (name, value) in request.variables {
+            self.inner.define_variable(&name, value.try_into()?)?;
         }
+        // Catch references to undefined variables even if no constraint uses them
+        self.inner.variables().values()?;
 
-        let response = SolverResponse {
-            primitives,
-            result: SolverResultJson::from(solver_result),
+        // Add all primitives
+        self.add_primitives(request.primitives)?;
+
+        // Add all constraints
+        self.add_constraints(request.constraints)?;
+
+        // Solve
+        let solver_result = self.inner.solve()?;
+
+        self.state_json(solver_result)
+    }
+    // Add more methods as needed
+}
+
+/// Forwards solver progress to a JavaScript callback
+struct JsProgressObserver<'a> {
+    callback: &'a js_sys::Function,
+    cancelled: bool,
+}
+
+impl SolveObserver for JsProgressObserver<'_> {
+    fn on_iteration(&mut self, progress: &SolveProgress) {
+        let Ok(progress) = serde_wasm_bindgen::to_value(&SolveProgressJson::from(progress.clone()))
+        else {
+            return;
         };
+        self.cancelled = match self.callback.call1(&JsValue::NULL, &progress) {
+            Ok(result) => result.as_bool() == Some(false),
+            Err(_) => true,
+        };
+    }
 
-        serde_json::to_string(&response)
-            .map_err(|e| format!("Failed to serialize response: {e}"))
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
     }
+}
 
-    pub fn solve_from_json(&mut self, json: String) -> Result<String, String> {
-        let request: SolverRequest = serde_json::from_str(&json)
-            .map_err(|e| format!("Failed to parse request JSON: {e}"))?;
+impl WrappedConstraintSolver {
+    /// Register a custom constraint type for JSON constraints with that
+    /// `"type"`. The factory gets the other fields of the constraint. Kept
+    /// across `reset` and `solve_from_json`. Fails for the names of built-in
+    /// types.
+    pub fn register_constraint_type<F>(
+        &mut self,
+        type_name: &str,
+        factory: F,
+    ) -> Result<(), AcsError>
+    where
+        F: Fn(&serde_json::Value) -> Result<Box<dyn Constraint>, AcsError> + 'static,
+    {
+        self.registry.register(type_name, factory)?;
+        self.inner.set_registry(self.registry.clone());
+        Ok(())
+    }
 
-        // Reset solver
-        self.inner = ConstraintSolver::new();
+    /// A solver without geometry, using the selected solver, options and
+    /// custom constraint types
+    fn empty_solver(&self) -> ConstraintSolver {
+        let mut solver = ConstraintSolver::with_solver(self.create_solver());
+        solver.set_options(self.options.clone());
+        solver.set_registry(self.registry.clone());
+        solver
+    }
 
-        // Add all primitives
-        for primitive in request.primitives {
-            match primitive {
-                PrimitiveJson::Point { id, x, y, fixed } => {
-                    self.inner.add_point(crate::Point { id, x, y, fixed });
-                }
-                PrimitiveJson::Circle {
-                    id,
-                    center,
-                    radius,
-                    fixed,
-                } => {
-                    self.inner.add_circle(crate::Circle {
-                        id,
-                        center,
-                        radius,
-                        fixed,
-                    });
-                }
-                PrimitiveJson::Line { id, start, end } => {
-                    self.inner.add_line(crate::Line { id, start, end });
-                }
-                PrimitiveJson::Arc {
-                    id,
-                    center,
-                    radius,
-                    start_angle,
-                    end_angle,
-                    fixed,
-                } => {
-                    self.inner.add_arc(crate::Arc {
-                        id,
-                        center,
-                        radius,
-                        start_angle,
-                        end_angle,
-                        fixed,
-                    });
-                }
+    /// The selected solver implementation, recording into the trace if enabled
+    fn create_solver(&self) -> Box<dyn Solver> {
+        match (self.solver_kind, &self.trace) {
+            (SolverKind::DogLeg, Some(recorder)) => {
+                Box::new(ParametricDogLegSolver::with_trace(recorder.clone()))
             }
+            (kind, _) => kind.create(),
         }
+    }
 
-        // Add all constraints
-        for constraint_json in request.constraints {
-            let constraint_type: crate::ConstraintType = constraint_json
-                .try_into()
-                .map_err(|e| format!("Failed to convert constraint: {e}"))?;
-            self.inner
-                .add_constraint(constraint_type)
-                .map_err(|e| format!("Failed to add constraint: {e}"))?;
+    fn removal_policy(cascade: bool) -> RemovalPolicy {
+        if cascade {
+            RemovalPolicy::Cascade
+        } else {
+            RemovalPolicy::Error
         }
+    }
 
-        // Solve
-        let solver_result = self.inner.solve().map_err(|e| e.to_string())?;
+    fn removed_to_json(removed: RemovedItemsJson) -> Result<String, AcsError> {
+        serde_json::to_string(&removed)
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize removed items: {e}"),
+            })
+    }
+
+    /// Add a batch of constraints. Every constraint is validated before any
+    /// of them is added, so a bad reference leaves the solver unchanged.
+    fn add_constraints(
+        &mut self,
+        constraints: Vec<ConstraintEntryJson>,
+    ) -> Result<Vec<ConstraintEntryJson>, AcsError> {
+        let mut converted = Vec::with_capacity(constraints.len());
+        for constraint_json in constraints {
+            let (constraint_type, options): (crate::ConstraintType, crate::ConstraintOptions) =
+                constraint_json.try_into()?;
+            self.inner.validate_constraint(&constraint_type, &options)?;
+            if let Some(id) = &options.id
+                && converted
+                    .iter()
+                    .any(|(_, other): &(_, crate::ConstraintOptions)| other.id.as_ref() == Some(id))
+            {
+                return Err(AcsError::DuplicateId {
+                    constraint: id.clone(),
+                });
+            }
+            converted.push((constraint_type, options));
+        }
 
+        let mut added = Vec::with_capacity(converted.len());
+        for (constraint_type, options) in converted {
+            let entry = self.inner.add_constraint_with_options(constraint_type, options)?;
+            added.push(ConstraintEntryJson::from(entry));
+        }
+        Ok(added)
+    }
+
+    /// Serialize the current primitives and constraints along with a solver result
+    fn state_json(&self, solver_result: SolverResult) -> Result<String, AcsError> {
         // Collect all primitives
         let mut primitives = Vec::new();
 
@@ -305,13 +587,93 @@ impl WrappedConstraintSolver {
             primitives.push(PrimitiveJson::from(arc.clone()));
         }
 
+        // Add all scalar variables, with their solved values
+        for variable in self.inner.get_all_scalar_variables().values() {
+            primitives.push(PrimitiveJson::from(variable.clone()));
+        }
+
+        let constraints = self
+            .inner
+            .get_constraint_entries()
+            .iter()
+            .cloned()
+            .map(ConstraintEntryJson::from)
+            .collect();
+
+        // Point the caller at the offending constraints when solving failed
+        let diagnosis = match solver_result {
+            SolverResult::Converged { .. } => None,
+            _ => Some(ConstraintDiagnosisJson::from(self.inner.diagnose_constraints()?)),
+        };
+
+        // Which hard constraints are left violated, and by how much
+        let mut result = SolverResultJson::from(solver_result);
+        let residuals = self.inner.constraint_residuals()?;
+        result.violated = residuals
+            .iter()
+            .filter(|residual| {
+                residual.priority.is_none() && residual.is_violated(self.options.tolerance)
+            })
+            .map(|residual| residual.constraint.clone())
+            .collect();
+        result.residuals = residuals.into_iter().map(ConstraintResidualJson::from).collect();
+
         let response = SolverResponse {
             primitives,
-            result: SolverResultJson::from(solver_result),
+            constraints,
+            result,
+            diagnosis,
+            variables: self.variables()?,
         };
 
         serde_json::to_string(&response)
-            .map_err(|e| format!("Failed to serialize response: {e}"))
+            .map_err(|e| AcsError::SerializationError {
+                message: format!("Failed to serialize response: {e}"),
+            })
+    }
+
+    fn variables(&self) -> Result<BTreeMap<String, VariableJson>, AcsError> {
+        let variables = self.inner.variables();
+        let values = variables.values()?;
+        Ok(variables
+            .iter()
+            .map(|(name, expression)| {
+                let variable = VariableJson {
+                    expression: expression.to_string(),
+                    value: values[name],
+                };
+                (name.to_string(), variable)
+            })
+            .collect())
+    }
+
+    /// Add primitives from their JSON form. Points are added first so that
+    /// lines can reference them regardless of their order in the input.
+    fn add_primitives(&mut self, primitives: Vec<PrimitiveJson>) -> Result<(), AcsError> {
+        let (points, others): (Vec<_>, Vec<_>) = primitives
+            .into_iter()
+            .partition(|primitive| matches!(primitive, PrimitiveJson::Point { .. }));
+
+        for primitive in points.into_iter().chain(others) {
+            match primitive {
+                PrimitiveJson::Point { .. } => {
+                    self.inner.add_point(primitive.try_into()?);
+                }
+                PrimitiveJson::Circle { .. } => {
+                    self.inner.add_circle(primitive.try_into()?);
+                }
+                PrimitiveJson::Line { .. } => {
+                    self.inner.add_line(primitive.try_into()?)?;
+                }
+                PrimitiveJson::Arc { .. } => {
+                    self.inner.add_arc(primitive.try_into()?);
+                }
+                PrimitiveJson::ScalarVariable { .. } => {
+                    self.inner.add_scalar_variable(primitive.try_into()?)?;
+                }
+            }
+        }
+
+        Ok(())
     }
-    // Add more methods as needed
 }
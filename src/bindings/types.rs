@@ -1,7 +1,20 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
-use crate::geometry::{Point, Circle, Line, Arc};
+use crate::geometry::{Point, Circle, Line, Arc, ScalarVariable};
+use crate::analysis::{
+    ConstraintDependency, ConstraintDiagnosis, ConstraintResidual, DofAnalysis, DofStatus,
+    EntityDof,
+};
 use crate::constraints::ConstraintType;
-use crate::solver::SolverResult;
+use crate::dogleg_solver::{SolveTrace, TraceStep};
+use crate::error::AcsError;
+use crate::expression::Expression;
+use crate::solver::{
+    ClusterResult, ConstraintEntry, ConstraintOptions, RemovedItems, SolveProgress, SolverKind,
+    SolverOptions, SolverResult,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -11,12 +24,18 @@ pub enum PrimitiveJson {
         x: f64,
         y: f64,
         fixed: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_x: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_y: bool,
     },
     Circle {
         id: String,
         center: String,
         radius: f64,
         fixed: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_radius: bool,
     },
     Line {
         id: String,
@@ -30,6 +49,20 @@ pub enum PrimitiveJson {
         start_angle: f64,
         end_angle: f64,
         fixed: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_radius: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_start_angle: bool,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        fixed_end_angle: bool,
+    },
+    /// A standalone unknown that constraints can reference, see
+    /// `ScalarVariable`
+    ScalarVariable {
+        id: String,
+        value: f64,
+        #[serde(default)]
+        fixed: bool,
     },
 }
 
@@ -52,11 +85,11 @@ pub enum ConstraintJson {
     },
     EqualX {
         point: String,
-        x: f64,
+        x: ValueJson,
     },
     EqualY {
         point: String,
-        y: f64,
+        y: ValueJson,
     },
     Coincident {
         point_a: String,
@@ -73,7 +106,7 @@ pub enum ConstraintJson {
     },
     FixedRadius {
         circle: String,
-        radius: f64,
+        radius: ValueJson,
     },
     PointOnCircle {
         point: String,
@@ -83,12 +116,143 @@ pub enum ConstraintJson {
         entity1: String,
         entity2: String,
     },
+    VerticalLine {
+        line: String,
+    },
+    HorizontalLine {
+        line: String,
+    },
+    ParallelLines {
+        line_a: String,
+        line_b: String,
+    },
+    PointOnLineEntity {
+        point: String,
+        line: String,
+    },
+    Distance {
+        point_a: String,
+        point_b: String,
+        distance: ValueJson,
+    },
+    VariableDistance {
+        point_a: String,
+        point_b: String,
+        variable: String,
+    },
+    /// A constraint of a type registered with the `ConstraintRegistry`; all
+    /// fields, including `"type"`
+    #[serde(untagged)]
+    Custom(serde_json::Map<String, serde_json::Value>),
+}
+
+impl ConstraintJson {
+    /// The dimension of constraints that have one, e.g. `x` of `EqualX`
+    fn value_mut(&mut self) -> Option<&mut ValueJson> {
+        match self {
+            ConstraintJson::EqualX { x: value, .. }
+            | ConstraintJson::EqualY { y: value, .. }
+            | ConstraintJson::FixedRadius { radius: value, .. }
+            | ConstraintJson::Distance { distance: value, .. } => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A dimension, either a number or an expression over the design variables
+/// such as `"width - 2*margin"`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ValueJson {
+    Number(f64),
+    Expression(String),
+}
+
+impl ValueJson {
+    fn number(self) -> Result<f64, AcsError> {
+        match self {
+            ValueJson::Number(value) => Ok(value),
+            ValueJson::Expression(source) => Err(AcsError::ParseError {
+                message: format!("Expression {source} is only allowed in constraint entries"),
+            }),
+        }
+    }
+
+    /// Take out an expression, leaving a number to be replaced by its value
+    fn take_expression(&mut self) -> Option<String> {
+        match std::mem::replace(self, ValueJson::Number(0.0)) {
+            ValueJson::Expression(source) => Some(source),
+            number => {
+                *self = number;
+                None
+            }
+        }
+    }
+}
+
+/// A design variable as reported in responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariableJson {
+    pub expression: String,
+    pub value: f64,
+}
+
+/// A constraint together with its ID and metadata. The constraint fields
+/// are flattened, so `{"type": "Vertical", "point_a": ..., "id": ...}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintEntryJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Residual weight; 1 when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    /// Makes the constraint soft, see `ConstraintOptions::priority`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    #[serde(flatten)]
+    pub constraint: ConstraintJson,
+}
+
+/// Solver implementation, as `"DogLeg"` or `"LevenbergMarquardt"`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SolverKindJson {
+    DogLeg,
+    LevenbergMarquardt,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SolverRequest {
     pub primitives: Vec<PrimitiveJson>,
-    pub constraints: Vec<ConstraintJson>,
+    pub constraints: Vec<ConstraintEntryJson>,
+    /// Solver to use; the previously selected one when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solver: Option<SolverKindJson>,
+    /// Solver options; the previously set ones when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<SolverOptionsJson>,
+    /// Design variables by name, as numbers or expressions over each other
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, ValueJson>,
+}
+
+/// Solver options. Omitted fields take their default values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolverOptionsJson {
+    pub tolerance: Option<f64>,
+    pub max_iterations: Option<usize>,
+    pub initial_trust_radius: Option<f64>,
+    pub max_trust_radius: Option<f64>,
+    pub min_trust_radius: Option<f64>,
+    pub stagnation_threshold: Option<f64>,
+    /// Wall-clock budget of a solve in milliseconds
+    pub time_budget_ms: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,15 +261,155 @@ pub struct SolverResultJson {
     pub iterations: usize,
     pub final_error: f64,
     pub initial_error: f64,
+    #[serde(default)]
+    pub clusters: Vec<ClusterResultJson>,
+    /// Residual of every constraint after the solve
+    #[serde(default)]
+    pub residuals: Vec<ConstraintResidualJson>,
+    /// IDs of the hard constraints still violated by more than the tolerance
+    #[serde(default)]
+    pub violated: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintResidualJson {
+    pub constraint: String,
+    pub index: usize,
+    pub constraint_type: String,
+    pub residuals: Vec<f64>,
+    pub norm: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterResultJson {
+    pub constraints: Vec<String>,
+    pub entities: Vec<String>,
+    pub converged: bool,
+    pub iterations: usize,
+    pub final_error: f64,
+    pub initial_error: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SolverResponse {
     pub primitives: Vec<PrimitiveJson>,
+    pub constraints: Vec<ConstraintEntryJson>,
     pub result: SolverResultJson,
+    /// Redundant and conflicting constraints, included when the solve did not converge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<ConstraintDiagnosisJson>,
+    /// Design variables with their current values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, VariableJson>,
+}
+
+/// Response of `drag_point`: only what a pointer-move handler needs to redraw
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DragResponseJson {
+    /// Points whose coordinates changed, with their new positions
+    pub points: Vec<PointPositionJson>,
+    pub converged: bool,
+    /// Status of the clusters containing the dragged point
+    pub clusters: Vec<ClusterResultJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointPositionJson {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Progress passed to the JavaScript callback of `solve_with_progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolveProgressJson {
+    pub cluster: usize,
+    pub iteration: usize,
+    pub residual_norm: f64,
+    pub trust_radius: Option<f64>,
+}
+
+/// Recorded dog-leg iterations, as returned by `trace_json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolveTraceJson {
+    pub parameter_names: Vec<String>,
+    pub steps: Vec<TraceStepJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceStepJson {
+    pub cluster: usize,
+    pub iteration: usize,
+    pub parameters: Vec<f64>,
+    pub residuals: Vec<f64>,
+    /// One of "GaussNewton", "Gradient" or "DogLeg"
+    pub step_type: String,
+    pub rho: f64,
+    pub trust_radius: f64,
+    pub reverted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DofAnalysisJson {
+    pub dof: usize,
+    pub rank: usize,
+    pub free_parameters: usize,
+    pub fully_constrained: bool,
+    pub entities: Vec<EntityDofJson>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityDofJson {
+    pub id: String,
+    pub entity_type: String,
+    pub parameters: usize,
+    pub dof: usize,
+    /// One of "free", "partially_constrained" or "fully_constrained"
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintDiagnosisJson {
+    pub consistent: bool,
+    pub redundant: Vec<ConstraintDependencyJson>,
+    pub conflicts: Vec<ConstraintDependencyJson>,
+    /// Every constraint taking part in a conflict, for highlighting
+    pub conflicting: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintDependencyJson {
+    pub constraint: String,
+    pub index: usize,
+    pub depends_on: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedItemsJson {
+    pub points: Vec<String>,
+    pub lines: Vec<String>,
+    pub circles: Vec<String>,
+    pub arcs: Vec<String>,
+    pub scalar_variables: Vec<String>,
+    pub constraints: Vec<String>,
 }
 
 // Conversion implementations: From internal types to JSON types
+impl From<RemovedItems> for RemovedItemsJson {
+    fn from(removed: RemovedItems) -> Self {
+        RemovedItemsJson {
+            points: removed.points,
+            lines: removed.lines,
+            circles: removed.circles,
+            arcs: removed.arcs,
+            scalar_variables: removed.scalar_variables,
+            constraints: removed.constraints,
+        }
+    }
+}
+
 impl From<Point> for PrimitiveJson {
     fn from(point: Point) -> Self {
         PrimitiveJson::Point {
@@ -113,6 +417,8 @@ impl From<Point> for PrimitiveJson {
             x: point.x,
             y: point.y,
             fixed: point.fixed,
+            fixed_x: point.fixed_x,
+            fixed_y: point.fixed_y,
         }
     }
 }
@@ -124,6 +430,7 @@ impl From<Circle> for PrimitiveJson {
             center: circle.center,
             radius: circle.radius,
             fixed: circle.fixed,
+            fixed_radius: circle.fixed_radius,
         }
     }
 }
@@ -147,6 +454,19 @@ impl From<Arc> for PrimitiveJson {
             start_angle: arc.start_angle,
             end_angle: arc.end_angle,
             fixed: arc.fixed,
+            fixed_radius: arc.fixed_radius,
+            fixed_start_angle: arc.fixed_start_angle,
+            fixed_end_angle: arc.fixed_end_angle,
+        }
+    }
+}
+
+impl From<ScalarVariable> for PrimitiveJson {
+    fn from(variable: ScalarVariable) -> Self {
+        PrimitiveJson::ScalarVariable {
+            id: variable.id,
+            value: variable.value,
+            fixed: variable.fixed,
         }
     }
 }
@@ -170,11 +490,11 @@ impl From<ConstraintType> for ConstraintJson {
             },
             ConstraintType::EqualX(p, x) => ConstraintJson::EqualX {
                 point: p,
-                x,
+                x: ValueJson::Number(x),
             },
             ConstraintType::EqualY(p, y) => ConstraintJson::EqualY {
                 point: p,
-                y,
+                y: ValueJson::Number(y),
             },
             ConstraintType::Coincident(p1, p2) => ConstraintJson::Coincident {
                 point_a: p1,
@@ -191,7 +511,7 @@ impl From<ConstraintType> for ConstraintJson {
             },
             ConstraintType::FixedRadius(c, r) => ConstraintJson::FixedRadius {
                 circle: c,
-                radius: r,
+                radius: ValueJson::Number(r),
             },
             ConstraintType::PointOnCircle(p, c) => ConstraintJson::PointOnCircle {
                 point: p,
@@ -201,6 +521,53 @@ impl From<ConstraintType> for ConstraintJson {
                 entity1: e1,
                 entity2: e2,
             },
+            ConstraintType::VerticalLine(l) => ConstraintJson::VerticalLine { line: l },
+            ConstraintType::HorizontalLine(l) => ConstraintJson::HorizontalLine { line: l },
+            ConstraintType::ParallelLines(l1, l2) => ConstraintJson::ParallelLines {
+                line_a: l1,
+                line_b: l2,
+            },
+            ConstraintType::PointOnLineEntity(p, l) => ConstraintJson::PointOnLineEntity {
+                point: p,
+                line: l,
+            },
+            ConstraintType::Distance(p1, p2, d) => ConstraintJson::Distance {
+                point_a: p1,
+                point_b: p2,
+                distance: ValueJson::Number(d),
+            },
+            ConstraintType::VariableDistance(p1, p2, v) => ConstraintJson::VariableDistance {
+                point_a: p1,
+                point_b: p2,
+                variable: v,
+            },
+            ConstraintType::Custom { type_name, data } => {
+                let mut fields = match data {
+                    serde_json::Value::Object(fields) => fields,
+                    _ => serde_json::Map::new(),
+                };
+                fields.insert("type".into(), serde_json::Value::String(type_name));
+                ConstraintJson::Custom(fields)
+            }
+        }
+    }
+}
+
+impl From<ConstraintEntry> for ConstraintEntryJson {
+    fn from(entry: ConstraintEntry) -> Self {
+        let mut constraint = ConstraintJson::from(entry.constraint_type);
+        if let (Some(expression), Some(value)) = (entry.expression, constraint.value_mut()) {
+            *value = ValueJson::Expression(expression.to_string());
+        }
+
+        ConstraintEntryJson {
+            id: Some(entry.id),
+            name: entry.name,
+            tags: entry.tags,
+            metadata: entry.metadata,
+            weight: (entry.weight != 1.0).then_some(entry.weight),
+            priority: entry.priority,
+            constraint,
         }
     }
 }
@@ -212,40 +579,223 @@ impl From<SolverResult> for SolverResultJson {
                 iterations,
                 final_error,
                 initial_error,
+                clusters,
             } => SolverResultJson {
                 converged: true,
                 iterations,
                 final_error,
                 initial_error,
+                clusters: clusters.into_iter().map(ClusterResultJson::from).collect(),
+                residuals: Vec::new(),
+                violated: Vec::new(),
             },
             SolverResult::MaxIterationsReached {
                 iterations,
                 final_error,
                 initial_error,
+                clusters,
             } => SolverResultJson {
                 converged: false,
                 iterations,
                 final_error,
                 initial_error,
+                clusters: clusters.into_iter().map(ClusterResultJson::from).collect(),
+                residuals: Vec::new(),
+                violated: Vec::new(),
             },
         }
     }
 }
 
+impl From<DofAnalysis> for DofAnalysisJson {
+    fn from(analysis: DofAnalysis) -> Self {
+        DofAnalysisJson {
+            dof: analysis.dof,
+            rank: analysis.rank,
+            free_parameters: analysis.free_parameters,
+            fully_constrained: analysis.is_fully_constrained(),
+            entities: analysis.entities.into_iter().map(EntityDofJson::from).collect(),
+        }
+    }
+}
+
+impl From<EntityDof> for EntityDofJson {
+    fn from(entity: EntityDof) -> Self {
+        let status = match entity.status {
+            DofStatus::Free => "free",
+            DofStatus::PartiallyConstrained => "partially_constrained",
+            DofStatus::FullyConstrained => "fully_constrained",
+        };
+
+        EntityDofJson {
+            id: entity.id,
+            entity_type: format!("{:?}", entity.entity_type),
+            parameters: entity.parameters,
+            dof: entity.dof,
+            status: status.to_string(),
+        }
+    }
+}
+
+impl TryFrom<SolverOptionsJson> for SolverOptions {
+    type Error = AcsError;
+
+    fn try_from(options: SolverOptionsJson) -> Result<Self, Self::Error> {
+        let defaults = SolverOptions::default();
+        let time_budget = options
+            .time_budget_ms
+            .map(|ms| Duration::try_from_secs_f64(ms / 1000.0))
+            .transpose()
+            .map_err(|e| AcsError::ParseError {
+                message: format!("Invalid time budget: {e}"),
+            })?;
+
+        Ok(SolverOptions {
+            tolerance: options.tolerance.unwrap_or(defaults.tolerance),
+            max_iterations: options.max_iterations.unwrap_or(defaults.max_iterations),
+            initial_trust_radius: options
+                .initial_trust_radius
+                .unwrap_or(defaults.initial_trust_radius),
+            max_trust_radius: options.max_trust_radius.unwrap_or(defaults.max_trust_radius),
+            min_trust_radius: options.min_trust_radius.unwrap_or(defaults.min_trust_radius),
+            stagnation_threshold: options
+                .stagnation_threshold
+                .unwrap_or(defaults.stagnation_threshold),
+            time_budget,
+        })
+    }
+}
+
+impl From<SolveProgress> for SolveProgressJson {
+    fn from(progress: SolveProgress) -> Self {
+        SolveProgressJson {
+            cluster: progress.cluster,
+            iteration: progress.iteration,
+            residual_norm: progress.residual_norm,
+            trust_radius: progress.trust_radius,
+        }
+    }
+}
+
+impl From<SolveTrace> for SolveTraceJson {
+    fn from(trace: SolveTrace) -> Self {
+        SolveTraceJson {
+            parameter_names: trace.parameter_names,
+            steps: trace.steps.into_iter().map(TraceStepJson::from).collect(),
+        }
+    }
+}
+
+impl From<TraceStep> for TraceStepJson {
+    fn from(step: TraceStep) -> Self {
+        TraceStepJson {
+            cluster: step.cluster,
+            iteration: step.iteration,
+            parameters: step.parameters,
+            residuals: step.residuals,
+            step_type: format!("{:?}", step.step_type),
+            rho: step.rho,
+            trust_radius: step.trust_radius,
+            reverted: step.reverted,
+        }
+    }
+}
+
+impl From<SolverKindJson> for SolverKind {
+    fn from(kind: SolverKindJson) -> Self {
+        match kind {
+            SolverKindJson::DogLeg => SolverKind::DogLeg,
+            SolverKindJson::LevenbergMarquardt => SolverKind::LevenbergMarquardt,
+        }
+    }
+}
+
+impl From<ConstraintDiagnosis> for ConstraintDiagnosisJson {
+    fn from(diagnosis: ConstraintDiagnosis) -> Self {
+        let conflicting = diagnosis.conflicting_constraints();
+        ConstraintDiagnosisJson {
+            consistent: diagnosis.is_consistent(),
+            redundant: diagnosis
+                .redundant
+                .into_iter()
+                .map(ConstraintDependencyJson::from)
+                .collect(),
+            conflicts: diagnosis
+                .conflicts
+                .into_iter()
+                .map(ConstraintDependencyJson::from)
+                .collect(),
+            conflicting,
+        }
+    }
+}
+
+impl From<ConstraintDependency> for ConstraintDependencyJson {
+    fn from(dependency: ConstraintDependency) -> Self {
+        ConstraintDependencyJson {
+            constraint: dependency.constraint,
+            index: dependency.index,
+            depends_on: dependency.depends_on,
+        }
+    }
+}
+
+impl From<ConstraintResidual> for ConstraintResidualJson {
+    fn from(residual: ConstraintResidual) -> Self {
+        ConstraintResidualJson {
+            constraint: residual.constraint,
+            index: residual.index,
+            constraint_type: residual.constraint_type,
+            residuals: residual.residuals,
+            norm: residual.norm,
+            priority: residual.priority,
+        }
+    }
+}
+
+impl From<ClusterResult> for ClusterResultJson {
+    fn from(cluster: ClusterResult) -> Self {
+        ClusterResultJson {
+            constraints: cluster.constraints,
+            entities: cluster.entities,
+            converged: cluster.converged,
+            iterations: cluster.iterations,
+            final_error: cluster.final_error,
+            initial_error: cluster.initial_error,
+        }
+    }
+}
+
 // Conversion implementations: From JSON types to internal types
 impl TryFrom<PrimitiveJson> for Point {
-    type Error = String;
+    type Error = AcsError;
 
     fn try_from(primitive: PrimitiveJson) -> Result<Self, Self::Error> {
         match primitive {
-            PrimitiveJson::Point { id, x, y, fixed } => Ok(Point { id, x, y, fixed }),
-            _ => Err("Expected Point primitive".to_string()),
+            PrimitiveJson::Point {
+                id,
+                x,
+                y,
+                fixed,
+                fixed_x,
+                fixed_y,
+            } => Ok(Point {
+                id,
+                x,
+                y,
+                fixed,
+                fixed_x,
+                fixed_y,
+            }),
+            _ => Err(AcsError::ParseError {
+                message: "Expected Point primitive".to_string(),
+            }),
         }
     }
 }
 
 impl TryFrom<PrimitiveJson> for Circle {
-    type Error = String;
+    type Error = AcsError;
 
     fn try_from(primitive: PrimitiveJson) -> Result<Self, Self::Error> {
         match primitive {
@@ -254,30 +804,36 @@ impl TryFrom<PrimitiveJson> for Circle {
                 center,
                 radius,
                 fixed,
+                fixed_radius,
             } => Ok(Circle {
                 id,
                 center,
                 radius,
                 fixed,
+                fixed_radius,
+            }),
+            _ => Err(AcsError::ParseError {
+                message: "Expected Circle primitive".to_string(),
             }),
-            _ => Err("Expected Circle primitive".to_string()),
         }
     }
 }
 
 impl TryFrom<PrimitiveJson> for Line {
-    type Error = String;
+    type Error = AcsError;
 
     fn try_from(primitive: PrimitiveJson) -> Result<Self, Self::Error> {
         match primitive {
             PrimitiveJson::Line { id, start, end } => Ok(Line { id, start, end }),
-            _ => Err("Expected Line primitive".to_string()),
+            _ => Err(AcsError::ParseError {
+                message: "Expected Line primitive".to_string(),
+            }),
         }
     }
 }
 
 impl TryFrom<PrimitiveJson> for Arc {
-    type Error = String;
+    type Error = AcsError;
 
     fn try_from(primitive: PrimitiveJson) -> Result<Self, Self::Error> {
         match primitive {
@@ -288,6 +844,9 @@ impl TryFrom<PrimitiveJson> for Arc {
                 start_angle,
                 end_angle,
                 fixed,
+                fixed_radius,
+                fixed_start_angle,
+                fixed_end_angle,
             } => Ok(Arc {
                 id,
                 center,
@@ -295,14 +854,68 @@ impl TryFrom<PrimitiveJson> for Arc {
                 start_angle,
                 end_angle,
                 fixed,
+                fixed_radius,
+                fixed_start_angle,
+                fixed_end_angle,
+            }),
+            _ => Err(AcsError::ParseError {
+                message: "Expected Arc primitive".to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<PrimitiveJson> for ScalarVariable {
+    type Error = AcsError;
+
+    fn try_from(primitive: PrimitiveJson) -> Result<Self, Self::Error> {
+        match primitive {
+            PrimitiveJson::ScalarVariable { id, value, fixed } => {
+                Ok(ScalarVariable { id, value, fixed })
+            }
+            _ => Err(AcsError::ParseError {
+                message: "Expected ScalarVariable primitive".to_string(),
             }),
-            _ => Err("Expected Arc primitive".to_string()),
+        }
+    }
+}
+
+impl TryFrom<ConstraintEntryJson> for (ConstraintType, ConstraintOptions) {
+    type Error = AcsError;
+
+    fn try_from(entry: ConstraintEntryJson) -> Result<Self, Self::Error> {
+        let mut constraint = entry.constraint;
+        let expression = constraint
+            .value_mut()
+            .and_then(ValueJson::take_expression)
+            .map(|source| Expression::parse(&source))
+            .transpose()?;
+        let options = ConstraintOptions {
+            id: entry.id,
+            name: entry.name,
+            tags: entry.tags,
+            metadata: entry.metadata,
+            weight: entry.weight.unwrap_or(1.0),
+            priority: entry.priority,
+            expression,
+        };
+        Ok((constraint.try_into()?, options))
+    }
+}
+
+impl TryFrom<ValueJson> for Expression {
+    type Error = AcsError;
+
+    fn try_from(value: ValueJson) -> Result<Self, Self::Error> {
+        match value {
+            ValueJson::Number(value) => Ok(Expression::number(value)),
+            ValueJson::Expression(source) => Expression::parse(&source),
         }
     }
 }
 
 impl TryFrom<ConstraintJson> for ConstraintType {
-    type Error = String;
+    type Error = AcsError;
 
     fn try_from(constraint: ConstraintJson) -> Result<Self, Self::Error> {
         match constraint {
@@ -318,8 +931,8 @@ impl TryFrom<ConstraintJson> for ConstraintType {
                 point_c,
                 point_d,
             } => Ok(ConstraintType::Parallel(point_a, point_b, point_c, point_d)),
-            ConstraintJson::EqualX { point, x } => Ok(ConstraintType::EqualX(point, x)),
-            ConstraintJson::EqualY { point, y } => Ok(ConstraintType::EqualY(point, y)),
+            ConstraintJson::EqualX { point, x } => Ok(ConstraintType::EqualX(point, x.number()?)),
+            ConstraintJson::EqualY { point, y } => Ok(ConstraintType::EqualY(point, y.number()?)),
             ConstraintJson::Coincident { point_a, point_b } => {
                 Ok(ConstraintType::Coincident(point_a, point_b))
             }
@@ -332,7 +945,7 @@ impl TryFrom<ConstraintJson> for ConstraintType {
                 Ok(ConstraintType::EqualRadius(circle1, circle2))
             }
             ConstraintJson::FixedRadius { circle, radius } => {
-                Ok(ConstraintType::FixedRadius(circle, radius))
+                Ok(ConstraintType::FixedRadius(circle, radius.number()?))
             }
             ConstraintJson::PointOnCircle { point, circle } => {
                 Ok(ConstraintType::PointOnCircle(point, circle))
@@ -340,6 +953,45 @@ impl TryFrom<ConstraintJson> for ConstraintType {
             ConstraintJson::Tangent { entity1, entity2 } => {
                 Ok(ConstraintType::Tangent(entity1, entity2))
             }
+            ConstraintJson::VerticalLine { line } => Ok(ConstraintType::VerticalLine(line)),
+            ConstraintJson::HorizontalLine { line } => Ok(ConstraintType::HorizontalLine(line)),
+            ConstraintJson::ParallelLines { line_a, line_b } => {
+                Ok(ConstraintType::ParallelLines(line_a, line_b))
+            }
+            ConstraintJson::PointOnLineEntity { point, line } => {
+                Ok(ConstraintType::PointOnLineEntity(point, line))
+            }
+            ConstraintJson::Distance {
+                point_a,
+                point_b,
+                distance,
+            } => Ok(ConstraintType::Distance(point_a, point_b, distance.number()?)),
+            ConstraintJson::VariableDistance {
+                point_a,
+                point_b,
+                variable,
+            } => Ok(ConstraintType::VariableDistance(point_a, point_b, variable)),
+            ConstraintJson::Custom(mut fields) => {
+                let type_name = match fields.remove("type") {
+                    Some(serde_json::Value::String(type_name)) => type_name,
+                    _ => {
+                        return Err(AcsError::ParseError {
+                            message: "Constraint is missing its \"type\"".into(),
+                        });
+                    }
+                };
+                // Built-in types only end up here when their fields are wrong
+                if ConstraintType::BUILT_IN_NAMES.contains(&type_name.as_str()) {
+                    return Err(AcsError::ParseError {
+                        message: format!("Invalid fields for {type_name} constraint"),
+                    });
+                }
+
+                Ok(ConstraintType::Custom {
+                    type_name,
+                    data: serde_json::Value::Object(fields),
+                })
+            }
         }
     }
 }
@@ -1,11 +1,158 @@
 use nalgebra::{DMatrix, DVector};
 
-use crate::ParameterManager;
+use crate::{AcsError, EntityType, GeometrySystem, ParameterManager};
+
+/// A non-zero Jacobian entry as `(row, column, value)`, where `row` is the
+/// residual index within the constraint and `column` the global parameter index
+pub type JacobianEntry = (usize, usize, f64);
 
 pub trait Constraint {
     fn num_residuals(&self) -> usize;
-    fn residual(&self, _param_manager: &ParameterManager) -> DVector<f64>;
-    fn jacobian(&self, _param_manager: &ParameterManager) -> DMatrix<f64>;
+
+    /// Resolve the entity IDs this constraint references to global parameter
+    /// indices. Done once per solve; the bound constraint is then evaluated
+    /// against the raw parameter vector.
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError>;
+
+    /// Name of the constraint type, reported in results and JSON
+    fn type_name(&self) -> &str {
+        "Custom"
+    }
+
+    /// Entities the constraint references. Custom constraints list them here
+    /// so they are validated and removed along with their entities; built-in
+    /// ones are described by their `ConstraintType` instead.
+    fn references(&self) -> Vec<EntityRef> {
+        Vec::new()
+    }
+
+    /// Residuals at the current parameter values. Fails like `bind` when a
+    /// referenced entity is missing.
+    fn residual(&self, param_manager: &ParameterManager) -> Result<DVector<f64>, AcsError> {
+        let bound = self.bind(param_manager)?;
+        let mut residuals = DVector::<f64>::zeros(self.num_residuals());
+        bound.residual(param_manager.get_parameters(), residuals.as_mut_slice());
+        Ok(residuals)
+    }
+
+    /// The non-zero entries of the Jacobian. Entries with the same row and
+    /// column are summed.
+    fn jacobian_entries(
+        &self,
+        param_manager: &ParameterManager,
+    ) -> Result<Vec<JacobianEntry>, AcsError> {
+        let bound = self.bind(param_manager)?;
+        let mut entries = Vec::new();
+        bound.jacobian_entries(param_manager.get_parameters(), &mut entries);
+        Ok(entries)
+    }
+
+    /// Dense Jacobian of width `num_parameters()`, assembled from `jacobian_entries`
+    fn jacobian(&self, param_manager: &ParameterManager) -> Result<DMatrix<f64>, AcsError> {
+        let mut jacobian =
+            DMatrix::<f64>::zeros(self.num_residuals(), param_manager.num_parameters());
+        for (row, col, value) in self.jacobian_entries(param_manager)? {
+            jacobian[(row, col)] += value;
+        }
+        Ok(jacobian)
+    }
+}
+
+/// A constraint with its entity references resolved to global parameter
+/// indices, evaluated directly against the parameter vector
+pub trait BoundConstraint {
+    fn num_residuals(&self) -> usize;
+
+    /// Global indices of the parameters the residuals depend on
+    fn parameters(&self) -> Vec<usize>;
+
+    /// Write the residuals into `out`, which has length `num_residuals()`
+    fn residual(&self, params: &[f64], out: &mut [f64]);
+
+    /// Append the non-zero Jacobian entries, with rows relative to this constraint
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>);
+}
+
+/// Jacobian entries of a bound constraint from central finite differences of
+/// its residuals, for constraints without analytic derivatives. Costs two
+/// residual evaluations per parameter.
+pub fn finite_difference_jacobian(
+    constraint: &dyn BoundConstraint,
+    params: &[f64],
+    out: &mut Vec<JacobianEntry>,
+) {
+    finite_difference_columns(constraint, params, &constraint.parameters(), out);
+}
+
+/// Like `finite_difference_jacobian`, but only for the given parameter
+/// indices, which must be in range
+pub(crate) fn finite_difference_columns(
+    constraint: &dyn BoundConstraint,
+    params: &[f64],
+    columns: &[usize],
+    out: &mut Vec<JacobianEntry>,
+) {
+    let rows = constraint.num_residuals();
+    let mut perturbed = params.to_vec();
+    let (mut plus, mut minus) = (vec![0.0; rows], vec![0.0; rows]);
+
+    for &index in columns {
+        let h = 1e-6 * params[index].abs().max(1.0);
+        perturbed[index] = params[index] + h;
+        constraint.residual(&perturbed, &mut plus);
+        perturbed[index] = params[index] - h;
+        constraint.residual(&perturbed, &mut minus);
+        perturbed[index] = params[index];
+
+        for row in 0..rows {
+            let derivative = (plus[row] - minus[row]) / (2.0 * h);
+            if derivative != 0.0 {
+                out.push((row, index, derivative));
+            }
+        }
+    }
+}
+
+/// A reference from a constraint to an entity, along with the entity types
+/// the constraint can work with
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityRef {
+    pub id: String,
+    pub accepts: Vec<EntityType>,
+}
+
+impl EntityRef {
+    pub fn new(id: &str, accepts: Vec<EntityType>) -> Self {
+        Self {
+            id: id.to_string(),
+            accepts,
+        }
+    }
+
+    pub fn point(id: &str) -> Self {
+        Self::new(id, vec![EntityType::Point])
+    }
+
+    pub fn line(id: &str) -> Self {
+        Self::new(id, vec![EntityType::Line])
+    }
+
+    /// A circle or an arc, i.e. anything with a radius
+    pub fn round(id: &str) -> Self {
+        Self::new(id, vec![EntityType::Circle, EntityType::Arc])
+    }
+
+    pub fn scalar_variable(id: &str) -> Self {
+        Self::new(id, vec![EntityType::ScalarVariable])
+    }
+
+    /// A line, circle or arc
+    pub fn curve(id: &str) -> Self {
+        Self::new(
+            id,
+            vec![EntityType::Line, EntityType::Circle, EntityType::Arc],
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -21,9 +168,172 @@ pub enum ConstraintType {
     FixedRadius(String, f64),                 // Circle/Arc ID, radius value
     PointOnCircle(String, String),            // Point ID, Circle ID
     Tangent(String, String),                  // Circle IDs (or Circle-Line)
+    VerticalLine(String),                     // Line ID
+    HorizontalLine(String),                   // Line ID
+    ParallelLines(String, String),            // Line IDs
+    PointOnLineEntity(String, String),        // Point ID, Line ID
+    Distance(String, String, f64),            // Point IDs, distance
+    VariableDistance(String, String, String), // Point IDs, scalar variable ID
+    /// A user-defined constraint, created by the `ConstraintRegistry` factory
+    /// registered for `type_name` from `data`
+    Custom {
+        type_name: String,
+        data: serde_json::Value,
+    },
 }
 
-pub fn create_constraint(constraint_type: ConstraintType) -> Result<Box<dyn Constraint>, String> {
+/// Generates `ConstraintType::BUILT_IN_NAMES` and `ConstraintType::name` from
+/// one list of the built-in variants, so the two cannot drift apart. The
+/// match in `name` is exhaustive, so a new variant must be added here.
+macro_rules! built_in_constraints {
+    ($($variant:ident),* $(,)?) => {
+        impl ConstraintType {
+            /// The `"type"` names of the built-in constraints in JSON
+            pub const BUILT_IN_NAMES: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+            /// Name of the constraint variant, e.g. `"Vertical"`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(ConstraintType::$variant(..) => stringify!($variant),)*
+                    ConstraintType::Custom { .. } => "Custom",
+                }
+            }
+        }
+    };
+}
+
+built_in_constraints!(
+    Vertical,
+    Horizontal,
+    Parallel,
+    EqualX,
+    EqualY,
+    Coincident,
+    PointOnLine,
+    EqualRadius,
+    FixedRadius,
+    PointOnCircle,
+    Tangent,
+    VerticalLine,
+    HorizontalLine,
+    ParallelLines,
+    PointOnLineEntity,
+    Distance,
+    VariableDistance,
+);
+
+impl ConstraintType {
+    /// Like `name`, but the registered type name for custom constraints
+    pub fn type_name(&self) -> &str {
+        match self {
+            ConstraintType::Custom { type_name, .. } => type_name,
+            other => other.name(),
+        }
+    }
+
+    /// Every entity referenced by this constraint, with the types it may have.
+    /// Custom constraints report theirs through `Constraint::references`.
+    pub fn references(&self) -> Vec<EntityRef> {
+        match self {
+            ConstraintType::Vertical(a, b)
+            | ConstraintType::Horizontal(a, b)
+            | ConstraintType::Coincident(a, b)
+            | ConstraintType::Distance(a, b, _) => vec![EntityRef::point(a), EntityRef::point(b)],
+            ConstraintType::Parallel(a, b, c, d) => vec![
+                EntityRef::point(a),
+                EntityRef::point(b),
+                EntityRef::point(c),
+                EntityRef::point(d),
+            ],
+            ConstraintType::EqualX(p, _) | ConstraintType::EqualY(p, _) => {
+                vec![EntityRef::point(p)]
+            }
+            ConstraintType::PointOnLine(p, a, b) => {
+                vec![
+                    EntityRef::point(p),
+                    EntityRef::point(a),
+                    EntityRef::point(b),
+                ]
+            }
+            ConstraintType::EqualRadius(c1, c2) => {
+                vec![EntityRef::round(c1), EntityRef::round(c2)]
+            }
+            ConstraintType::FixedRadius(c, _) => vec![EntityRef::round(c)],
+            ConstraintType::PointOnCircle(p, c) => vec![EntityRef::point(p), EntityRef::round(c)],
+            ConstraintType::Tangent(e1, e2) => vec![EntityRef::curve(e1), EntityRef::curve(e2)],
+            ConstraintType::VerticalLine(l) | ConstraintType::HorizontalLine(l) => {
+                vec![EntityRef::line(l)]
+            }
+            ConstraintType::ParallelLines(l1, l2) => vec![EntityRef::line(l1), EntityRef::line(l2)],
+            ConstraintType::PointOnLineEntity(p, l) => {
+                vec![EntityRef::point(p), EntityRef::line(l)]
+            }
+            ConstraintType::VariableDistance(a, b, v) => vec![
+                EntityRef::point(a),
+                EntityRef::point(b),
+                EntityRef::scalar_variable(v),
+            ],
+            ConstraintType::Custom { .. } => Vec::new(),
+        }
+    }
+
+    /// The dimension of constraints that have one, e.g. the x of `EqualX`
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            ConstraintType::EqualX(_, value)
+            | ConstraintType::EqualY(_, value)
+            | ConstraintType::FixedRadius(_, value)
+            | ConstraintType::Distance(_, _, value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The same constraint with its dimension replaced, or `None` if it
+    /// has no dimension
+    pub fn with_value(mut self, value: f64) -> Option<ConstraintType> {
+        match &mut self {
+            ConstraintType::EqualX(_, current)
+            | ConstraintType::EqualY(_, current)
+            | ConstraintType::FixedRadius(_, current)
+            | ConstraintType::Distance(_, _, current) => *current = value,
+            _ => return None,
+        }
+        Some(self)
+    }
+
+    /// Replace line references with the IDs of the line endpoints, so the
+    /// constraint can be built from its point-based counterpart
+    pub fn resolve_lines(self, geometry: &GeometrySystem) -> Result<ConstraintType, AcsError> {
+        match self {
+            ConstraintType::VerticalLine(line) => {
+                let (start, end) = geometry.get_line_endpoints(&line)?;
+                Ok(ConstraintType::Vertical(start, end))
+            }
+            ConstraintType::HorizontalLine(line) => {
+                let (start, end) = geometry.get_line_endpoints(&line)?;
+                Ok(ConstraintType::Horizontal(start, end))
+            }
+            ConstraintType::ParallelLines(line_a, line_b) => {
+                let (a_start, a_end) = geometry.get_line_endpoints(&line_a)?;
+                let (b_start, b_end) = geometry.get_line_endpoints(&line_b)?;
+                Ok(ConstraintType::Parallel(a_start, a_end, b_start, b_end))
+            }
+            ConstraintType::PointOnLineEntity(point, line) => {
+                let (start, end) = geometry.get_line_endpoints(&line)?;
+                Ok(ConstraintType::PointOnLine(point, start, end))
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+pub fn create_constraint(constraint_type: ConstraintType) -> Result<Box<dyn Constraint>, AcsError> {
+    let name = constraint_type.name();
+    let unsupported = |message: &str| AcsError::UnsupportedConstraint {
+        constraint: name.to_string(),
+        message: message.to_string(),
+    };
+
     match constraint_type {
         ConstraintType::Vertical(p1, p2) => Ok(Box::new(
             crate::constraints::vertical::VerticalConstraint::new(p1, p2),
@@ -49,17 +359,35 @@ pub fn create_constraint(constraint_type: ConstraintType) -> Result<Box<dyn Cons
         ConstraintType::EqualRadius(_c1, _c2) => Ok(Box::new(
             crate::constraints::equal_radius::EqualRadiusConstraint::new(_c1, _c2),
         )),
+        ConstraintType::Distance(_, _, distance) if distance < 0.0 => Err(unsupported(&format!(
+            "Distance must not be negative, got {distance}"
+        ))),
+        ConstraintType::Distance(p1, p2, distance) => Ok(Box::new(
+            crate::constraints::distance::DistanceConstraint::new(p1, p2, distance),
+        )),
+        ConstraintType::VariableDistance(p1, p2, variable) => Ok(Box::new(
+            crate::constraints::distance::VariableDistanceConstraint::new(p1, p2, variable),
+        )),
         ConstraintType::FixedRadius(_c1, _radius) => {
             // TODO: Implement FixedRadiusConstraint
-            Err("FixedRadius constraint not yet implemented".to_string())
+            Err(unsupported("FixedRadius constraint not yet implemented"))
         }
         ConstraintType::PointOnCircle(_p1, _c1) => {
             // TODO: Implement PointOnCircleConstraint
-            Err("PointOnCircle constraint not yet implemented".to_string())
+            Err(unsupported("PointOnCircle constraint not yet implemented"))
         }
         ConstraintType::Tangent(_e1, _e2) => {
             // TODO: Implement TangentConstraint
-            Err("Tangent constraint not yet implemented".to_string())
+            Err(unsupported("Tangent constraint not yet implemented"))
         }
+        ConstraintType::VerticalLine(_)
+        | ConstraintType::HorizontalLine(_)
+        | ConstraintType::ParallelLines(_, _)
+        | ConstraintType::PointOnLineEntity(_, _) => Err(unsupported(
+            "Line constraints must be resolved with ConstraintType::resolve_lines",
+        )),
+        ConstraintType::Custom { .. } => Err(unsupported(
+            "Custom constraints are created by a ConstraintRegistry",
+        )),
     }
 }
@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct CoincidentConstraint {
     pub p1: String, // Index of the first point
@@ -21,53 +22,44 @@ impl Constraint for CoincidentConstraint {
         2
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get the parameter indices for both points
-        let p1_x_idx = param_manager
-            .get_global_index(&self.p1, 0)
-            .expect("Point 1 not found");
-        let p1_y_idx = param_manager
-            .get_global_index(&self.p1, 1)
-            .expect("Point 1 not found");
-        let p2_x_idx = param_manager
-            .get_global_index(&self.p2, 0)
-            .expect("Point 2 not found");
-        let p2_y_idx = param_manager
-            .get_global_index(&self.p2, 1)
-            .expect("Point 2 not found");
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundCoincident {
+            p1_x: param_manager.resolve_index(&self.p1, 0)?,
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            p2_x: param_manager.resolve_index(&self.p2, 0)?,
+            p2_y: param_manager.resolve_index(&self.p2, 1)?,
+        }))
+    }
+}
 
-        let params = param_manager.get_parameters();
-        let x1 = params[p1_x_idx];
-        let y1 = params[p1_y_idx];
-        let x2 = params[p2_x_idx];
-        let y2 = params[p2_y_idx];
+struct BoundCoincident {
+    p1_x: usize,
+    p1_y: usize,
+    p2_x: usize,
+    p2_y: usize,
+}
 
-        DVector::from(vec![
-            x1 - x2, // Residual for x-coordinates
-            y1 - y2, // Residual for y-coordinates
-        ])
+impl BoundConstraint for BoundCoincident {
+    fn num_residuals(&self) -> usize {
+        2
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(2, total_params);
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_x, self.p1_y, self.p2_x, self.p2_y]
+    }
 
-        // Get the parameter indices for both points
-        if let (Some(p1_x_idx), Some(p1_y_idx), Some(p2_x_idx), Some(p2_y_idx)) = (
-            param_manager.get_global_index(&self.p1, 0),
-            param_manager.get_global_index(&self.p1, 1),
-            param_manager.get_global_index(&self.p2, 0),
-            param_manager.get_global_index(&self.p2, 1),
-        ) {
-            // Row 0: X residual derivatives
-            J[(0, p1_x_idx)] = 1.0; // d(x1-x2)/dx1
-            J[(0, p2_x_idx)] = -1.0; // d(x1-x2)/dx2
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        out[0] = params[self.p1_x] - params[self.p2_x]; // Residual for x-coordinates
+        out[1] = params[self.p1_y] - params[self.p2_y]; // Residual for y-coordinates
+    }
 
-            // Row 1: Y residual derivatives
-            J[(1, p1_y_idx)] = 1.0; // d(y1-y2)/dy1
-            J[(1, p2_y_idx)] = -1.0; // d(y1-y2)/dy2
-        }
+    fn jacobian_entries(&self, _params: &[f64], J: &mut Vec<JacobianEntry>) {
+        // Row 0: X residual derivatives
+        J.push((0, self.p1_x, 1.0)); // d(x1-x2)/dx1
+        J.push((0, self.p2_x, -1.0)); // d(x1-x2)/dx2
 
-        J
+        // Row 1: Y residual derivatives
+        J.push((1, self.p1_y, 1.0)); // d(y1-y2)/dy1
+        J.push((1, self.p2_y, -1.0)); // d(y1-y2)/dy2
     }
 }
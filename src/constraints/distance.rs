@@ -0,0 +1,120 @@
+use crate::{
+    AcsError, AutodiffConstraint, ParameterManager, Scalar,
+    constraints::{BoundConstraint, Constraint},
+};
+
+pub struct DistanceConstraint {
+    pub p1: String,
+    pub p2: String,
+    pub distance: f64, // Required distance between the points
+}
+
+impl DistanceConstraint {
+    pub fn new(p1: String, p2: String, distance: f64) -> Self {
+        Self { p1, p2, distance }
+    }
+}
+
+impl Constraint for DistanceConstraint {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundDistance {
+            p1_x: param_manager.resolve_index(&self.p1, 0)?,
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            p2_x: param_manager.resolve_index(&self.p2, 0)?,
+            p2_y: param_manager.resolve_index(&self.p2, 1)?,
+            distance: self.distance,
+        }))
+    }
+}
+
+/// Distance between two points equal to a scalar variable, which the
+/// solver determines along with the points
+pub struct VariableDistanceConstraint {
+    pub p1: String,
+    pub p2: String,
+    pub variable: String, // ID of the scalar variable holding the distance
+}
+
+impl VariableDistanceConstraint {
+    pub fn new(p1: String, p2: String, variable: String) -> Self {
+        Self { p1, p2, variable }
+    }
+}
+
+impl Constraint for VariableDistanceConstraint {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundVariableDistance {
+            p1_x: param_manager.resolve_index(&self.p1, 0)?,
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            p2_x: param_manager.resolve_index(&self.p2, 0)?,
+            p2_y: param_manager.resolve_index(&self.p2, 1)?,
+            distance: param_manager.resolve_index(&self.variable, 0)?,
+        }))
+    }
+}
+
+/// Length of the segment `x[0..4]` minus `distance`
+fn distance_residual<S: Scalar>(x: &[S], distance: S) -> S {
+    let dx = x[2] - x[0];
+    let dy = x[3] - x[1];
+    let length_squared = dx * dx + dy * dy;
+
+    // The length has no derivative where the points coincide
+    if length_squared.value() < 1e-24 {
+        -distance
+    } else {
+        length_squared.sqrt() - distance
+    }
+}
+
+struct BoundDistance {
+    p1_x: usize,
+    p1_y: usize,
+    p2_x: usize,
+    p2_y: usize,
+    distance: f64,
+}
+
+impl AutodiffConstraint for BoundDistance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_x, self.p1_y, self.p2_x, self.p2_y]
+    }
+
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]) {
+        out[0] = distance_residual(x, S::constant(self.distance));
+    }
+}
+
+struct BoundVariableDistance {
+    p1_x: usize,
+    p1_y: usize,
+    p2_x: usize,
+    p2_y: usize,
+    distance: usize,
+}
+
+impl AutodiffConstraint for BoundVariableDistance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_x, self.p1_y, self.p2_x, self.p2_y, self.distance]
+    }
+
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]) {
+        out[0] = distance_residual(x, x[4]);
+    }
+}
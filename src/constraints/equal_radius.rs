@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct EqualRadiusConstraint {
     pub circle1_id: String, // ID of the first circle
@@ -24,37 +25,36 @@ impl Constraint for EqualRadiusConstraint {
         1
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get radius parameters of both circles
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
         // Circles now have parameters [radius] with radius at index 0
-        let c1_radius_idx = param_manager
-            .get_global_index(&self.circle1_id, 0)
-            .expect("Circle 1 not found in parameter manager");
-        let c2_radius_idx = param_manager
-            .get_global_index(&self.circle2_id, 0)
-            .expect("Circle 2 not found in parameter manager");
+        Ok(Box::new(BoundEqualRadius {
+            c1_radius: param_manager.resolve_index(&self.circle1_id, 0)?,
+            c2_radius: param_manager.resolve_index(&self.circle2_id, 0)?,
+        }))
+    }
+}
 
-        let params = param_manager.get_parameters();
-        let c1_radius = params[c1_radius_idx];
-        let c2_radius = params[c2_radius_idx];
+struct BoundEqualRadius {
+    c1_radius: usize,
+    c2_radius: usize,
+}
 
-        // Residual: radius1 - radius2 = 0
-        DVector::from(vec![c1_radius - c2_radius])
+impl BoundConstraint for BoundEqualRadius {
+    fn num_residuals(&self) -> usize {
+        1
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
-
-        // Get global indices for radius parameters
-        if let Some(c1_radius_idx) = param_manager.get_global_index(&self.circle1_id, 0) {
-            J[(0, c1_radius_idx)] = 1.0; // derivative wrt circle1.radius
-        }
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.c1_radius, self.c2_radius]
+    }
 
-        if let Some(c2_radius_idx) = param_manager.get_global_index(&self.circle2_id, 0) {
-            J[(0, c2_radius_idx)] = -1.0; // derivative wrt circle2.radius
-        }
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        // Residual: radius1 - radius2 = 0
+        out[0] = params[self.c1_radius] - params[self.c2_radius];
+    }
 
-        J
+    fn jacobian_entries(&self, _params: &[f64], J: &mut Vec<JacobianEntry>) {
+        J.push((0, self.c1_radius, 1.0)); // derivative wrt circle1.radius
+        J.push((0, self.c2_radius, -1.0)); // derivative wrt circle2.radius
     }
 }
@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct EqualYConstraint {
     pub p1: String, // Index of the first point
@@ -21,26 +22,33 @@ impl Constraint for EqualYConstraint {
         1
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get the y parameter for the point
-        let p1_y_idx = param_manager
-            .get_global_index(&self.p1, 1)
-            .expect("Point not found");
-        let params = param_manager.get_parameters();
-        let y1 = params[p1_y_idx];
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundEqualY {
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            y: self.y,
+        }))
+    }
+}
+
+struct BoundEqualY {
+    p1_y: usize,
+    y: f64,
+}
 
-        DVector::from(vec![y1 - self.y])
+impl BoundConstraint for BoundEqualY {
+    fn num_residuals(&self) -> usize {
+        1
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_y]
+    }
 
-        // Get the y parameter index for the point
-        if let Some(p1_y_idx) = param_manager.get_global_index(&self.p1, 1) {
-            J[(0, p1_y_idx)] = 1.0; // derivative wrt p1.y
-        }
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        out[0] = params[self.p1_y] - self.y;
+    }
 
-        J
+    fn jacobian_entries(&self, _params: &[f64], J: &mut Vec<JacobianEntry>) {
+        J.push((0, self.p1_y, 1.0)); // derivative wrt p1.y
     }
 }
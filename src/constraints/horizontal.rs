@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct HorizontalConstraint {
     pub p1: String, // Index of the first point
@@ -21,34 +22,35 @@ impl Constraint for HorizontalConstraint {
         1
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get the y parameters for both points
-        let p1_y_idx = param_manager
-            .get_global_index(&self.p1, 1)
-            .expect("Point 1 not found");
-        let p2_y_idx = param_manager
-            .get_global_index(&self.p2, 1)
-            .expect("Point 2 not found");
-        let params = param_manager.get_parameters();
-        let y1 = params[p1_y_idx];
-        let y2 = params[p2_y_idx];
-
-        DVector::from(vec![y1 - y2])
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        // Only the y coordinates of both points are involved
+        Ok(Box::new(BoundHorizontal {
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            p2_y: param_manager.resolve_index(&self.p2, 1)?,
+        }))
+    }
+}
+
+struct BoundHorizontal {
+    p1_y: usize,
+    p2_y: usize,
+}
+
+impl BoundConstraint for BoundHorizontal {
+    fn num_residuals(&self) -> usize {
+        1
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_y, self.p2_y]
+    }
 
-        // Get the y parameter indices for both points
-        if let (Some(p1_y_idx), Some(p2_y_idx)) = (
-            param_manager.get_global_index(&self.p1, 1),
-            param_manager.get_global_index(&self.p2, 1),
-        ) {
-            J[(0, p1_y_idx)] = 1.0; // derivative wrt p1.y
-            J[(0, p2_y_idx)] = -1.0; // derivative wrt p2.y
-        }
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        out[0] = params[self.p1_y] - params[self.p2_y];
+    }
 
-        J
+    fn jacobian_entries(&self, _params: &[f64], J: &mut Vec<JacobianEntry>) {
+        J.push((0, self.p1_y, 1.0)); // derivative wrt p1.y
+        J.push((0, self.p2_y, -1.0)); // derivative wrt p2.y
     }
 }
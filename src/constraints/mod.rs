@@ -1,5 +1,6 @@
 pub mod base;
 pub mod coincident;
+pub mod distance;
 pub mod equal_radius;
 pub mod equal_x;
 pub mod equal_y;
@@ -10,6 +11,7 @@ pub mod vertical;
 
 pub use base::*;
 pub use coincident::*;
+pub use distance::*;
 pub use equal_radius::*;
 pub use equal_x::*;
 pub use equal_y::*;
@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct ParallelConstraint {
     pub p1: String, // Index of the first point (L1P1)
@@ -23,96 +24,66 @@ impl Constraint for ParallelConstraint {
         1
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get the parameters for all four points
-        let p1_x_idx = param_manager
-            .get_global_index(&self.p1, 0)
-            .expect("Point 1 not found");
-        let p1_y_idx = param_manager
-            .get_global_index(&self.p1, 1)
-            .expect("Point 1 not found");
-        let p2_x_idx = param_manager
-            .get_global_index(&self.p2, 0)
-            .expect("Point 2 not found");
-        let p2_y_idx = param_manager
-            .get_global_index(&self.p2, 1)
-            .expect("Point 2 not found");
-        let p3_x_idx = param_manager
-            .get_global_index(&self.p3, 0)
-            .expect("Point 3 not found");
-        let p3_y_idx = param_manager
-            .get_global_index(&self.p3, 1)
-            .expect("Point 3 not found");
-        let p4_x_idx = param_manager
-            .get_global_index(&self.p4, 0)
-            .expect("Point 4 not found");
-        let p4_y_idx = param_manager
-            .get_global_index(&self.p4, 1)
-            .expect("Point 4 not found");
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        let mut indices = [0; 8];
+        for (k, point) in [&self.p1, &self.p2, &self.p3, &self.p4]
+            .into_iter()
+            .enumerate()
+        {
+            indices[2 * k] = param_manager.resolve_index(point, 0)?;
+            indices[2 * k + 1] = param_manager.resolve_index(point, 1)?;
+        }
+        Ok(Box::new(BoundParallel { indices }))
+    }
+}
 
-        let params = param_manager.get_parameters();
-        let x1 = params[p1_x_idx];
-        let y1 = params[p1_y_idx];
-        let x2 = params[p2_x_idx];
-        let y2 = params[p2_y_idx];
-        let x3 = params[p3_x_idx];
-        let y3 = params[p3_y_idx];
-        let x4 = params[p4_x_idx];
-        let y4 = params[p4_y_idx];
+/// Parameter indices in the order x1, y1, x2, y2, x3, y3, x4, y4
+struct BoundParallel {
+    indices: [usize; 8],
+}
+
+impl BoundParallel {
+    fn coordinates(&self, params: &[f64]) -> [f64; 8] {
+        self.indices.map(|i| params[i])
+    }
+}
+
+impl BoundConstraint for BoundParallel {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.to_vec()
+    }
+
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        let [x1, y1, x2, y2, x3, y3, x4, y4] = self.coordinates(params);
 
         let dx1 = x2 - x1;
         let dy1 = y2 - y1;
         let dx2 = x4 - x3;
         let dy2 = y4 - y3;
 
-        DVector::from(vec![dx1 * dy2 - dy1 * dx2])
+        out[0] = dx1 * dy2 - dy1 * dx2;
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
+    fn jacobian_entries(&self, params: &[f64], J: &mut Vec<JacobianEntry>) {
+        let [x1, y1, x2, y2, x3, y3, x4, y4] = self.coordinates(params);
 
-        // Get parameter indices
-        if let (
-            Some(p1_x_idx),
-            Some(p1_y_idx),
-            Some(p2_x_idx),
-            Some(p2_y_idx),
-            Some(p3_x_idx),
-            Some(p3_y_idx),
-            Some(p4_x_idx),
-            Some(p4_y_idx),
-        ) = (
-            param_manager.get_global_index(&self.p1, 0),
-            param_manager.get_global_index(&self.p1, 1),
-            param_manager.get_global_index(&self.p2, 0),
-            param_manager.get_global_index(&self.p2, 1),
-            param_manager.get_global_index(&self.p3, 0),
-            param_manager.get_global_index(&self.p3, 1),
-            param_manager.get_global_index(&self.p4, 0),
-            param_manager.get_global_index(&self.p4, 1),
-        ) {
-            let params = param_manager.get_parameters();
-            let x1 = params[p1_x_idx];
-            let y1 = params[p1_y_idx];
-            let x2 = params[p2_x_idx];
-            let y2 = params[p2_y_idx];
-            let x3 = params[p3_x_idx];
-            let y3 = params[p3_y_idx];
-            let x4 = params[p4_x_idx];
-            let y4 = params[p4_y_idx];
-
-            // Partial derivatives of (dx1 * dy2 - dy1 * dx2)
-            J[(0, p1_x_idx)] = -(y4 - y3); // ∂r/∂x1
-            J[(0, p1_y_idx)] = (x4 - x3); // ∂r/∂y1
-            J[(0, p2_x_idx)] = (y4 - y3); // ∂r/∂x2
-            J[(0, p2_y_idx)] = -(x4 - x3); // ∂r/∂y2
-            J[(0, p3_x_idx)] = (y2 - y1); // ∂r/∂x3
-            J[(0, p3_y_idx)] = -(x2 - x1); // ∂r/∂y3
-            J[(0, p4_x_idx)] = -(y2 - y1); // ∂r/∂x4
-            J[(0, p4_y_idx)] = (x2 - x1); // ∂r/∂y4
+        // Partial derivatives of (dx1 * dy2 - dy1 * dx2), in the order of the indices
+        let derivatives = [
+            -(y4 - y3), // ∂r/∂x1
+            x4 - x3,    // ∂r/∂y1
+            y4 - y3,    // ∂r/∂x2
+            -(x4 - x3), // ∂r/∂y2
+            y2 - y1,    // ∂r/∂x3
+            -(x2 - x1), // ∂r/∂y3
+            -(y2 - y1), // ∂r/∂x4
+            x2 - x1,    // ∂r/∂y4
+        ];
+        for (&index, derivative) in self.indices.iter().zip(derivatives) {
+            J.push((0, index, derivative));
         }
-
-        J
     }
 }
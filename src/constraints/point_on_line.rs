@@ -1,9 +1,7 @@
-#![allow(non_snake_case)] // Makes sense for mathematical variables
-#![allow(unused_parens)]
-
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, AutodiffConstraint, ParameterManager, Scalar,
+    constraints::{BoundConstraint, Constraint},
+};
 
 pub struct PointOnLineConstraint {
     pub p1: String,       // Index of the point to check
@@ -23,118 +21,79 @@ impl PointOnLineConstraint {
 
 impl Constraint for PointOnLineConstraint {
     fn num_residuals(&self) -> usize {
-        1 // One residual for point on line segment constraint
+        1 // Signed distance from the point to the line segment
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get point coordinates from parameter manager
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
         // Points have parameters [x, y] at indices 0, 1
-        let p1_x_idx = param_manager
-            .get_global_index(&self.p1, 0)
-            .expect("Point 1 not found in parameter manager");
-        let p1_y_idx = param_manager
-            .get_global_index(&self.p1, 1)
-            .expect("Point 1 not found in parameter manager");
-        let p2_x_idx = param_manager
-            .get_global_index(&self.p_line_a, 0)
-            .expect("Line point A not found in parameter manager");
-        let p2_y_idx = param_manager
-            .get_global_index(&self.p_line_a, 1)
-            .expect("Line point A not found in parameter manager");
-        let p3_x_idx = param_manager
-            .get_global_index(&self.p_line_b, 0)
-            .expect("Line point B not found in parameter manager");
-        let p3_y_idx = param_manager
-            .get_global_index(&self.p_line_b, 1)
-            .expect("Line point B not found in parameter manager");
-
-        let params = param_manager.get_parameters();
-        let x1 = params[p1_x_idx];
-        let y1 = params[p1_y_idx];
-        let x2 = params[p2_x_idx];
-        let y2 = params[p2_y_idx];
-        let x3 = params[p3_x_idx];
-        let y3 = params[p3_y_idx];
-
-        // Find the parameter t for the closest point on the line segment
-        let dx = x3 - x2;
-        let dy = y3 - y2;
-        let segment_length_squared = dx * dx + dy * dy;
-
-        let t = if segment_length_squared < 1e-12 {
-            0.0 // Degenerate case: line segment is a point
-        } else {
-            let t_unclamped = ((x1 - x2) * dx + (y1 - y2) * dy) / segment_length_squared;
-            t_unclamped.clamp(0.0, 1.0) // Clamp t to [0,1] to stay within segment
-        };
-
-        // Point on segment at parameter t
-        let px = x2 + t * dx;
-        let py = y2 + t * dy;
-
-        // Residual is the distance from p1 to the closest point on the segment
-        DVector::from(vec![(x1 - px) * (x1 - px) + (y1 - py) * (y1 - py)])
+        Ok(Box::new(BoundPointOnLine {
+            p1_x: param_manager.resolve_index(&self.p1, 0)?,
+            p1_y: param_manager.resolve_index(&self.p1, 1)?,
+            p2_x: param_manager.resolve_index(&self.p_line_a, 0)?,
+            p2_y: param_manager.resolve_index(&self.p_line_a, 1)?,
+            p3_x: param_manager.resolve_index(&self.p_line_b, 0)?,
+            p3_y: param_manager.resolve_index(&self.p_line_b, 1)?,
+        }))
     }
+}
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
+struct BoundPointOnLine {
+    p1_x: usize,
+    p1_y: usize,
+    p2_x: usize,
+    p2_y: usize,
+    p3_x: usize,
+    p3_y: usize,
+}
 
-        // Get parameter indices
-        let p1_x_idx = param_manager.get_global_index(&self.p1, 0);
-        let p1_y_idx = param_manager.get_global_index(&self.p1, 1);
-        let p2_x_idx = param_manager.get_global_index(&self.p_line_a, 0);
-        let p2_y_idx = param_manager.get_global_index(&self.p_line_a, 1);
-        let p3_x_idx = param_manager.get_global_index(&self.p_line_b, 0);
-        let p3_y_idx = param_manager.get_global_index(&self.p_line_b, 1);
+impl AutodiffConstraint for BoundPointOnLine {
+    fn num_residuals(&self) -> usize {
+        1
+    }
 
-        // If any point is not found, return zero jacobian (shouldn't happen in practice)
-        let (p1_x_idx, p1_y_idx, p2_x_idx, p2_y_idx, p3_x_idx, p3_y_idx) = match (
-            p1_x_idx, p1_y_idx, p2_x_idx, p2_y_idx, p3_x_idx, p3_y_idx
-        ) {
-            (Some(x1), Some(y1), Some(x2), Some(y2), Some(x3), Some(y3)) => {
-                (x1, y1, x2, y2, x3, y3)
-            }
-            _ => return J,
-        };
+    fn parameters(&self) -> Vec<usize> {
+        vec![
+            self.p1_x, self.p1_y, self.p2_x, self.p2_y, self.p3_x, self.p3_y,
+        ]
+    }
 
-        let params = param_manager.get_parameters();
-        let x1 = params[p1_x_idx];
-        let y1 = params[p1_y_idx];
-        let x2 = params[p2_x_idx];
-        let y2 = params[p2_y_idx];
-        let x3 = params[p3_x_idx];
-        let y3 = params[p3_y_idx];
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]) {
+        let [x1, y1, x2, y2, x3, y3] = [x[0], x[1], x[2], x[3], x[4], x[5]];
 
+        // Find the parameter t for the closest point on the line segment
         let dx = x3 - x2;
         let dy = y3 - y2;
         let segment_length_squared = dx * dx + dy * dy;
+        let cross = (x1 - x2) * dy - (y1 - y2) * dx;
 
-        if segment_length_squared < 1e-12 {
-            // Degenerate case: treat as point-to-point distance
-            J[(0, p1_x_idx)] = 2.0 * (x1 - x2);
-            J[(0, p1_y_idx)] = 2.0 * (y1 - y2);
-            return J;
+        if segment_length_squared.value() < 1e-24 {
+            // Degenerate case: line segment is a point
+            out[0] = point_distance(x1 - x2, y1 - y2, cross);
+            return;
         }
 
-        let t_unclamped = ((x1 - x2) * dx + (y1 - y2) * dy) / segment_length_squared;
-        let t = t_unclamped.clamp(0.0, 1.0);
-
-        // Point on segment
-        let px = x2 + t * dx;
-        let py = y2 + t * dy;
-
-        // If t is clamped, derivatives with respect to segment endpoints are zero
-        if (0.0..=1.0).contains(&t_unclamped) {
-            // t is not clamped, use full derivatives
-            J[(0, p1_x_idx)] = 2.0 * (x1 - px);
-            J[(0, p1_y_idx)] = 2.0 * (y1 - py);
+        let t = ((x1 - x2) * dx + (y1 - y2) * dy) / segment_length_squared;
+        out[0] = if t.value() <= 0.0 {
+            point_distance(x1 - x2, y1 - y2, cross)
+        } else if t.value() >= 1.0 {
+            point_distance(x1 - x3, y1 - y3, cross)
         } else {
-            // t is clamped, simplified derivatives
-            J[(0, p1_x_idx)] = 2.0 * (x1 - px);
-            J[(0, p1_y_idx)] = 2.0 * (y1 - py);
-        }
-
-        J
+            // Signed distance to the segment, which is linear in p1 and so
+            // converges as fast as the other constraints
+            cross / segment_length_squared.sqrt()
+        };
     }
 }
+
+/// Distance from p1 to the segment endpoint it is closest to, signed like
+/// the distance to the line so the residual does not jump at the endpoints
+fn point_distance<S: Scalar>(dx: S, dy: S, cross: S) -> S {
+    let length_squared = dx * dx + dy * dy;
+    let length = if length_squared.value() < 1e-24 {
+        // The length has no derivative where p1 reaches the endpoint
+        length_squared
+    } else {
+        length_squared.sqrt()
+    };
+    if cross.value() < 0.0 { -length } else { length }
+}
@@ -1,9 +1,10 @@
 #![allow(non_snake_case)] // Makes sense for mathematical variables
 #![allow(unused_parens)]
 
-use nalgebra::{DMatrix, DVector};
-
-use crate::{ParameterManager, constraints::Constraint};
+use crate::{
+    AcsError, ParameterManager,
+    constraints::{BoundConstraint, Constraint, JacobianEntry},
+};
 
 pub struct VerticalConstraint {
     pub p1: String, // Index of the first point
@@ -21,35 +22,35 @@ impl Constraint for VerticalConstraint {
         1
     }
 
-    fn residual(&self, param_manager: &ParameterManager) -> DVector<f64> {
-        // Get x coordinates of both points
-        let p1_x_idx = param_manager
-            .get_global_index(&self.p1, 0)
-            .expect("Point p1 not found in parameter manager");
-        let p2_x_idx = param_manager
-            .get_global_index(&self.p2, 0)
-            .expect("Point p2 not found in parameter manager");
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        // Only the x coordinates of both points are involved
+        Ok(Box::new(BoundVertical {
+            p1_x: param_manager.resolve_index(&self.p1, 0)?,
+            p2_x: param_manager.resolve_index(&self.p2, 0)?,
+        }))
+    }
+}
 
-        let params = param_manager.get_parameters();
-        let p1_x = params[p1_x_idx];
-        let p2_x = params[p2_x_idx];
+struct BoundVertical {
+    p1_x: usize,
+    p2_x: usize,
+}
 
-        DVector::from(vec![p1_x - p2_x])
+impl BoundConstraint for BoundVertical {
+    fn num_residuals(&self) -> usize {
+        1
     }
 
-    fn jacobian(&self, param_manager: &ParameterManager) -> DMatrix<f64> {
-        let total_params = param_manager.num_parameters();
-        let mut J = DMatrix::<f64>::zeros(1, total_params);
-
-        // Get global indices for x coordinates
-        if let Some(p1_x_idx) = param_manager.get_global_index(&self.p1, 0) {
-            J[(0, p1_x_idx)] = 1.0; // derivative wrt p1.x
-        }
+    fn parameters(&self) -> Vec<usize> {
+        vec![self.p1_x, self.p2_x]
+    }
 
-        if let Some(p2_x_idx) = param_manager.get_global_index(&self.p2, 0) {
-            J[(0, p2_x_idx)] = -1.0; // derivative wrt p2.x
-        }
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        out[0] = params[self.p1_x] - params[self.p2_x];
+    }
 
-        J
+    fn jacobian_entries(&self, _params: &[f64], J: &mut Vec<JacobianEntry>) {
+        J.push((0, self.p1_x, 1.0)); // derivative wrt p1.x
+        J.push((0, self.p2_x, -1.0)); // derivative wrt p2.x
     }
 }
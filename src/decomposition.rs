@@ -0,0 +1,244 @@
+use crate::{
+    AcsError, BoundConstraint, ClusterResult, ConstraintEntry, ConstraintGraph, Deadline,
+    GeometrySystem, ParameterManager, SolveObserver, SolverOptions, SolverResult, priority,
+    sparse::{self, FreeColumns},
+};
+
+/// Outcome of the iteration on a single cluster
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterStatus {
+    pub converged: bool,
+    /// Number of steps taken. A cluster that already satisfies its
+    /// constraints takes none.
+    pub iterations: usize,
+    pub final_error: f64,
+    pub initial_error: f64,
+}
+
+/// An independent part of the constraint system. No free parameter of a
+/// cluster is touched by a constraint of another cluster, so clusters can be
+/// solved separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Indices into the constraint list
+    pub constraints: Vec<usize>,
+    /// Free global parameter indices, sorted
+    pub parameters: Vec<usize>,
+}
+
+/// Splits the constraints into clusters connected through shared free
+/// parameters. Clusters are formed per parameter rather than per entity, so
+/// e.g. a horizontal and a vertical constraint on the same point end up in
+/// different clusters. Fixed parameters do not connect constraints, since
+/// neither side can move them. Parameters that no constraint touches are left
+/// out, and clusters are ordered by their first constraint.
+pub fn find_clusters(constraints: &[Box<dyn BoundConstraint>], is_free: &[bool]) -> Vec<Cluster> {
+    let mut parents: Vec<usize> = (0..is_free.len()).collect();
+
+    let free_parameters: Vec<Vec<usize>> = constraints
+        .iter()
+        .map(|c| {
+            let mut parameters: Vec<usize> =
+                c.parameters().into_iter().filter(|&i| is_free[i]).collect();
+            parameters.sort_unstable();
+            parameters.dedup();
+            parameters
+        })
+        .collect();
+
+    for parameters in &free_parameters {
+        for pair in parameters.windows(2) {
+            union(&mut parents, pair[0], pair[1]);
+        }
+    }
+
+    // Clusters keyed by the root of their parameters. A constraint on fixed
+    // parameters only gets a cluster of its own.
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut cluster_of_root = vec![None; is_free.len()];
+
+    for (index, parameters) in free_parameters.into_iter().enumerate() {
+        let Some(&first) = parameters.first() else {
+            clusters.push(Cluster {
+                constraints: vec![index],
+                parameters: Vec::new(),
+            });
+            continue;
+        };
+
+        let root = find(&mut parents, first);
+        let cluster = *cluster_of_root[root].get_or_insert_with(|| {
+            clusters.push(Cluster {
+                constraints: Vec::new(),
+                parameters: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+        clusters[cluster].constraints.push(index);
+        clusters[cluster].parameters.extend(parameters);
+    }
+
+    for cluster in &mut clusters {
+        cluster.parameters.sort_unstable();
+        cluster.parameters.dedup();
+    }
+
+    clusters
+}
+
+/// Bind the constraints of a graph, split them into clusters and solve each
+/// cluster with `solve_cluster`, which gets the cluster's index and its hard
+/// constraints, only moves the parameters of the given columns and reports
+/// to `observer`. Soft constraints are then satisfied as far as the hard
+/// ones allow, see `priority::solve_soft_levels`. The final parameter values
+/// are written back to the geometry, unless a cluster fails, e.g. because
+/// the solve was cancelled, in which case the geometry is left untouched.
+pub fn solve_by_cluster<F>(
+    geometry: &mut GeometrySystem,
+    constraint_graph: &ConstraintGraph,
+    options: &SolverOptions,
+    deadline: Deadline,
+    observer: &mut dyn SolveObserver,
+    mut solve_cluster: F,
+) -> Result<SolverResult, AcsError>
+where
+    F: FnMut(
+        usize,
+        &mut ParameterManager,
+        &[Box<dyn BoundConstraint>],
+        &FreeColumns,
+        &mut dyn SolveObserver,
+    ) -> Result<ClusterStatus, AcsError>,
+{
+    let mut param_manager = ParameterManager::from_geometry(geometry);
+
+    let constraints = priority::bind_weighted(&param_manager, constraint_graph)?;
+    let is_free: Vec<bool> = param_manager
+        .get_parameter_info()
+        .iter()
+        .map(|info| !info.is_fixed)
+        .collect();
+    let clusters = find_clusters(&constraints, &is_free);
+
+    // Hand every cluster its own constraints and solve it on its own
+    let entries = constraint_graph.get_entries();
+    let mut constraints: Vec<Option<Box<dyn BoundConstraint>>> =
+        constraints.into_iter().map(Some).collect();
+    let mut results = Vec::with_capacity(clusters.len());
+
+    for (index, cluster) in clusters.into_iter().enumerate() {
+        let level_indices = priority::levels(entries, &cluster.constraints);
+        let levels: Vec<Vec<Box<dyn BoundConstraint>>> = level_indices
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .filter_map(|&i| constraints[i].take())
+                    .collect()
+            })
+            .collect();
+        let hard = &levels[0];
+        let hard_columns = FreeColumns::from_parameters(
+            param_manager.num_parameters(),
+            priority::free_parameters(hard, &is_free),
+        );
+
+        check_finite(
+            param_manager.get_parameters(),
+            &levels,
+            &level_indices,
+            entries,
+        )?;
+        let status = if hard.is_empty() {
+            Ok(ClusterStatus {
+                converged: true,
+                iterations: 0,
+                final_error: 0.0,
+                initial_error: 0.0,
+            })
+        } else {
+            solve_cluster(index, &mut param_manager, hard, &hard_columns, observer)
+        };
+        // A constraint that stops evaluating to numbers during the solve is
+        // reported by name, rather than as the numerical failure it causes
+        check_finite(
+            param_manager.get_parameters(),
+            &levels,
+            &level_indices,
+            entries,
+        )?;
+        let mut status = status?;
+
+        if levels.len() > 1 {
+            let hierarchy = priority::Hierarchy {
+                levels: &levels,
+                parameters: &cluster.parameters,
+                hard_columns: &hard_columns,
+            };
+            let mut context = priority::SoftContext {
+                cluster: index,
+                iterations: status.iterations,
+                deadline,
+                observer,
+            };
+            status.iterations +=
+                priority::solve_soft_levels(&mut param_manager, &hierarchy, options, &mut context)?;
+            status.final_error =
+                sparse::evaluate_residuals(param_manager.get_parameters(), hard).norm();
+            status.converged &= status.final_error < options.tolerance;
+        }
+
+        results.push(ClusterResult {
+            converged: status.converged,
+            iterations: status.iterations,
+            final_error: status.final_error,
+            initial_error: status.initial_error,
+            ..ClusterResult::for_constraints(entries, &cluster.constraints)
+        });
+    }
+
+    // Update geometry with final parameter values
+    param_manager.sync_to_geometry(geometry)?;
+
+    Ok(SolverResult::from_clusters(results))
+}
+
+/// Fail with `AcsError::NonFiniteResidual` for the first constraint, by
+/// level, whose residuals are not all finite
+fn check_finite(
+    params: &[f64],
+    levels: &[Vec<Box<dyn BoundConstraint>>],
+    level_indices: &[Vec<usize>],
+    entries: &[ConstraintEntry],
+) -> Result<(), AcsError> {
+    let mut residuals = Vec::new();
+    for (constraints, indices) in levels.iter().zip(level_indices) {
+        for (constraint, &index) in constraints.iter().zip(indices) {
+            residuals.clear();
+            residuals.resize(constraint.num_residuals(), 0.0);
+            constraint.residual(params, &mut residuals);
+            if !residuals.iter().all(|r| r.is_finite()) {
+                return Err(AcsError::NonFiniteResidual {
+                    constraint: entries[index].id.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn find(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    node
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let root_a = find(parents, a);
+    let root_b = find(parents, b);
+    if root_a != root_b {
+        parents[root_b] = root_a;
+    }
+}
@@ -1,25 +1,106 @@
-use nalgebra::{DMatrix, DVector};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::DVector;
 
 use crate::{
-    Constraint, ConstraintGraph, EntityType, GeometrySystem, ParameterManager, Solver, SolverResult,
+    AcsError, BoundConstraint, ConstraintGraph, Deadline, GeometrySystem, ParameterManager,
+    SolveObserver, SolveProgress, Solver, SolverOptions, SolverResult,
+    decomposition::{self, ClusterStatus},
+    sparse::{self, FreeColumns},
 };
 
-pub struct ParametricDogLegSolver {
-    max_iterations: usize,
-    tolerance: f64,
+/// Steps with a worse ratio of actual to predicted reduction are undone
+const MIN_ACCEPTED_RHO: f64 = 0.1;
+
+/// Which part of the dog-leg path a step was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepType {
+    /// The full Gauss-Newton step fit inside the trust region
+    GaussNewton,
+    /// The steepest-descent step, cut at the trust region boundary
+    Gradient,
+    /// A blend of the two, ending on the trust region boundary
+    DogLeg,
+}
+
+/// State of one dog-leg iteration, before its step was applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub cluster: usize,
+    pub iteration: usize,
+    /// Every parameter of the sketch, in the order of `SolveTrace::parameter_names`
+    pub parameters: Vec<f64>,
+    /// Residuals of the cluster's constraints
+    pub residuals: Vec<f64>,
+    pub step_type: StepType,
+    /// Actual over predicted reduction of the squared residual norm
+    pub rho: f64,
+    pub trust_radius: f64,
+    /// Whether the step made things worse and was undone
+    pub reverted: bool,
 }
 
-impl Default for ParametricDogLegSolver {
-    fn default() -> Self {
-        Self::new()
+/// Every iteration of a dog-leg solve, for debugging and replay
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SolveTrace {
+    /// Names of the parameters, e.g. `"p1.x"`, in parameter vector order
+    pub parameter_names: Vec<String>,
+    pub steps: Vec<TraceStep>,
+}
+
+/// Shared handle to the trace of the most recent solve. Keep a clone and
+/// hand another to `ParametricDogLegSolver::with_trace`.
+#[derive(Debug, Clone, Default)]
+pub struct TraceRecorder(Arc<Mutex<SolveTrace>>);
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The trace recorded by the last solve
+    pub fn trace(&self) -> SolveTrace {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SolveTrace> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 }
 
+/// Outcome of one dog-leg step
+struct DogLegStep {
+    rho: f64,
+    step_type: StepType,
+    reverted: bool,
+}
+
+/// What a cluster solve reports to, besides its result
+struct ClusterContext<'a> {
+    cluster: usize,
+    deadline: Deadline,
+    observer: &'a mut dyn SolveObserver,
+    trace: Option<&'a TraceRecorder>,
+}
+
+/// Powell's dog-leg trust-region method
+#[derive(Debug, Default)]
+pub struct ParametricDogLegSolver {
+    trace: Option<TraceRecorder>,
+}
+
 impl ParametricDogLegSolver {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A solver that records every iteration into `recorder`. Each solve
+    /// replaces the previous trace.
+    pub fn with_trace(recorder: TraceRecorder) -> Self {
         Self {
-            max_iterations: 100,
-            tolerance: 1e-6,
+            trace: Some(recorder),
         }
     }
 
@@ -27,178 +108,195 @@ impl ParametricDogLegSolver {
         &self,
         geometry: &mut GeometrySystem,
         constraint_graph: &ConstraintGraph,
-    ) -> Result<SolverResult, String> {
-        // Build parameter manager from geometry
-        let mut param_manager = ParameterManager::new();
-
-        // Register all points
-        for (id, point) in geometry.get_all_points() {
-            param_manager.register_entity(id.clone(), EntityType::Point, point);
-        }
-
-        // Register all circles
-        for (id, circle) in geometry.get_all_circles() {
-            param_manager.register_entity(id.clone(), EntityType::Circle, circle);
-        }
-
-        // Register all arcs
-        for (id, arc) in geometry.get_all_arcs() {
-            param_manager.register_entity(id.clone(), EntityType::Arc, arc);
+        options: &SolverOptions,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<SolverResult, AcsError> {
+        let deadline = Deadline::start(options.time_budget);
+        if let Some(recorder) = &self.trace {
+            let parameter_names = ParameterManager::from_geometry(geometry)
+                .get_parameter_info()
+                .iter()
+                .map(|info| info.name.clone())
+                .collect();
+            *recorder.lock() = SolveTrace {
+                parameter_names,
+                steps: Vec::new(),
+            };
         }
 
-        let constraints = constraint_graph.get_constraints();
-
-        let result = Self::solve_constraints_parametric(
-            &mut param_manager,
-            constraints,
-            self.max_iterations,
-            self.tolerance,
-        );
-
-        // Update geometry with final parameter values
-        Self::sync_geometry_from_parameters(&mut param_manager, geometry)?;
-
-        Ok(result)
+        decomposition::solve_by_cluster(
+            geometry,
+            constraint_graph,
+            options,
+            deadline,
+            observer,
+            |cluster, param_manager, constraints, columns, observer| {
+                Self::solve_constraints_parametric(
+                    param_manager,
+                    constraints,
+                    columns,
+                    options,
+                    ClusterContext {
+                        cluster,
+                        deadline,
+                        observer,
+                        trace: self.trace.as_ref(),
+                    },
+                )
+            },
+        )
     }
 
     fn solve_constraints_parametric(
         param_manager: &mut ParameterManager,
-        constraints: &[Box<dyn Constraint>],
-        max_iter: usize,
-        tolerance: f64,
-    ) -> SolverResult {
-        let mut trust_radius = 1.0;
+        constraints: &[Box<dyn BoundConstraint>],
+        columns: &FreeColumns,
+        options: &SolverOptions,
+        context: ClusterContext,
+    ) -> Result<ClusterStatus, AcsError> {
+        let ClusterContext {
+            cluster,
+            deadline,
+            observer,
+            trace,
+        } = context;
+        let mut trust_radius = options.initial_trust_radius;
         let mut prev_residual_norm = f64::INFINITY;
         let mut stagnation_count = 0;
+        let initial_error =
+            sparse::evaluate_residuals(param_manager.get_parameters(), constraints).norm();
 
-        for iter in 0..max_iter {
-            let (residuals, _) = Self::build_system_parametric(param_manager, constraints);
+        for iter in 0..options.max_iterations {
+            let residuals = sparse::evaluate_residuals(param_manager.get_parameters(), constraints);
             let residual_norm = residuals.norm();
 
-            if residual_norm < tolerance {
-                println!(
-                    "Converged after {} iterations with residual norm: {:.2e}",
-                    iter + 1,
-                    residual_norm
+            observer.on_iteration(&SolveProgress {
+                cluster,
+                iteration: iter,
+                residual_norm,
+                trust_radius: Some(trust_radius),
+            });
+            if observer.is_cancelled() {
+                return Err(AcsError::Cancelled);
+            }
+
+            if residual_norm < options.tolerance {
+                log::debug!(
+                    "Cluster {cluster} converged in {iter} iterations (residual {residual_norm:.2e})"
                 );
 
-                return SolverResult::Converged {
-                    initial_error: prev_residual_norm,
+                return Ok(ClusterStatus {
+                    converged: true,
+                    initial_error,
                     final_error: residual_norm,
-                    iterations: iter + 1,
-                };
+                    iterations: iter,
+                });
+            }
+
+            if deadline.expired() {
+                log::warn!("Cluster {cluster} ran out of time after {iter} iterations");
+                return Ok(ClusterStatus {
+                    converged: false,
+                    initial_error,
+                    iterations: iter,
+                    final_error: residual_norm,
+                });
             }
 
             // Check for stagnation
-            if (residual_norm - prev_residual_norm).abs() < 1e-12 {
+            if (residual_norm - prev_residual_norm).abs() < options.stagnation_threshold {
                 stagnation_count += 1;
                 if stagnation_count > 5 {
-                    return SolverResult::MaxIterationsReached {
-                        initial_error: prev_residual_norm,
-                        iterations: iter + 1,
+                    log::debug!("Cluster {cluster} stagnated at residual norm {residual_norm:.2e}");
+                    return Ok(ClusterStatus {
+                        converged: false,
+                        initial_error,
+                        iterations: iter,
                         final_error: residual_norm,
-                    };
+                    });
                 }
             } else {
                 stagnation_count = 0;
             }
             prev_residual_norm = residual_norm;
 
-            let rho = Self::dog_leg_step_parametric(param_manager, constraints, trust_radius);
+            let parameters = trace.map(|_| param_manager.get_parameters().to_vec());
+            let step = Self::dog_leg_step_parametric(
+                param_manager,
+                constraints,
+                columns,
+                &residuals,
+                trust_radius,
+            )?;
+            if let (Some(recorder), Some(parameters)) = (trace, parameters) {
+                recorder.lock().steps.push(TraceStep {
+                    cluster,
+                    iteration: iter,
+                    parameters,
+                    residuals: residuals.iter().copied().collect(),
+                    step_type: step.step_type,
+                    rho: step.rho,
+                    trust_radius,
+                    reverted: step.reverted,
+                });
+            }
+            let rho = step.rho;
 
             // Update trust radius based on step quality
             if rho > 0.75 {
-                trust_radius = (trust_radius * 2.0).min(10.0);
+                trust_radius = (trust_radius * 2.0).min(options.max_trust_radius);
             } else if rho < 0.25 {
                 trust_radius *= 0.5;
             }
 
             // Ensure trust radius doesn't get too small
-            if trust_radius < 1e-8 {
-                return SolverResult::MaxIterationsReached {
-                    initial_error: prev_residual_norm,
+            if trust_radius < options.min_trust_radius {
+                return Ok(ClusterStatus {
+                    converged: false,
+                    initial_error,
                     iterations: iter + 1,
                     final_error: residual_norm,
-                };
+                });
             }
         }
 
         // If we exit the loop without converging, return final status
-        let (final_residuals, _) = Self::build_system_parametric(param_manager, constraints);
-        let final_residual_norm = final_residuals.norm();
-        if final_residual_norm >= tolerance {
-            return SolverResult::MaxIterationsReached {
-                initial_error: prev_residual_norm,
-                iterations: max_iter,
-                final_error: final_residual_norm,
-            };
-        }
+        let final_residual_norm =
+            sparse::evaluate_residuals(param_manager.get_parameters(), constraints).norm();
 
-        SolverResult::Converged {
-            initial_error: prev_residual_norm,
+        Ok(ClusterStatus {
+            converged: final_residual_norm < options.tolerance,
+            initial_error,
             final_error: final_residual_norm,
-            iterations: max_iter,
-        }
-    }
-
-    fn build_system_parametric(
-        param_manager: &ParameterManager,
-        constraints: &[Box<dyn Constraint>],
-    ) -> (DVector<f64>, DMatrix<f64>) {
-        let total_residuals: usize = constraints.iter().map(|c| c.num_residuals()).sum();
-        let total_vars = param_manager.num_parameters();
-
-        let mut residuals = DVector::<f64>::zeros(total_residuals);
-        let mut jacobian = DMatrix::<f64>::zeros(total_residuals, total_vars);
-
-        let mut row_offset = 0;
-        for c in constraints {
-            let r = c.residual(param_manager);
-            let j = c.jacobian(param_manager);
-
-            residuals.rows_mut(row_offset, r.len()).copy_from(&r);
-            jacobian.rows_mut(row_offset, j.nrows()).copy_from(&j);
-
-            row_offset += r.len();
-        }
-
-        (residuals, jacobian)
+            iterations: options.max_iterations,
+        })
     }
 
     fn dog_leg_step_parametric(
         param_manager: &mut ParameterManager,
-        constraints: &[Box<dyn Constraint>],
+        constraints: &[Box<dyn BoundConstraint>],
+        columns: &FreeColumns,
+        residuals: &DVector<f64>,
         trust_radius: f64,
-    ) -> f64 {
-        let (residuals, jacobian) = Self::build_system_parametric(param_manager, constraints);
-
+    ) -> Result<DogLegStep, AcsError> {
         if residuals.norm() < 1e-14 {
-            return 1.0; // Already at solution
+            // Already at solution
+            return Ok(DogLegStep {
+                rho: 1.0,
+                step_type: StepType::GaussNewton,
+                reverted: false,
+            });
         }
 
-        // Gauss-Newton step
-        let jt = jacobian.transpose();
-        let jtj = &jt * &jacobian;
-        let jtr = &jt * &residuals;
-
-        // Clone jtj and jtr for later use
-        let jtj_clone = jtj.clone();
-        let jtr_clone = jtr.clone();
-
-        // Use pseudo-inverse for robustness
-        let gn_step = match jtj.try_inverse() {
-            Some(inv) => -inv * &jtr,
-            None => {
-                // Use SVD-based pseudo-inverse for rank-deficient cases
-                let svd = jtj_clone.svd(true, true);
-                -svd.pseudo_inverse(1e-12)
-                    .expect("SVD pseudo-inverse computation failed: matrix is too rank-deficient")
-                    * &jtr
-            }
-        };
+        // Jacobian with respect to the free parameters only
+        let jacobian =
+            sparse::assemble_jacobian(param_manager.get_parameters(), constraints, columns);
+        let gradient = &jacobian.transpose() * residuals;
+
+        // Gauss-Newton step from the sparse normal equations
+        let gn_step = -sparse::solve_normal_equations(&jacobian, &gradient)?;
 
         // Gradient step
-        let gradient = jtr_clone;
         let grad_step = if gradient.norm() > 1e-14 {
             let alpha = gradient.dot(&gradient) / (&jacobian * &gradient).norm_squared();
             -alpha * &gradient
@@ -210,10 +308,10 @@ impl ParametricDogLegSolver {
         let gn_norm = gn_step.norm();
         let grad_norm = grad_step.norm();
 
-        let step = if gn_norm <= trust_radius {
-            gn_step
+        let (step, step_type) = if gn_norm <= trust_radius {
+            (gn_step, StepType::GaussNewton)
         } else if grad_norm >= trust_radius {
-            (trust_radius / grad_norm) * grad_step
+            ((trust_radius / grad_norm) * grad_step, StepType::Gradient)
         } else {
             // Dog leg interpolation
             let beta_num = trust_radius * trust_radius - grad_norm * grad_norm;
@@ -226,37 +324,27 @@ impl ParametricDogLegSolver {
             } else {
                 0.0
             };
-            grad_step + beta * diff
+            (grad_step + beta * diff, StepType::DogLeg)
         };
 
-        // Apply step to parameters (only non-fixed ones)
+        // Apply step to the free parameters
         let old_params = param_manager.get_parameters().to_vec();
         let old_residual_norm = residuals.norm();
 
-        // Apply step (collect fixed status to avoid borrowing issues)
-        let fixed_params: Vec<bool> = param_manager
-            .get_parameter_info()
-            .iter()
-            .map(|info| info.is_fixed)
-            .collect();
-
-        for (i, &step_val) in step.iter().enumerate() {
-            if i < fixed_params.len() && !fixed_params[i] {
-                let new_val = old_params[i] + step_val;
-                let _ = param_manager.set_parameter(i, new_val);
-            }
+        for (column, &step_val) in step.iter().enumerate() {
+            let i = columns.parameter(column);
+            let _ = param_manager.set_parameter(i, old_params[i] + step_val);
         }
 
         // Compute new residuals
-        let (new_residuals, _) = Self::build_system_parametric(param_manager, constraints);
-        let new_residual_norm = new_residuals.norm();
+        let new_residual_norm =
+            sparse::evaluate_residuals(param_manager.get_parameters(), constraints).norm();
 
         // Compute step quality
-        let jt_jacobian = &jt * &jacobian;
         let predicted_reduction = old_residual_norm.powi(2)
             - (old_residual_norm.powi(2)
                 + 2.0 * gradient.dot(&step)
-                + step.dot(&(&jt_jacobian * &step)));
+                + (&jacobian * &step).norm_squared());
         let actual_reduction = old_residual_norm.powi(2) - new_residual_norm.powi(2);
 
         let rho = if predicted_reduction.abs() < 1e-14 {
@@ -266,38 +354,19 @@ impl ParametricDogLegSolver {
         };
 
         // If step quality is poor, revert the step
-        if rho < 0.1 {
-            // Revert parameters
-            for (i, &old_val) in old_params.iter().enumerate() {
-                if i < fixed_params.len() && !fixed_params[i] {
-                    let _ = param_manager.set_parameter(i, old_val);
-                }
+        let reverted = rho < MIN_ACCEPTED_RHO;
+        if reverted {
+            for column in 0..columns.len() {
+                let i = columns.parameter(column);
+                let _ = param_manager.set_parameter(i, old_params[i]);
             }
         }
 
-        rho
-    }
-
-    fn sync_geometry_from_parameters(
-        param_manager: &mut ParameterManager,
-        geometry: &mut GeometrySystem,
-    ) -> Result<(), String> {
-        // Update points
-        for (id, point) in geometry.get_all_points_mut() {
-            param_manager.update_entity_parameters(id, point)?;
-        }
-
-        // Update circles
-        for (id, circle) in geometry.get_all_circles_mut() {
-            param_manager.update_entity_parameters(id, circle)?;
-        }
-
-        // Update arcs
-        for (id, arc) in geometry.get_all_arcs_mut() {
-            param_manager.update_entity_parameters(id, arc)?;
-        }
-
-        Ok(())
+        Ok(DogLegStep {
+            rho,
+            step_type,
+            reverted,
+        })
     }
 }
 
@@ -306,7 +375,9 @@ impl Solver for ParametricDogLegSolver {
         &self,
         geometry: &mut GeometrySystem,
         constraint_graph: &ConstraintGraph,
-    ) -> Result<SolverResult, String> {
-        self.solve_parametric(geometry, constraint_graph)
+        options: &SolverOptions,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<SolverResult, AcsError> {
+        self.solve_parametric(geometry, constraint_graph, options, observer)
     }
 }
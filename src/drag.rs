@@ -0,0 +1,192 @@
+use nalgebra::DVector;
+
+use crate::{
+    AcsError, BoundConstraint, ClusterResult, ConstraintGraph, Deadline, EntityType,
+    GeometrySystem, ParameterManager, SolverOptions, SolverResult, decomposition,
+    sparse::{self, FreeColumns},
+};
+
+/// Scale of the dragged point's parameters relative to the others. Steps
+/// minimize Σ (Δᵢ / sᵢ)², so a small scale makes the dragged point the most
+/// expensive one to move away from its target.
+const DRAG_SCALE: f64 = 0.05;
+
+/// How often a step that increases the residual is halved before it is
+/// taken anyway
+const MAX_BACKTRACKS: usize = 10;
+
+/// Move a point towards a target position and re-satisfy the constraints,
+/// changing the rest of the sketch as little as possible.
+///
+/// The point is placed at the target, as far as its locked coordinates
+/// allow, then Newton steps of minimum weighted norm pull the parameters
+/// back onto the constraints. Starting from the previous positions, this
+/// finds the nearby solution rather than whatever solution a least-squares
+/// solve happens to reach. Only the clusters that contain the point are
+/// solved, which keeps the cost proportional to the part of the sketch that
+/// can move.
+///
+/// Soft constraints are not enforced while dragging, since the drag itself
+/// is the strongest preference.
+pub fn drag_point(
+    geometry: &mut GeometrySystem,
+    constraint_graph: &ConstraintGraph,
+    options: &SolverOptions,
+    point_id: &str,
+    x: f64,
+    y: f64,
+) -> Result<SolverResult, AcsError> {
+    if geometry.get_point(point_id).is_none() {
+        return Err(match geometry.entity_type(point_id) {
+            Some(found) => AcsError::WrongEntityType {
+                entity: point_id.to_string(),
+                expected: vec![EntityType::Point],
+                found,
+                constraint: None,
+            },
+            None => AcsError::unknown_entity(point_id),
+        });
+    }
+
+    let deadline = Deadline::start(options.time_budget);
+    let mut param_manager = ParameterManager::from_geometry(geometry);
+    let dragged = [
+        param_manager.resolve_index(point_id, 0)?,
+        param_manager.resolve_index(point_id, 1)?,
+    ];
+
+    // A point with one locked coordinate slides along the other axis
+    let info = param_manager.get_parameter_info();
+    let free: Vec<(usize, f64)> = dragged
+        .into_iter()
+        .zip([x, y])
+        .filter(|&(index, _)| !info[index].is_fixed)
+        .collect();
+    if free.is_empty() {
+        return Err(AcsError::FixedParameter {
+            parameter: info[dragged[0]].name.clone(),
+        });
+    }
+    for (index, value) in free {
+        param_manager.set_parameter(index, value)?;
+    }
+
+    let constraints = constraint_graph.bind_hard_constraints(&param_manager)?;
+    let is_free: Vec<bool> = param_manager
+        .get_parameter_info()
+        .iter()
+        .map(|info| !info.is_fixed)
+        .collect();
+    let clusters = decomposition::find_clusters(&constraints, &is_free);
+
+    let entries = constraint_graph.get_entries();
+    let mut constraints: Vec<Option<Box<dyn BoundConstraint>>> =
+        constraints.into_iter().map(Some).collect();
+    let mut results = Vec::new();
+
+    // Clusters without the dragged point are left as they are
+    for cluster in clusters {
+        if !dragged
+            .iter()
+            .any(|index| cluster.parameters.binary_search(index).is_ok())
+        {
+            continue;
+        }
+
+        let cluster_constraints: Vec<Box<dyn BoundConstraint>> = cluster
+            .constraints
+            .iter()
+            .filter_map(|&i| constraints[i].take())
+            .collect();
+        let columns =
+            FreeColumns::from_parameters(param_manager.num_parameters(), cluster.parameters);
+        let scales = DVector::from_fn(columns.len(), |column, _| {
+            if dragged.contains(&columns.parameter(column)) {
+                DRAG_SCALE
+            } else {
+                1.0
+            }
+        });
+
+        let (converged, iterations, initial_error, final_error) = project(
+            &mut param_manager,
+            &cluster_constraints,
+            &columns,
+            &scales,
+            options,
+            deadline,
+        )?;
+
+        results.push(ClusterResult {
+            converged,
+            iterations,
+            final_error,
+            initial_error,
+            ..ClusterResult::for_constraints(entries, &cluster.constraints)
+        });
+    }
+
+    param_manager.sync_to_geometry(geometry)?;
+
+    Ok(SolverResult::from_clusters(results))
+}
+
+/// Newton iteration with minimum weighted-norm steps: each step solves
+/// `J Δ = -r` while minimizing `Σ (Δᵢ / sᵢ)²`, i.e. `Δ = -S (JS)ᵀ z` with
+/// `(JS)(JS)ᵀ z = r`. Returns whether it converged, the iteration count and
+/// the initial and final residual norms.
+pub(crate) fn project(
+    param_manager: &mut ParameterManager,
+    constraints: &[Box<dyn BoundConstraint>],
+    columns: &FreeColumns,
+    scales: &DVector<f64>,
+    options: &SolverOptions,
+    deadline: Deadline,
+) -> Result<(bool, usize, f64, f64), AcsError> {
+    let mut residuals = sparse::evaluate_residuals(param_manager.get_parameters(), constraints);
+    let initial_error = residuals.norm();
+
+    for iter in 0..options.max_iterations {
+        let residual_norm = residuals.norm();
+        if residual_norm < options.tolerance {
+            return Ok((true, iter, initial_error, residual_norm));
+        }
+        if deadline.expired() {
+            return Ok((false, iter, initial_error, residual_norm));
+        }
+
+        let mut jacobian =
+            sparse::assemble_jacobian(param_manager.get_parameters(), constraints, columns);
+        for (_, column, value) in jacobian.triplet_iter_mut() {
+            *value *= scales[column];
+        }
+
+        // Solving with the transpose makes the normal equations (JS)(JS)ᵀ
+        let transpose = jacobian.transpose();
+        let z = sparse::solve_normal_equations(&transpose, &residuals)?;
+        let step = -(&transpose * &z).component_mul(scales);
+
+        // Halve steps that overshoot on nonlinear constraints
+        let old_params = param_manager.get_parameters().to_vec();
+        let mut factor = 1.0;
+        for _ in 0..MAX_BACKTRACKS {
+            for (column, &step_val) in step.iter().enumerate() {
+                let i = columns.parameter(column);
+                param_manager.set_parameter(i, old_params[i] + factor * step_val)?;
+            }
+            residuals = sparse::evaluate_residuals(param_manager.get_parameters(), constraints);
+            if residuals.norm() < residual_norm {
+                break;
+            }
+            factor *= 0.5;
+        }
+    }
+
+    let final_error = residuals.norm();
+    Ok((
+        final_error < options.tolerance,
+        options.max_iterations,
+        initial_error,
+        final_error,
+    ))
+}
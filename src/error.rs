@@ -0,0 +1,167 @@
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+use crate::EntityType;
+
+/// Errors reported by the solver and its bindings.
+///
+/// In JavaScript an error is thrown as an object of the form
+/// `{ kind: "UnknownEntity", details: { entity: "p9", constraint: "Horizontal" } }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "details")]
+pub enum AcsError {
+    /// No entity with the given ID exists. `constraint` names the constraint
+    /// that referenced it, if any.
+    UnknownEntity {
+        entity: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        constraint: Option<String>,
+    },
+    /// An entity exists but has a type the caller cannot work with
+    WrongEntityType {
+        entity: String,
+        expected: Vec<EntityType>,
+        found: EntityType,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        constraint: Option<String>,
+    },
+    /// No constraint with the given ID exists
+    UnknownConstraint { constraint: String },
+    /// A constraint ID is already in use
+    DuplicateId { constraint: String },
+    /// The constraint type is recognized but cannot be built
+    UnsupportedConstraint { constraint: String, message: String },
+    /// An entity cannot be removed because other items depend on it
+    EntityInUse {
+        entity: String,
+        dependents: Vec<String>,
+    },
+    /// Geometry that is structurally invalid, e.g. a line with identical endpoints
+    InvalidGeometry { entity: String, message: String },
+    /// A parameter vector or parameter index that does not fit the entity
+    InvalidParameter { message: String },
+    /// A parameter is fixed and cannot be modified
+    FixedParameter { parameter: String },
+    /// Input JSON could not be parsed or converted
+    ParseError { message: String },
+    /// Output could not be serialized
+    SerializationError { message: String },
+    /// The numerical method broke down
+    NumericalFailure { message: String },
+    /// A constraint evaluated to NaN or infinity, e.g. because a JavaScript
+    /// callback threw
+    NonFiniteResidual { constraint: String },
+    /// An expression could not be parsed or evaluated, or a variable name
+    /// is not an identifier
+    InvalidExpression { expression: String, message: String },
+    /// An expression references a variable that is not defined
+    UnknownVariable { variable: String },
+    /// The solve was cancelled by its observer; the geometry is unchanged
+    Cancelled,
+}
+
+impl AcsError {
+    pub fn unknown_entity(entity: &str) -> Self {
+        AcsError::UnknownEntity {
+            entity: entity.to_string(),
+            constraint: None,
+        }
+    }
+
+    /// Name of the variant, as exposed in the `kind` field to JavaScript
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AcsError::UnknownEntity { .. } => "UnknownEntity",
+            AcsError::WrongEntityType { .. } => "WrongEntityType",
+            AcsError::UnknownConstraint { .. } => "UnknownConstraint",
+            AcsError::DuplicateId { .. } => "DuplicateId",
+            AcsError::UnsupportedConstraint { .. } => "UnsupportedConstraint",
+            AcsError::EntityInUse { .. } => "EntityInUse",
+            AcsError::InvalidGeometry { .. } => "InvalidGeometry",
+            AcsError::InvalidParameter { .. } => "InvalidParameter",
+            AcsError::FixedParameter { .. } => "FixedParameter",
+            AcsError::ParseError { .. } => "ParseError",
+            AcsError::SerializationError { .. } => "SerializationError",
+            AcsError::NumericalFailure { .. } => "NumericalFailure",
+            AcsError::NonFiniteResidual { .. } => "NonFiniteResidual",
+            AcsError::InvalidExpression { .. } => "InvalidExpression",
+            AcsError::UnknownVariable { .. } => "UnknownVariable",
+            AcsError::Cancelled => "Cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for AcsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcsError::UnknownEntity {
+                entity,
+                constraint: Some(constraint),
+            } => write!(
+                f,
+                "Constraint {constraint} references unknown entity {entity}"
+            ),
+            AcsError::UnknownEntity {
+                entity,
+                constraint: None,
+            } => write!(f, "Entity {entity} not found"),
+            AcsError::WrongEntityType {
+                entity,
+                expected,
+                found,
+                constraint,
+            } => {
+                if let Some(constraint) = constraint {
+                    write!(f, "Constraint {constraint} expects ")?;
+                } else {
+                    write!(f, "Expected ")?;
+                }
+                write!(f, "{entity} to be one of {expected:?}, found {found:?}")
+            }
+            AcsError::UnknownConstraint { constraint } => {
+                write!(f, "Constraint {constraint} not found")
+            }
+            AcsError::DuplicateId { constraint } => {
+                write!(f, "Constraint ID {constraint} is already in use")
+            }
+            AcsError::UnsupportedConstraint {
+                constraint,
+                message,
+            } => write!(f, "Constraint {constraint} is not supported: {message}"),
+            AcsError::EntityInUse { entity, dependents } => {
+                write!(f, "{entity} is still used by: {}", dependents.join(", "))
+            }
+            AcsError::InvalidGeometry { entity, message } => write!(f, "{entity}: {message}"),
+            AcsError::InvalidParameter { message } => write!(f, "{message}"),
+            AcsError::FixedParameter { parameter } => {
+                write!(f, "Parameter {parameter} is fixed and cannot be modified")
+            }
+            AcsError::ParseError { message } => write!(f, "Parse error: {message}"),
+            AcsError::SerializationError { message } => {
+                write!(f, "Serialization error: {message}")
+            }
+            AcsError::NumericalFailure { message } => write!(f, "Numerical failure: {message}"),
+            AcsError::NonFiniteResidual { constraint } => {
+                write!(
+                    f,
+                    "Constraint {constraint} has a residual that is not finite"
+                )
+            }
+            AcsError::InvalidExpression {
+                expression,
+                message,
+            } => write!(f, "Invalid expression {expression}: {message}"),
+            AcsError::UnknownVariable { variable } => write!(f, "Variable {variable} not found"),
+            AcsError::Cancelled => write!(f, "Solve was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AcsError {}
+
+impl From<AcsError> for JsValue {
+    fn from(error: AcsError) -> Self {
+        serde_wasm_bindgen::to_value(&error)
+            .unwrap_or_else(|_| JsValue::from_str(&error.to_string()))
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::AcsError;
+
+/// Functions of one argument that expressions may call. Angles are in radians.
+const FUNCTIONS: &[&str] = &["sqrt", "abs", "sin", "cos", "tan"];
+
+/// Names that cannot be used for variables
+const RESERVED: &[&str] = &["pi"];
+
+/// Deepest expression tree the parser accepts. Parsing and evaluation
+/// recurse once per level, so this keeps them off the end of the stack.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Call(String, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(
+        &self,
+        lookup: &mut dyn FnMut(&str) -> Result<f64, AcsError>,
+    ) -> Result<f64, AcsError> {
+        Ok(match self {
+            Node::Number(value) => *value,
+            Node::Variable(name) => lookup(name)?,
+            Node::Negate(operand) => -operand.evaluate(lookup)?,
+            Node::Binary(op, left, right) => {
+                let (left, right) = (left.evaluate(lookup)?, right.evaluate(lookup)?);
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left.powf(right),
+                }
+            }
+            Node::Call(function, argument) => {
+                let argument = argument.evaluate(lookup)?;
+                match function.as_str() {
+                    "sqrt" => argument.sqrt(),
+                    "abs" => argument.abs(),
+                    "sin" => argument.sin(),
+                    "cos" => argument.cos(),
+                    _ => argument.tan(),
+                }
+            }
+        })
+    }
+
+    fn collect_variables(&self, out: &mut Vec<String>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Variable(name) => {
+                if !out.contains(name) {
+                    out.push(name.clone());
+                }
+            }
+            Node::Negate(operand) | Node::Call(_, operand) => operand.collect_variables(out),
+            Node::Binary(_, left, right) => {
+                left.collect_variables(out);
+                right.collect_variables(out);
+            }
+        }
+    }
+}
+
+/// An arithmetic expression over named variables, e.g. `width - 2*margin`.
+///
+/// Supports numbers, `+ - * / ^`, parentheses, the constant `pi` and the
+/// functions `sqrt`, `abs`, `sin`, `cos` and `tan`. Displays as the source
+/// it was parsed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, AcsError> {
+        let mut parser = Parser {
+            source,
+            chars: source.chars().collect(),
+            position: 0,
+            nesting: 0,
+        };
+        let (root, _) = parser.expression()?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.peek() {
+            return Err(parser.error(&format!("Unexpected '{c}'")));
+        }
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    /// An expression that is just a number
+    pub fn number(value: f64) -> Self {
+        Self {
+            source: value.to_string(),
+            root: Node::Number(value),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Names of the variables the expression references, in order of
+    /// first appearance
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.root.collect_variables(&mut variables);
+        variables
+    }
+
+    /// Evaluate with the values of the variables in `variables`
+    pub fn evaluate(&self, variables: &VariableTable) -> Result<f64, AcsError> {
+        variables.evaluate(self)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A parsed node and the depth of its tree
+type Parsed = (Node, usize);
+
+/// Recursive-descent parser. Precedence from loosest to tightest: `+ -`,
+/// `* /`, unary minus, `^` (right-associative).
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    position: usize,
+    /// Current depth of recursion through `factor`
+    nesting: usize,
+}
+
+impl Parser<'_> {
+    fn expression(&mut self) -> Result<Parsed, AcsError> {
+        let mut left = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.next_symbol() {
+            self.position += 1;
+            let right = self.term()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Parsed, AcsError> {
+        let mut left = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.next_symbol() {
+            self.position += 1;
+            let right = self.factor()?;
+            left = self.binary(op, left, right)?;
+        }
+        Ok(left)
+    }
+
+    /// Every nested construct recurses through here, so this is where the
+    /// nesting is bounded before it can exhaust the stack
+    fn factor(&mut self) -> Result<Parsed, AcsError> {
+        if self.nesting >= MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+        self.nesting += 1;
+        let factor = self.unary();
+        self.nesting -= 1;
+        factor
+    }
+
+    fn unary(&mut self) -> Result<Parsed, AcsError> {
+        match self.next_symbol() {
+            Some('-') => {
+                self.position += 1;
+                let (operand, depth) = self.factor()?;
+                self.nested(Node::Negate(Box::new(operand)), depth)
+            }
+            Some('+') => {
+                self.position += 1;
+                self.factor()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Parsed, AcsError> {
+        let base = self.primary()?;
+        if self.next_symbol() == Some('^') {
+            self.position += 1;
+            let exponent = self.factor()?;
+            return self.binary('^', base, exponent);
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Parsed, AcsError> {
+        match self.next_symbol() {
+            Some('(') => {
+                self.position += 1;
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => Ok((self.number()?, 1)),
+            Some(c) if is_identifier_start(c) => {
+                let name = self.identifier();
+                if FUNCTIONS.contains(&name.as_str()) {
+                    self.expect('(')?;
+                    let (argument, depth) = self.expression()?;
+                    self.expect(')')?;
+                    self.nested(Node::Call(name, Box::new(argument)), depth)
+                } else if name == "pi" {
+                    Ok((Node::Number(std::f64::consts::PI), 1))
+                } else {
+                    Ok((Node::Variable(name), 1))
+                }
+            }
+            Some(c) => Err(self.error(&format!("Unexpected '{c}'"))),
+            None => Err(self.error("Unexpected end of expression")),
+        }
+    }
+
+    fn binary(&self, op: char, left: Parsed, right: Parsed) -> Result<Parsed, AcsError> {
+        let depth = left.1.max(right.1);
+        self.nested(Node::Binary(op, Box::new(left.0), Box::new(right.0)), depth)
+    }
+
+    /// `node` one level above children of depth `depth`. Long chains such
+    /// as `1 + 1 + ...` build deep trees without nesting, so the depth of
+    /// the tree is bounded as well.
+    fn nested(&self, node: Node, depth: usize) -> Result<Parsed, AcsError> {
+        if depth >= MAX_DEPTH {
+            return Err(self.too_deep());
+        }
+        Ok((node, depth + 1))
+    }
+
+    fn too_deep(&self) -> AcsError {
+        self.error(&format!(
+            "Expression is nested more than {MAX_DEPTH} levels deep"
+        ))
+    }
+
+    fn number(&mut self) -> Result<Node, AcsError> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            let exponent_sign =
+                (c == '+' || c == '-') && matches!(self.chars[self.position - 1], 'e' | 'E');
+            if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse()
+            .map(Node::Number)
+            .map_err(|_| self.error(&format!("Invalid number '{text}'")))
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        while self.peek().is_some_and(is_identifier_char) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), AcsError> {
+        match self.next_symbol() {
+            Some(c) if c == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("Expected '{expected}', found '{c}'"))),
+            None => Err(self.error(&format!("Expected '{expected}'"))),
+        }
+    }
+
+    /// The next character that is not whitespace, without consuming it
+    fn next_symbol(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.peek()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn error(&self, message: &str) -> AcsError {
+        AcsError::InvalidExpression {
+            expression: self.source.to_string(),
+            message: format!("{message} at position {}", self.position),
+        }
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Named design variables, each defined by an expression over the others,
+/// e.g. `width = 40` and `hole_r = width / 8`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariableTable {
+    variables: BTreeMap<String, Expression>,
+}
+
+impl VariableTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define or redefine a variable. The expression may reference
+    /// variables that are not defined yet, but not, through other
+    /// variables, the variable itself.
+    pub fn set(&mut self, name: &str, expression: Expression) -> Result<(), AcsError> {
+        let valid_name = name.chars().next().is_some_and(is_identifier_start)
+            && name.chars().all(is_identifier_char)
+            && !FUNCTIONS.contains(&name)
+            && !RESERVED.contains(&name);
+        if !valid_name {
+            return Err(AcsError::InvalidExpression {
+                expression: name.to_string(),
+                message: "Not a valid variable name".into(),
+            });
+        }
+
+        // The table is acyclic, so a cycle has to pass through `name`
+        let mut pending = expression.variables();
+        let mut visited = Vec::new();
+        while let Some(variable) = pending.pop() {
+            if variable == name {
+                return Err(AcsError::InvalidExpression {
+                    expression: expression.to_string(),
+                    message: format!("{name} would depend on itself"),
+                });
+            }
+            if !visited.contains(&variable) {
+                if let Some(definition) = self.variables.get(&variable) {
+                    pending.extend(definition.variables());
+                }
+                visited.push(variable);
+            }
+        }
+
+        self.variables.insert(name.to_string(), expression);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expression> {
+        self.variables.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Expression> {
+        self.variables.remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    /// Variables and their definitions, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Expression)> {
+        self.variables
+            .iter()
+            .map(|(name, expression)| (name.as_str(), expression))
+    }
+
+    /// Current value of a variable
+    pub fn value(&self, name: &str) -> Result<f64, AcsError> {
+        self.cached_value(name, &mut BTreeMap::new())
+    }
+
+    /// Values of every variable, sorted by name
+    pub fn values(&self) -> Result<BTreeMap<String, f64>, AcsError> {
+        let mut cache = BTreeMap::new();
+        for name in self.variables.keys() {
+            self.cached_value(name, &mut cache)?;
+        }
+        Ok(cache)
+    }
+
+    /// Evaluate an expression over the variables. Fails on unknown
+    /// variables and on results that are not finite, e.g. from `1 / 0`.
+    pub fn evaluate(&self, expression: &Expression) -> Result<f64, AcsError> {
+        self.cached_evaluate(expression, &mut BTreeMap::new())
+    }
+
+    /// Variables are evaluated once per call and looked up in `cache`
+    /// afterwards, so shared dependencies do not multiply the work
+    fn cached_value(&self, name: &str, cache: &mut BTreeMap<String, f64>) -> Result<f64, AcsError> {
+        if let Some(&value) = cache.get(name) {
+            return Ok(value);
+        }
+        let expression = self
+            .variables
+            .get(name)
+            .ok_or_else(|| AcsError::UnknownVariable {
+                variable: name.to_string(),
+            })?;
+        let value = self.cached_evaluate(expression, cache)?;
+        cache.insert(name.to_string(), value);
+        Ok(value)
+    }
+
+    fn cached_evaluate(
+        &self,
+        expression: &Expression,
+        cache: &mut BTreeMap<String, f64>,
+    ) -> Result<f64, AcsError> {
+        let value = expression
+            .root
+            .evaluate(&mut |name: &str| self.cached_value(name, cache))?;
+        if !value.is_finite() {
+            return Err(AcsError::InvalidExpression {
+                expression: expression.to_string(),
+                message: format!("Evaluates to {value}"),
+            });
+        }
+        Ok(value)
+    }
+}
@@ -1,4 +1,5 @@
-use crate::parameter_system::ParametricEntity;
+use crate::AcsError;
+use crate::parameter_system::{EntityType, ParametricEntity};
 use std::collections::HashMap;
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -8,14 +9,26 @@ pub struct Point {
     pub id: String,
     pub x: f64,
     pub y: f64,
+    /// Locks both coordinates
     pub fixed: bool,
+    /// Locks only x, e.g. for a guide point that slides vertically
+    pub fixed_x: bool,
+    /// Locks only y
+    pub fixed_y: bool,
 }
 
 #[wasm_bindgen]
 impl Point {
     #[wasm_bindgen(constructor)]
     pub fn new(id: String, x: f64, y: f64, fixed: bool) -> Self {
-        Self { id, x, y, fixed }
+        Self {
+            id,
+            x,
+            y,
+            fixed,
+            fixed_x: false,
+            fixed_y: false,
+        }
     }
 }
 
@@ -24,12 +37,11 @@ impl ParametricEntity for Point {
         vec![self.x, self.y]
     }
 
-    fn set_parameters(&mut self, params: &[f64]) -> Result<(), String> {
+    fn set_parameters(&mut self, params: &[f64]) -> Result<(), AcsError> {
         if params.len() != 2 {
-            return Err(format!(
-                "Point requires exactly 2 parameters, got {}",
-                params.len()
-            ));
+            return Err(AcsError::InvalidParameter {
+                message: format!("Point requires exactly 2 parameters, got {}", params.len()),
+            });
         }
         self.x = params[0];
         self.y = params[1];
@@ -42,8 +54,9 @@ impl ParametricEntity for Point {
 
     fn is_parameter_fixed(&self, param_index: usize) -> bool {
         match param_index {
-            0 | 1 => self.fixed, // Both x and y are fixed if the point is fixed
-            _ => true,           // Invalid parameter indices are considered fixed
+            0 => self.fixed || self.fixed_x,
+            1 => self.fixed || self.fixed_y,
+            _ => true, // Invalid parameter indices are considered fixed
         }
     }
 }
@@ -71,6 +84,7 @@ pub struct Circle {
     pub center: String, // Point ID
     pub radius: f64,
     pub fixed: bool,
+    pub fixed_radius: bool,
 }
 
 #[wasm_bindgen]
@@ -82,6 +96,7 @@ impl Circle {
             center,
             radius,
             fixed,
+            fixed_radius: false,
         }
     }
 }
@@ -91,12 +106,11 @@ impl ParametricEntity for Circle {
         vec![self.radius]
     }
 
-    fn set_parameters(&mut self, params: &[f64]) -> Result<(), String> {
+    fn set_parameters(&mut self, params: &[f64]) -> Result<(), AcsError> {
         if params.len() != 1 {
-            return Err(format!(
-                "Circle requires exactly 1 parameter, got {}",
-                params.len()
-            ));
+            return Err(AcsError::InvalidParameter {
+                message: format!("Circle requires exactly 1 parameter, got {}", params.len()),
+            });
         }
         self.radius = params[0];
         Ok(())
@@ -108,8 +122,8 @@ impl ParametricEntity for Circle {
 
     fn is_parameter_fixed(&self, param_index: usize) -> bool {
         match param_index {
-            0 => self.fixed, // Radius is fixed if the circle is fixed
-            _ => true,       // Invalid parameter indices are considered fixed
+            0 => self.fixed || self.fixed_radius,
+            _ => true, // Invalid parameter indices are considered fixed
         }
     }
 }
@@ -122,7 +136,11 @@ pub struct Arc {
     pub radius: f64,
     pub start_angle: f64, // in radians
     pub end_angle: f64,   // in radians
+    /// Locks the radius and both angles
     pub fixed: bool,
+    pub fixed_radius: bool,
+    pub fixed_start_angle: bool,
+    pub fixed_end_angle: bool,
 }
 
 #[wasm_bindgen]
@@ -143,6 +161,9 @@ impl Arc {
             start_angle,
             end_angle,
             fixed,
+            fixed_radius: false,
+            fixed_start_angle: false,
+            fixed_end_angle: false,
         }
     }
 }
@@ -152,12 +173,11 @@ impl ParametricEntity for Arc {
         vec![self.radius, self.start_angle, self.end_angle]
     }
 
-    fn set_parameters(&mut self, params: &[f64]) -> Result<(), String> {
+    fn set_parameters(&mut self, params: &[f64]) -> Result<(), AcsError> {
         if params.len() != 3 {
-            return Err(format!(
-                "Arc requires exactly 3 parameters, got {}",
-                params.len()
-            ));
+            return Err(AcsError::InvalidParameter {
+                message: format!("Arc requires exactly 3 parameters, got {}", params.len()),
+            });
         }
         self.radius = params[0];
         self.start_angle = params[1];
@@ -175,18 +195,66 @@ impl ParametricEntity for Arc {
 
     fn is_parameter_fixed(&self, param_index: usize) -> bool {
         match param_index {
-            0..=2 => self.fixed, // All parameters are fixed if the arc is fixed
-            _ => true,           // Invalid parameter indices are considered fixed
+            0 => self.fixed || self.fixed_radius,
+            1 => self.fixed || self.fixed_start_angle,
+            2 => self.fixed || self.fixed_end_angle,
+            _ => true, // Invalid parameter indices are considered fixed
         }
     }
 }
 
+/// A standalone unknown, e.g. a length shared by several segments, that
+/// constraints can reference like an entity and the solver determines
+#[derive(Debug, Clone, PartialEq)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct ScalarVariable {
+    pub id: String,
+    pub value: f64,
+    pub fixed: bool,
+}
+
+#[wasm_bindgen]
+impl ScalarVariable {
+    #[wasm_bindgen(constructor)]
+    pub fn new(id: String, value: f64, fixed: bool) -> Self {
+        Self { id, value, fixed }
+    }
+}
+
+impl ParametricEntity for ScalarVariable {
+    fn get_parameters(&self) -> Vec<f64> {
+        vec![self.value]
+    }
+
+    fn set_parameters(&mut self, params: &[f64]) -> Result<(), AcsError> {
+        if params.len() != 1 {
+            return Err(AcsError::InvalidParameter {
+                message: format!(
+                    "Scalar variable requires exactly 1 parameter, got {}",
+                    params.len()
+                ),
+            });
+        }
+        self.value = params[0];
+        Ok(())
+    }
+
+    fn parameter_names(&self) -> Vec<String> {
+        vec![format!("{}.value", self.id)]
+    }
+
+    fn is_parameter_fixed(&self, param_index: usize) -> bool {
+        param_index != 0 || self.fixed
+    }
+}
+
 #[derive(Debug)]
 pub struct GeometrySystem {
     points: HashMap<String, Point>,
     lines: HashMap<String, Line>,
     circles: HashMap<String, Circle>,
     arcs: HashMap<String, Arc>,
+    scalar_variables: HashMap<String, ScalarVariable>,
 }
 
 impl Default for GeometrySystem {
@@ -202,6 +270,7 @@ impl GeometrySystem {
             lines: HashMap::new(),
             circles: HashMap::new(),
             arcs: HashMap::new(),
+            scalar_variables: HashMap::new(),
         }
     }
 
@@ -211,10 +280,36 @@ impl GeometrySystem {
         id
     }
 
-    pub fn add_line(&mut self, line: Line) -> String {
+    /// Add a line, checking that both of its endpoints are existing points
+    pub fn add_line(&mut self, line: Line) -> Result<String, AcsError> {
+        self.validate_line(&line)?;
         let id = line.id.clone();
         self.lines.insert(id.clone(), line);
-        id
+        Ok(id)
+    }
+
+    /// Check that a line references two distinct, existing points
+    pub fn validate_line(&self, line: &Line) -> Result<(), AcsError> {
+        for endpoint in [&line.start, &line.end] {
+            if !self.points.contains_key(endpoint) {
+                return Err(match self.entity_type(endpoint) {
+                    Some(found) => AcsError::WrongEntityType {
+                        entity: endpoint.clone(),
+                        expected: vec![EntityType::Point],
+                        found,
+                        constraint: None,
+                    },
+                    None => AcsError::unknown_entity(endpoint),
+                });
+            }
+        }
+        if line.start == line.end {
+            return Err(AcsError::InvalidGeometry {
+                entity: line.id.clone(),
+                message: format!("both endpoints are {}", line.start),
+            });
+        }
+        Ok(())
     }
 
     pub fn get_point(&self, id: &str) -> Option<&Point> {
@@ -229,6 +324,14 @@ impl GeometrySystem {
         self.lines.get(id)
     }
 
+    /// Resolve a line ID to the IDs of its start and end points
+    pub fn get_line_endpoints(&self, id: &str) -> Result<(String, String), AcsError> {
+        self.lines
+            .get(id)
+            .map(|line| (line.start.clone(), line.end.clone()))
+            .ok_or_else(|| AcsError::unknown_entity(id))
+    }
+
     pub fn get_all_points(&self) -> &HashMap<String, Point> {
         &self.points
     }
@@ -285,25 +388,180 @@ impl GeometrySystem {
         &mut self.arcs
     }
 
-    pub fn update_point(&mut self, id: &str, point: Point) -> Result<(), String> {
+    /// Add a scalar variable, replacing one with the same ID. Constraints
+    /// look entities up by ID alone, so an ID already used by another kind of
+    /// entity is rejected.
+    pub fn add_scalar_variable(&mut self, variable: ScalarVariable) -> Result<String, AcsError> {
+        if let Some(found) = self.entity_type(&variable.id)
+            && found != EntityType::ScalarVariable
+        {
+            return Err(AcsError::InvalidGeometry {
+                entity: variable.id.clone(),
+                message: format!("ID is already used by a {found:?}"),
+            });
+        }
+        let id = variable.id.clone();
+        self.scalar_variables.insert(id.clone(), variable);
+        Ok(id)
+    }
+
+    pub fn get_scalar_variable(&self, id: &str) -> Option<&ScalarVariable> {
+        self.scalar_variables.get(id)
+    }
+
+    pub fn get_all_scalar_variables(&self) -> &HashMap<String, ScalarVariable> {
+        &self.scalar_variables
+    }
+
+    pub fn get_all_scalar_variables_mut(&mut self) -> &mut HashMap<String, ScalarVariable> {
+        &mut self.scalar_variables
+    }
+
+    /// Check whether an entity of the given type exists
+    pub fn contains_entity(&self, id: &str, entity_type: &EntityType) -> bool {
+        match entity_type {
+            EntityType::Point => self.points.contains_key(id),
+            EntityType::Line => self.lines.contains_key(id),
+            EntityType::Circle => self.circles.contains_key(id),
+            EntityType::Arc => self.arcs.contains_key(id),
+            EntityType::ScalarVariable => self.scalar_variables.contains_key(id),
+        }
+    }
+
+    /// Type of the entity with the given ID, if any
+    pub fn entity_type(&self, id: &str) -> Option<EntityType> {
+        [
+            EntityType::Point,
+            EntityType::Line,
+            EntityType::Circle,
+            EntityType::Arc,
+            EntityType::ScalarVariable,
+        ]
+        .into_iter()
+        .find(|entity_type| self.contains_entity(id, entity_type))
+    }
+
+    pub fn remove_point(&mut self, id: &str) -> Option<Point> {
+        self.points.remove(id)
+    }
+
+    pub fn remove_line(&mut self, id: &str) -> Option<Line> {
+        self.lines.remove(id)
+    }
+
+    pub fn remove_circle(&mut self, id: &str) -> Option<Circle> {
+        self.circles.remove(id)
+    }
+
+    pub fn remove_arc(&mut self, id: &str) -> Option<Arc> {
+        self.arcs.remove(id)
+    }
+
+    pub fn remove_scalar_variable(&mut self, id: &str) -> Option<ScalarVariable> {
+        self.scalar_variables.remove(id)
+    }
+
+    /// IDs of the lines that use the given point as an endpoint
+    pub fn lines_using_point(&self, point_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .lines
+            .values()
+            .filter(|line| line.start == point_id || line.end == point_id)
+            .map(|line| line.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// IDs of the circles centered on the given point
+    pub fn circles_using_point(&self, point_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .circles
+            .values()
+            .filter(|circle| circle.center == point_id)
+            .map(|circle| circle.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// IDs of the arcs centered on the given point
+    pub fn arcs_using_point(&self, point_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .arcs
+            .values()
+            .filter(|arc| arc.center == point_id)
+            .map(|arc| arc.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn update_point(&mut self, id: &str, point: Point) -> Result<(), AcsError> {
         if !self.points.contains_key(id) {
-            return Err("Point not found".to_string());
+            return Err(AcsError::unknown_entity(id));
         }
         self.points.insert(id.to_string(), point);
         Ok(())
     }
 
-    pub fn update_circle(&mut self, id: &str, circle: Circle) -> Result<(), String> {
+    pub fn update_circle(&mut self, id: &str, circle: Circle) -> Result<(), AcsError> {
         if !self.circles.contains_key(id) {
-            return Err("Circle not found".to_string());
+            return Err(AcsError::unknown_entity(id));
         }
         self.circles.insert(id.to_string(), circle);
         Ok(())
     }
 
-    pub fn update_arc(&mut self, id: &str, arc: Arc) -> Result<(), String> {
+    /// Lock or unlock a single parameter of a point (`"x"`, `"y"`), circle
+    /// (`"radius"`), arc (`"radius"`, `"start_angle"`, `"end_angle"`) or
+    /// scalar variable (`"value"`).
+    /// A fixed entity keeps all its parameters locked regardless.
+    pub fn set_parameter_locked(
+        &mut self,
+        id: &str,
+        parameter: &str,
+        locked: bool,
+    ) -> Result<(), AcsError> {
+        let lock = if let Some(point) = self.points.get_mut(id) {
+            match parameter {
+                "x" => Some(&mut point.fixed_x),
+                "y" => Some(&mut point.fixed_y),
+                _ => None,
+            }
+        } else if let Some(circle) = self.circles.get_mut(id) {
+            match parameter {
+                "radius" => Some(&mut circle.fixed_radius),
+                _ => None,
+            }
+        } else if let Some(arc) = self.arcs.get_mut(id) {
+            match parameter {
+                "radius" => Some(&mut arc.fixed_radius),
+                "start_angle" => Some(&mut arc.fixed_start_angle),
+                "end_angle" => Some(&mut arc.fixed_end_angle),
+                _ => None,
+            }
+        } else if let Some(variable) = self.scalar_variables.get_mut(id) {
+            match parameter {
+                "value" => Some(&mut variable.fixed),
+                _ => None,
+            }
+        } else if self.lines.contains_key(id) {
+            None
+        } else {
+            return Err(AcsError::unknown_entity(id));
+        };
+
+        let lock = lock.ok_or_else(|| AcsError::InvalidParameter {
+            message: format!("{id} has no parameter {parameter}"),
+        })?;
+        *lock = locked;
+        Ok(())
+    }
+
+    pub fn update_arc(&mut self, id: &str, arc: Arc) -> Result<(), AcsError> {
         if !self.arcs.contains_key(id) {
-            return Err("Arc not found".to_string());
+            return Err(AcsError::unknown_entity(id));
         }
         self.arcs.insert(id.to_string(), arc);
         Ok(())
@@ -0,0 +1,191 @@
+use nalgebra::DVector;
+
+use crate::{
+    AcsError, BoundConstraint, ConstraintGraph, Deadline, GeometrySystem, ParameterManager,
+    SolveObserver, SolveProgress, Solver, SolverOptions, SolverResult,
+    decomposition::{self, ClusterStatus},
+    sparse::{self, FreeColumns},
+};
+
+/// Initial damping relative to the largest diagonal entry of the scaled JᵀJ
+const INITIAL_DAMPING: f64 = 1e-3;
+
+/// Damping beyond which steps are too small to make progress
+const MAX_DAMPING: f64 = 1e16;
+
+/// Levenberg–Marquardt with Nielsen's damping update and Marquardt's
+/// parameter scaling. Slower per iteration than dog-leg on easy sketches, but
+/// more robust when the Jacobian is close to singular.
+#[derive(Debug, Default)]
+pub struct LevenbergMarquardtSolver;
+
+impl LevenbergMarquardtSolver {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn solve_cluster(
+        param_manager: &mut ParameterManager,
+        constraints: &[Box<dyn BoundConstraint>],
+        columns: &FreeColumns,
+        options: &SolverOptions,
+        deadline: Deadline,
+        cluster: usize,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<ClusterStatus, AcsError> {
+        let mut residuals = sparse::evaluate_residuals(param_manager.get_parameters(), constraints);
+        let initial_error = residuals.norm();
+        let status = |converged, iterations, final_error| ClusterStatus {
+            converged,
+            iterations,
+            final_error,
+            initial_error,
+        };
+
+        // Column norms of J, kept at the largest value seen so that the
+        // scaling only ever grows, as Marquardt suggested
+        let mut scales = DVector::<f64>::zeros(columns.len());
+        let mut mu: Option<f64> = None;
+        let mut nu = 2.0;
+
+        let mut stagnant = 0;
+        for iter in 0..options.max_iterations {
+            let residual_norm = residuals.norm();
+            observer.on_iteration(&SolveProgress {
+                cluster,
+                iteration: iter,
+                residual_norm,
+                trust_radius: None,
+            });
+            if observer.is_cancelled() {
+                return Err(AcsError::Cancelled);
+            }
+
+            if residual_norm < options.tolerance {
+                log::debug!(
+                    "Cluster {cluster} converged in {} iterations (residual {:.2e})",
+                    iter,
+                    residual_norm
+                );
+                return Ok(status(true, iter, residual_norm));
+            }
+            if deadline.expired() {
+                log::warn!("Cluster {cluster} ran out of time after {iter} iterations");
+                return Ok(status(false, iter, residual_norm));
+            }
+            if stagnant > 5 {
+                log::debug!("Cluster {cluster} stagnated at residual norm {residual_norm:.2e}");
+                return Ok(status(false, iter, residual_norm));
+            }
+
+            let mut jacobian =
+                sparse::assemble_jacobian(param_manager.get_parameters(), constraints, columns);
+            let mut squared_norms = DVector::<f64>::zeros(columns.len());
+            for (_, column, value) in jacobian.triplet_iter() {
+                squared_norms[column] += value * value;
+            }
+            for (scale, squared) in scales.iter_mut().zip(squared_norms.iter()) {
+                *scale = scale.max(squared.sqrt());
+            }
+            let scales_or_one = scales.map(|scale| if scale > 0.0 { scale } else { 1.0 });
+
+            // Solve in scaled variables δ̃ = D δ, i.e. with J D⁻¹
+            for (_, column, value) in jacobian.triplet_iter_mut() {
+                *value /= scales_or_one[column];
+            }
+            let gradient = &jacobian.transpose() * &residuals;
+            if gradient.amax() < 1e-14 {
+                // Stationary point with a nonzero residual
+                return Ok(status(false, iter, residual_norm));
+            }
+
+            let damping = *mu.get_or_insert_with(|| {
+                let max_diagonal = squared_norms
+                    .component_div(&scales_or_one.component_mul(&scales_or_one))
+                    .max();
+                INITIAL_DAMPING * max_diagonal
+            });
+            let scaled_step =
+                sparse::solve_damped_normal_equations(&jacobian, &-&gradient, damping)?;
+            let step = scaled_step.component_div(&scales_or_one);
+
+            let old_params = param_manager.get_parameters().to_vec();
+            for (column, &step_val) in step.iter().enumerate() {
+                let i = columns.parameter(column);
+                param_manager.set_parameter(i, old_params[i] + step_val)?;
+            }
+            let new_residuals =
+                sparse::evaluate_residuals(param_manager.get_parameters(), constraints);
+
+            let predicted_reduction =
+                residual_norm.powi(2) - (&residuals + &jacobian * &scaled_step).norm_squared();
+            let actual_reduction = residual_norm.powi(2) - new_residuals.norm_squared();
+            let rho = if predicted_reduction > 0.0 {
+                actual_reduction / predicted_reduction
+            } else {
+                -1.0
+            };
+
+            let next_mu = if rho > 0.0 {
+                if residual_norm - new_residuals.norm() < options.stagnation_threshold {
+                    stagnant += 1;
+                } else {
+                    stagnant = 0;
+                }
+                residuals = new_residuals;
+                nu = 2.0;
+                damping * (1.0 / 3.0f64).max(1.0 - (2.0 * rho - 1.0).powi(3))
+            } else {
+                for column in 0..columns.len() {
+                    let i = columns.parameter(column);
+                    param_manager.set_parameter(i, old_params[i])?;
+                }
+                let increased = damping * nu;
+                nu *= 2.0;
+                increased
+            };
+
+            if next_mu > MAX_DAMPING {
+                return Ok(status(false, iter + 1, residuals.norm()));
+            }
+            mu = Some(next_mu);
+        }
+
+        let final_error = residuals.norm();
+        Ok(status(
+            final_error < options.tolerance,
+            options.max_iterations,
+            final_error,
+        ))
+    }
+}
+
+impl Solver for LevenbergMarquardtSolver {
+    fn solve(
+        &self,
+        geometry: &mut GeometrySystem,
+        constraint_graph: &ConstraintGraph,
+        options: &SolverOptions,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<SolverResult, AcsError> {
+        let deadline = Deadline::start(options.time_budget);
+        decomposition::solve_by_cluster(
+            geometry,
+            constraint_graph,
+            options,
+            deadline,
+            observer,
+            |cluster, param_manager, constraints, columns, observer| {
+                Self::solve_cluster(
+                    param_manager,
+                    constraints,
+                    columns,
+                    options,
+                    deadline,
+                    cluster,
+                    observer,
+                )
+            },
+        )
+    }
+}
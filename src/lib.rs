@@ -1,19 +1,37 @@
+pub mod analysis;
+pub mod autodiff;
 pub mod constraints;
+pub mod decomposition;
+pub mod drag;
+pub mod error;
+pub mod expression;
 pub mod geometry;
 pub mod parameter_system;
+pub mod priority;
+pub mod registry;
 pub mod solver;
+pub mod sparse;
 
 pub mod dogleg_solver;
+pub mod levenberg_marquardt_solver;
 
+pub use analysis::*;
+pub use autodiff::*;
 pub use constraints::*;
 pub use dogleg_solver::*;
+pub use error::*;
+pub use expression::*;
 pub use geometry::*;
+pub use levenberg_marquardt_solver::*;
 pub use parameter_system::*;
+pub use registry::*;
 pub use solver::*;
 
 // WebAssembly bindings
 pub mod bindings {
     pub mod geometry;
+    pub mod js_constraint;
+    pub mod logging;
     pub mod solver;
     pub mod types;
 }
@@ -1,4 +1,8 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
+
+use serde::Serialize;
+
+use crate::{AcsError, GeometrySystem, ScalarVariable};
 
 /// Trait for geometric entities that can provide parameters to the solver
 pub trait ParametricEntity {
@@ -6,7 +10,7 @@ pub trait ParametricEntity {
     fn get_parameters(&self) -> Vec<f64>;
 
     /// Update the entity with new parameter values
-    fn set_parameters(&mut self, params: &[f64]) -> Result<(), String>;
+    fn set_parameters(&mut self, params: &[f64]) -> Result<(), AcsError>;
 
     /// Get the names/descriptions of each parameter (for debugging)
     fn parameter_names(&self) -> Vec<String>;
@@ -31,18 +35,20 @@ pub struct ParameterInfo {
     pub is_fixed: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum EntityType {
     Point,
+    Line,
     Circle,
     Arc,
+    ScalarVariable,
     // Add more entity types as needed
 }
 
 /// Manages the global parameter vector and entity-to-parameter mapping
 pub struct ParameterManager {
-    /// Maps entity ID to its starting index in the global parameter vector
-    entity_to_global_index: HashMap<String, usize>,
+    /// Maps entity ID to its range of indices in the global parameter vector
+    entity_ranges: HashMap<String, Range<usize>>,
 
     /// Maps entity ID to its type
     entity_types: HashMap<String, EntityType>,
@@ -63,13 +69,68 @@ impl Default for ParameterManager {
 impl ParameterManager {
     pub fn new() -> Self {
         Self {
-            entity_to_global_index: HashMap::new(),
+            entity_ranges: HashMap::new(),
             entity_types: HashMap::new(),
             parameter_info: Vec::new(),
             parameters: Vec::new(),
         }
     }
 
+    /// Build a parameter manager holding every point, circle, arc and scalar
+    /// variable of the geometry
+    pub fn from_geometry(geometry: &GeometrySystem) -> Self {
+        let mut param_manager = Self::new();
+
+        // Register all points
+        for (id, point) in geometry.get_all_points() {
+            param_manager.register_entity(id.clone(), EntityType::Point, point);
+        }
+
+        // Register all circles
+        for (id, circle) in geometry.get_all_circles() {
+            param_manager.register_entity(id.clone(), EntityType::Circle, circle);
+        }
+
+        // Register all arcs
+        for (id, arc) in geometry.get_all_arcs() {
+            param_manager.register_entity(id.clone(), EntityType::Arc, arc);
+        }
+
+        // Register all scalar variables. The geometry keeps their IDs apart
+        // from those of other entities.
+        for (id, variable) in geometry.get_all_scalar_variables() {
+            param_manager.register_entity(id.clone(), EntityType::ScalarVariable, variable);
+        }
+
+        param_manager
+    }
+
+    /// Write the current parameter values back to the entities of a geometry
+    /// system built with `from_geometry`
+    pub fn sync_to_geometry(&mut self, geometry: &mut GeometrySystem) -> Result<(), AcsError> {
+        // Update points
+        for (id, point) in geometry.get_all_points_mut() {
+            self.update_entity_parameters(id, point)?;
+        }
+
+        // Update circles
+        for (id, circle) in geometry.get_all_circles_mut() {
+            self.update_entity_parameters(id, circle)?;
+        }
+
+        // Update arcs
+        for (id, arc) in geometry.get_all_arcs_mut() {
+            self.update_entity_parameters(id, arc)?;
+        }
+
+        // Update scalar variables
+        for (id, variable) in geometry.get_all_scalar_variables_mut() {
+            self.update_entity_parameters(id, variable)?;
+        }
+
+        Ok(())
+    }
+
     /// Register an entity with the parameter manager
     pub fn register_entity<T: ParametricEntity>(
         &mut self,
@@ -82,8 +143,10 @@ impl ParameterManager {
         let param_names = entity.parameter_names();
 
         // Add entity to mapping
-        self.entity_to_global_index
-            .insert(entity_id.clone(), start_index);
+        self.entity_ranges.insert(
+            entity_id.clone(),
+            start_index..start_index + entity_params.len(),
+        );
         self.entity_types
             .insert(entity_id.clone(), entity_type.clone());
 
@@ -108,31 +171,48 @@ impl ParameterManager {
         }
     }
 
+    /// Register a standalone scalar unknown, which constraints reference by
+    /// its ID with parameter index 0. Fails if an entity with the same ID is
+    /// already registered.
+    pub fn register_scalar_variable(&mut self, variable: &ScalarVariable) -> Result<(), AcsError> {
+        if self.entity_ranges.contains_key(&variable.id) {
+            return Err(AcsError::InvalidGeometry {
+                entity: variable.id.clone(),
+                message: "ID is already registered".into(),
+            });
+        }
+        self.register_entity(variable.id.clone(), EntityType::ScalarVariable, variable);
+        Ok(())
+    }
+
     /// Get the global parameter index for a specific entity parameter
     pub fn get_global_index(&self, entity_id: &str, param_index: usize) -> Option<usize> {
-        self.entity_to_global_index
+        self.entity_ranges
             .get(entity_id)
-            .map(|&start_idx| start_idx + param_index)
+            .map(|range| range.start + param_index)
     }
 
     /// Get all parameter indices for an entity
     pub fn get_entity_indices(&self, entity_id: &str) -> Option<Vec<usize>> {
-        if let Some(&start_idx) = self.entity_to_global_index.get(entity_id) {
-            // Find the entity type to determine how many parameters it has
-            if let Some(_entity_type) = self.entity_types.get(entity_id) {
-                let param_count = self
-                    .parameter_info
-                    .iter()
-                    .filter(|info| info.entity_id == entity_id)
-                    .count();
-
-                Some((start_idx..start_idx + param_count).collect())
-            } else {
-                None
-            }
-        } else {
-            None
+        self.entity_ranges
+            .get(entity_id)
+            .map(|range| range.clone().collect())
+    }
+
+    /// Resolve an entity parameter to its global index, failing if the entity
+    /// is not registered or has no such parameter
+    pub fn resolve_index(&self, entity_id: &str, param_index: usize) -> Result<usize, AcsError> {
+        let range = self
+            .entity_ranges
+            .get(entity_id)
+            .ok_or_else(|| AcsError::unknown_entity(entity_id))?;
+
+        if param_index >= range.len() {
+            return Err(AcsError::InvalidParameter {
+                message: format!("{entity_id} has no parameter {param_index}"),
+            });
         }
+        Ok(range.start + param_index)
     }
 
     /// Get the current global parameter vector
@@ -146,19 +226,20 @@ impl ParameterManager {
     }
 
     /// Update a specific parameter
-    pub fn set_parameter(&mut self, global_index: usize, value: f64) -> Result<(), String> {
+    pub fn set_parameter(&mut self, global_index: usize, value: f64) -> Result<(), AcsError> {
         if global_index >= self.parameters.len() {
-            return Err("Parameter index out of bounds".to_string());
+            return Err(AcsError::InvalidParameter {
+                message: format!("Parameter index {global_index} out of bounds"),
+            });
         }
 
         // Check if parameter is fixed
-        if let Some(info) = self.parameter_info.get(global_index) {
-            if info.is_fixed {
-                return Err(format!(
-                    "Parameter {} is fixed and cannot be modified",
-                    info.name
-                ));
-            }
+        if let Some(info) = self.parameter_info.get(global_index)
+            && info.is_fixed
+        {
+            return Err(AcsError::FixedParameter {
+                parameter: info.name.clone(),
+            });
         }
 
         self.parameters[global_index] = value;
@@ -170,15 +251,14 @@ impl ParameterManager {
         &mut self,
         entity_id: &str,
         entity: &mut T,
-    ) -> Result<(), String> {
-        if let Some(&start_idx) = self.entity_to_global_index.get(entity_id) {
+    ) -> Result<(), AcsError> {
+        if let Some(range) = self.entity_ranges.get(entity_id) {
+            let start_idx = range.start;
             let param_count = entity.num_parameters();
             let entity_params = &self.parameters[start_idx..start_idx + param_count];
             entity.set_parameters(entity_params)
         } else {
-            Err(format!(
-                "Entity {entity_id} not found in parameter manager"
-            ))
+            Err(AcsError::unknown_entity(entity_id))
         }
     }
 
@@ -187,17 +267,16 @@ impl ParameterManager {
         &mut self,
         entity_id: &str,
         entity: &T,
-    ) -> Result<(), String> {
-        if let Some(&start_idx) = self.entity_to_global_index.get(entity_id) {
+    ) -> Result<(), AcsError> {
+        if let Some(range) = self.entity_ranges.get(entity_id) {
+            let start_idx = range.start;
             let entity_params = entity.get_parameters();
             for (i, &value) in entity_params.iter().enumerate() {
                 self.parameters[start_idx + i] = value;
             }
             Ok(())
         } else {
-            Err(format!(
-                "Entity {entity_id} not found in parameter manager"
-            ))
+            Err(AcsError::unknown_entity(entity_id))
         }
     }
 
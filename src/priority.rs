@@ -0,0 +1,304 @@
+use nalgebra::{DMatrix, DVector};
+use nalgebra_sparse::{CooMatrix, CsrMatrix};
+
+use crate::{
+    AcsError, BoundConstraint, ConstraintEntry, ConstraintGraph, Deadline, JacobianEntry,
+    ParameterManager, SolveObserver, SolveProgress, SolverOptions, drag,
+    sparse::{self, FreeColumns, NormalEquations},
+};
+
+/// How often a soft step that breaks a higher level is halved before the
+/// level is given up on
+const MAX_BACKTRACKS: usize = 10;
+
+/// Singular values below this fraction of the largest one are ignored when
+/// solving for a soft step
+const STEP_TOLERANCE: f64 = 1e-12;
+
+/// A bound constraint with its residual and Jacobian rows scaled by a weight
+pub struct WeightedConstraint {
+    constraint: Box<dyn BoundConstraint>,
+    weight: f64,
+}
+
+impl WeightedConstraint {
+    pub fn new(constraint: Box<dyn BoundConstraint>, weight: f64) -> Self {
+        Self { constraint, weight }
+    }
+}
+
+impl BoundConstraint for WeightedConstraint {
+    fn num_residuals(&self) -> usize {
+        self.constraint.num_residuals()
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.constraint.parameters()
+    }
+
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        self.constraint.residual(params, out);
+        for value in out.iter_mut() {
+            *value *= self.weight;
+        }
+    }
+
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>) {
+        let start = out.len();
+        self.constraint.jacobian_entries(params, out);
+        for (_, _, value) in &mut out[start..] {
+            *value *= self.weight;
+        }
+    }
+}
+
+/// Bind every constraint of a graph, scaling those with a weight other than 1
+pub fn bind_weighted(
+    param_manager: &ParameterManager,
+    constraint_graph: &ConstraintGraph,
+) -> Result<Vec<Box<dyn BoundConstraint>>, AcsError> {
+    let constraints = sparse::bind_constraints(param_manager, constraint_graph.get_constraints())?;
+
+    Ok(constraints
+        .into_iter()
+        .zip(constraint_graph.get_entries())
+        .map(|(constraint, entry)| {
+            if entry.weight == 1.0 {
+                constraint
+            } else {
+                Box::new(WeightedConstraint::new(constraint, entry.weight))
+            }
+        })
+        .collect())
+}
+
+/// Split constraint indices into priority levels: the hard constraints,
+/// possibly none, then one level per soft priority in ascending order
+pub fn levels(entries: &[ConstraintEntry], constraints: &[usize]) -> Vec<Vec<usize>> {
+    let mut priorities: Vec<u32> = constraints
+        .iter()
+        .filter_map(|&i| entries[i].priority)
+        .collect();
+    priorities.sort_unstable();
+    priorities.dedup();
+
+    let mut levels = vec![Vec::new(); priorities.len() + 1];
+    for &i in constraints {
+        let level = match entries[i].priority {
+            None => 0,
+            Some(priority) => priorities.partition_point(|&p| p < priority) + 1,
+        };
+        levels[level].push(i);
+    }
+    levels
+}
+
+/// Free parameters the given constraints depend on, sorted
+pub fn free_parameters(constraints: &[Box<dyn BoundConstraint>], is_free: &[bool]) -> Vec<usize> {
+    let mut parameters: Vec<usize> = constraints
+        .iter()
+        .flat_map(|c| c.parameters())
+        .filter(|&i| is_free[i])
+        .collect();
+    parameters.sort_unstable();
+    parameters.dedup();
+    parameters
+}
+
+/// The constraints of one cluster by level, as from `levels`, and what
+/// soft steps may move
+pub struct Hierarchy<'a> {
+    pub levels: &'a [Vec<Box<dyn BoundConstraint>>],
+    /// Free parameters of the cluster, sorted
+    pub parameters: &'a [usize],
+    /// Free parameters of the hard constraints
+    pub hard_columns: &'a FreeColumns,
+}
+
+/// What the soft levels of a cluster report to, as the hard solve of the
+/// cluster does
+pub struct SoftContext<'a> {
+    pub cluster: usize,
+    /// Iterations already spent on the cluster, which soft iterations
+    /// continue from
+    pub iterations: usize,
+    pub deadline: Deadline,
+    pub observer: &'a mut dyn SolveObserver,
+}
+
+/// Satisfy the soft levels of a cluster whose hard constraints are already
+/// solved, one level at a time. Returns the number of iterations.
+///
+/// Each Gauss-Newton step of a level is restricted to the null space of the
+/// Jacobian of the levels before it, so to first order it leaves them alone.
+/// The step is then projected back onto the hard constraints, and halved
+/// while it would make the hard constraints or an earlier level worse.
+pub fn solve_soft_levels(
+    param_manager: &mut ParameterManager,
+    hierarchy: &Hierarchy,
+    options: &SolverOptions,
+    context: &mut SoftContext,
+) -> Result<usize, AcsError> {
+    let first_iteration = context.iterations;
+    for level in 1..hierarchy.levels.len() {
+        context.iterations += solve_level(param_manager, hierarchy, level, options, context)?;
+    }
+    Ok(context.iterations - first_iteration)
+}
+
+fn solve_level(
+    param_manager: &mut ParameterManager,
+    hierarchy: &Hierarchy,
+    level: usize,
+    options: &SolverOptions,
+    context: &mut SoftContext,
+) -> Result<usize, AcsError> {
+    let Hierarchy {
+        levels,
+        parameters,
+        hard_columns,
+    } = *hierarchy;
+    let columns = FreeColumns::from_parameters(param_manager.num_parameters(), parameters.to_vec());
+    let unit_scales = DVector::from_element(hard_columns.len(), 1.0);
+
+    // Earlier soft levels may not get worse than they were when this level
+    // started, beyond the tolerance
+    let level_norms = |params: &[f64]| -> Vec<f64> {
+        levels[..=level]
+            .iter()
+            .map(|constraints| sparse::evaluate_residuals(params, constraints).norm())
+            .collect()
+    };
+    let baseline = level_norms(param_manager.get_parameters());
+
+    for iter in 0..options.max_iterations {
+        let old_params = param_manager.get_parameters().to_vec();
+        let old_norms = level_norms(&old_params);
+        let norm = old_norms[level];
+
+        context.observer.on_iteration(&SolveProgress {
+            cluster: context.cluster,
+            iteration: context.iterations + iter,
+            residual_norm: norm,
+            trust_radius: None,
+        });
+        if context.observer.is_cancelled() {
+            return Err(AcsError::Cancelled);
+        }
+        if norm < options.tolerance || context.deadline.expired() {
+            return Ok(iter);
+        }
+
+        let residuals = sparse::evaluate_residuals(&old_params, &levels[level]);
+        let step = constrained_step(
+            &old_params,
+            &levels[..level],
+            &levels[level],
+            &columns,
+            &residuals,
+        )?;
+        if step.iter().all(|&value| value == 0.0) {
+            return Ok(iter);
+        }
+
+        let mut accepted = None;
+        let mut factor = 1.0;
+        for _ in 0..MAX_BACKTRACKS {
+            for (k, &i) in parameters.iter().enumerate() {
+                param_manager.set_parameter(i, old_params[i] + factor * step[k])?;
+            }
+            if !hard_columns.is_empty() {
+                drag::project(
+                    param_manager,
+                    &levels[0],
+                    hard_columns,
+                    &unit_scales,
+                    options,
+                    context.deadline,
+                )?;
+            }
+
+            let norms = level_norms(param_manager.get_parameters());
+            let keeps_higher = norms[0] <= old_norms[0].max(options.tolerance)
+                && (1..level).all(|j| norms[j] <= baseline[j] + options.tolerance);
+            if keeps_higher && norms[level] < norm {
+                accepted = Some(norms[level]);
+                break;
+            }
+            factor *= 0.5;
+        }
+
+        match accepted {
+            Some(new_norm) if norm - new_norm > options.stagnation_threshold => {}
+            Some(_) => return Ok(iter + 1),
+            None => {
+                for &i in parameters {
+                    param_manager.set_parameter(i, old_params[i])?;
+                }
+                return Ok(iter + 1);
+            }
+        }
+    }
+
+    Ok(options.max_iterations)
+}
+
+/// Minimum-norm Gauss-Newton step for the `current` constraints that keeps
+/// the `higher` levels unchanged to first order.
+///
+/// With `P` the projection onto the null space of the higher Jacobian `H`,
+/// the step is `B c` where `B = P Jᵀ` and `BᵀB c = -r`. `P` is applied
+/// through the sparse normal equations of `Hᵀ`, so apart from `B` itself
+/// the dense work only grows with the number of soft residuals.
+fn constrained_step(
+    params: &[f64],
+    higher: &[Vec<Box<dyn BoundConstraint>>],
+    current: &[Box<dyn BoundConstraint>],
+    columns: &FreeColumns,
+    residuals: &DVector<f64>,
+) -> Result<DVector<f64>, AcsError> {
+    let jacobian = sparse::assemble_jacobian(params, current, columns);
+    let mut directions = DMatrix::<f64>::zeros(columns.len(), jacobian.nrows());
+    for (row, column, &value) in jacobian.triplet_iter() {
+        directions[(column, row)] = value;
+    }
+
+    let higher = stacked_jacobian(params, higher, columns);
+    if higher.nrows() > 0 {
+        // P g = g - Hᵀ z with (H Hᵀ) z = H g
+        let transpose = higher.transpose();
+        let normal_equations = NormalEquations::factor(&transpose, 0.0)?;
+        for k in 0..directions.ncols() {
+            let direction = directions.column(k).into_owned();
+            let z = normal_equations.solve(&(&higher * &direction));
+            directions.set_column(k, &(direction - &transpose * &z));
+        }
+    }
+
+    let coefficients = (directions.transpose() * &directions)
+        .svd(true, true)
+        .solve(&-residuals, STEP_TOLERANCE)
+        .map_err(|message| AcsError::NumericalFailure {
+            message: message.to_string(),
+        })?;
+    Ok(directions * coefficients)
+}
+
+/// Sparse Jacobian of several levels of constraints, stacked in order
+fn stacked_jacobian(
+    params: &[f64],
+    levels: &[Vec<Box<dyn BoundConstraint>>],
+    columns: &FreeColumns,
+) -> CsrMatrix<f64> {
+    let rows = levels.iter().flatten().map(|c| c.num_residuals()).sum();
+    let mut stacked = CooMatrix::new(rows, columns.len());
+    let mut row_offset = 0;
+    for constraints in levels {
+        let jacobian = sparse::assemble_jacobian(params, constraints, columns);
+        for (row, column, &value) in jacobian.triplet_iter() {
+            stacked.push(row_offset + row, column, value);
+        }
+        row_offset += jacobian.nrows();
+    }
+    CsrMatrix::from(&stacked)
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{AcsError, Constraint, ConstraintType};
+
+/// Builds a custom constraint from the fields of its JSON form, without the
+/// `"type"` field
+pub type ConstraintFactory =
+    Arc<dyn Fn(&serde_json::Value) -> Result<Box<dyn Constraint>, AcsError>>;
+
+/// Custom constraint types by name, so `ConstraintType::Custom` and JSON
+/// constraints with a `"type"` that is not built in can be created
+#[derive(Clone, Default)]
+pub struct ConstraintRegistry {
+    factories: HashMap<String, ConstraintFactory>,
+}
+
+impl ConstraintRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the factory for `type_name`, replacing any earlier one.
+    /// Built-in type names cannot be registered, as JSON constraints with
+    /// those names always refer to the built-in constraints.
+    pub fn register<F>(&mut self, type_name: &str, factory: F) -> Result<(), AcsError>
+    where
+        F: Fn(&serde_json::Value) -> Result<Box<dyn Constraint>, AcsError> + 'static,
+    {
+        if ConstraintType::BUILT_IN_NAMES.contains(&type_name) {
+            return Err(AcsError::UnsupportedConstraint {
+                constraint: type_name.to_string(),
+                message: format!("{type_name} is a built-in constraint type"),
+            });
+        }
+        self.factories
+            .insert(type_name.to_string(), Arc::new(factory));
+        Ok(())
+    }
+
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.factories.contains_key(type_name)
+    }
+
+    /// Names of the registered types, sorted
+    pub fn type_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.factories.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Create a constraint of a registered type
+    pub fn create(
+        &self,
+        type_name: &str,
+        data: &serde_json::Value,
+    ) -> Result<Box<dyn Constraint>, AcsError> {
+        let factory =
+            self.factories
+                .get(type_name)
+                .ok_or_else(|| AcsError::UnsupportedConstraint {
+                    constraint: type_name.to_string(),
+                    message: format!("Unknown constraint type: {type_name}"),
+                })?;
+        factory(data)
+    }
+}
+
+impl fmt::Debug for ConstraintRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConstraintRegistry")
+            .field("types", &self.type_names())
+            .finish()
+    }
+}
@@ -1,9 +1,81 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use web_time::Instant;
+
 use crate::{
-    Constraint, ConstraintType, GeometrySystem, ParametricDogLegSolver, Point, create_constraint,
+    AcsError, BoundConstraint, Constraint, ConstraintDiagnosis, ConstraintRegistry,
+    ConstraintResidual, ConstraintType, DofAnalysis, EntityRef, Expression, GeometrySystem,
+    JacobianEntry, LevenbergMarquardtSolver, ParameterManager, ParametricDogLegSolver, Point,
+    VariableTable, analysis, constraint_residuals, create_constraint, diagnose_constraints, drag,
 };
 
+/// Optional identification, metadata and strength supplied when adding a
+/// constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintOptions {
+    /// Caller-supplied ID; one is generated when `None`
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: HashMap<String, String>,
+    /// Factor on the constraint's residual and Jacobian rows. Trades soft
+    /// constraints of the same priority off against each other.
+    pub weight: f64,
+    /// `None` for a hard constraint. Soft constraints are satisfied as far
+    /// as the hard constraints allow, a priority level at a time, starting
+    /// with the lowest value. A level never gives way to a later one.
+    pub priority: Option<u32>,
+    /// Drives the dimension of the constraint, e.g. the x of `EqualX`, from
+    /// the solver's variables. Its value replaces the one in the constraint
+    /// type and follows later changes to the variables.
+    pub expression: Option<Expression>,
+}
+
+impl Default for ConstraintOptions {
+    fn default() -> Self {
+        Self {
+            id: None,
+            name: None,
+            tags: Vec::new(),
+            metadata: HashMap::new(),
+            weight: 1.0,
+            priority: None,
+            expression: None,
+        }
+    }
+}
+
+/// Bookkeeping for a constraint stored in the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintEntry {
+    pub id: String,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    pub metadata: HashMap<String, String>,
+    pub weight: f64,
+    pub priority: Option<u32>,
+    /// Expression the dimension in `constraint_type` was evaluated from
+    pub expression: Option<Expression>,
+    pub constraint_type: ConstraintType,
+    /// Every entity the constraint depends on, including the endpoints of
+    /// referenced lines
+    pub entities: Vec<String>,
+}
+
+impl ConstraintEntry {
+    /// Whether the constraint is a preference rather than a requirement
+    pub fn is_soft(&self) -> bool {
+        self.priority.is_some()
+    }
+}
+
 pub struct ConstraintGraph {
     constraints: Vec<Box<dyn Constraint>>,
+    entries: Vec<ConstraintEntry>,
+    next_id: usize,
 }
 
 impl Default for ConstraintGraph {
@@ -16,12 +88,181 @@ impl ConstraintGraph {
     pub fn new() -> Self {
         Self {
             constraints: Vec::new(),
+            entries: Vec::new(),
+            next_id: 0,
         }
     }
 
     pub fn get_constraints(&self) -> &[Box<dyn Constraint>] {
         &self.constraints
     }
+
+    /// Entries describing each constraint, in the same order as `get_constraints`
+    pub fn get_entries(&self) -> &[ConstraintEntry] {
+        &self.entries
+    }
+
+    pub fn get_entry(&self, id: &str) -> Option<&ConstraintEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// IDs of the constraints that reference any of the given entities
+    pub fn constraints_referencing(&self, entity_ids: &[String]) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.entities.iter().any(|id| entity_ids.contains(id)))
+            .map(|entry| entry.id.clone())
+            .collect()
+    }
+
+    /// Bind the hard constraints to the parameter layout of `param_manager`.
+    /// Soft constraints are bound as constraints without residuals, so
+    /// indices still match the entries.
+    pub fn bind_hard_constraints(
+        &self,
+        param_manager: &ParameterManager,
+    ) -> Result<Vec<Box<dyn BoundConstraint>>, AcsError> {
+        self.constraints
+            .iter()
+            .zip(&self.entries)
+            .map(|(constraint, entry)| {
+                if entry.is_soft() {
+                    Ok(Box::new(Ignored) as Box<dyn BoundConstraint>)
+                } else {
+                    constraint.bind(param_manager)
+                }
+            })
+            .collect()
+    }
+
+    fn insert(
+        &mut self,
+        constraint: Box<dyn Constraint>,
+        constraint_type: ConstraintType,
+        entities: Vec<String>,
+        options: ConstraintOptions,
+    ) -> Result<&ConstraintEntry, AcsError> {
+        let id = match options.id {
+            Some(id) if self.get_entry(&id).is_some() => {
+                return Err(AcsError::DuplicateId { constraint: id });
+            }
+            Some(id) => id,
+            None => self.generate_id(),
+        };
+
+        self.constraints.push(constraint);
+        self.entries.push(ConstraintEntry {
+            id,
+            name: options.name,
+            tags: options.tags,
+            metadata: options.metadata,
+            weight: options.weight,
+            priority: options.priority,
+            expression: options.expression,
+            constraint_type,
+            entities,
+        });
+        Ok(&self.entries[self.entries.len() - 1])
+    }
+
+    fn replace(
+        &mut self,
+        index: usize,
+        constraint: Box<dyn Constraint>,
+        constraint_type: ConstraintType,
+    ) {
+        self.constraints[index] = constraint;
+        self.entries[index].constraint_type = constraint_type;
+    }
+
+    fn remove(&mut self, id: &str) -> Option<ConstraintEntry> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        self.constraints.remove(index);
+        Some(self.entries.remove(index))
+    }
+
+    fn generate_id(&mut self) -> String {
+        loop {
+            let id = format!("constraint_{}", self.next_id);
+            self.next_id += 1;
+            if self.get_entry(&id).is_none() {
+                return id;
+            }
+        }
+    }
+}
+
+/// Stand-in for a soft constraint where only hard constraints count
+struct Ignored;
+
+impl BoundConstraint for Ignored {
+    fn num_residuals(&self) -> usize {
+        0
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    fn residual(&self, _params: &[f64], _out: &mut [f64]) {}
+
+    fn jacobian_entries(&self, _params: &[f64], _out: &mut Vec<JacobianEntry>) {}
+}
+
+/// What to do with dependent lines, circles, arcs and constraints when an
+/// entity is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalPolicy {
+    /// Refuse to remove an entity that something else depends on
+    Error,
+    /// Remove the dependents along with the entity
+    Cascade,
+}
+
+/// IDs of everything deleted by a removal
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemovedItems {
+    pub points: Vec<String>,
+    pub lines: Vec<String>,
+    pub circles: Vec<String>,
+    pub arcs: Vec<String>,
+    pub scalar_variables: Vec<String>,
+    pub constraints: Vec<String>,
+}
+
+/// Outcome of solving one independent cluster of the constraint graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterResult {
+    /// IDs of the constraints in the cluster
+    pub constraints: Vec<String>,
+    /// IDs of the entities those constraints reference
+    pub entities: Vec<String>,
+    pub converged: bool,
+    pub iterations: usize,
+    pub final_error: f64,
+    pub initial_error: f64,
+}
+
+impl ClusterResult {
+    /// An unsolved result naming the given constraints and the entities they
+    /// reference. Callers fill in the solver status.
+    pub(crate) fn for_constraints(entries: &[ConstraintEntry], constraints: &[usize]) -> Self {
+        let mut entities: Vec<String> = constraints
+            .iter()
+            .flat_map(|&i| entries[i].entities.iter().cloned())
+            .collect();
+        entities.sort();
+        entities.dedup();
+
+        Self {
+            constraints: constraints.iter().map(|&i| entries[i].id.clone()).collect(),
+            entities,
+            converged: false,
+            iterations: 0,
+            final_error: 0.0,
+            initial_error: 0.0,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -30,26 +271,194 @@ pub enum SolverResult {
         iterations: usize,
         final_error: f64,
         initial_error: f64,
+        clusters: Vec<ClusterResult>,
     },
     MaxIterationsReached {
         iterations: usize,
         final_error: f64,
         initial_error: f64,
+        clusters: Vec<ClusterResult>,
     },
 }
 
+impl SolverResult {
+    /// Combine per-cluster results. The system converged if every cluster
+    /// did, iterations are the most any cluster needed, and errors are the
+    /// norm over the residuals of all clusters.
+    pub fn from_clusters(clusters: Vec<ClusterResult>) -> Self {
+        let iterations = clusters.iter().map(|c| c.iterations).max().unwrap_or(0);
+        let final_error = clusters
+            .iter()
+            .map(|c| c.final_error.powi(2))
+            .sum::<f64>()
+            .sqrt();
+        let initial_error = clusters
+            .iter()
+            .map(|c| c.initial_error.powi(2))
+            .sum::<f64>()
+            .sqrt();
+
+        if clusters.iter().all(|c| c.converged) {
+            SolverResult::Converged {
+                iterations,
+                final_error,
+                initial_error,
+                clusters,
+            }
+        } else {
+            SolverResult::MaxIterationsReached {
+                iterations,
+                final_error,
+                initial_error,
+                clusters,
+            }
+        }
+    }
+
+    /// Per-cluster results, in the order of each cluster's first constraint
+    pub fn clusters(&self) -> &[ClusterResult] {
+        match self {
+            SolverResult::Converged { clusters, .. } => clusters,
+            SolverResult::MaxIterationsReached { clusters, .. } => clusters,
+        }
+    }
+}
+
+/// Tuning and budgets shared by the solver implementations
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolverOptions {
+    /// Residual norm below which a cluster counts as solved
+    pub tolerance: f64,
+    /// Iterations allowed per cluster
+    pub max_iterations: usize,
+    /// Trust radius of the first dog-leg step
+    pub initial_trust_radius: f64,
+    /// Largest trust radius dog-leg grows to
+    pub max_trust_radius: f64,
+    /// Trust radius below which dog-leg gives up
+    pub min_trust_radius: f64,
+    /// Change of the residual norm below which an iteration counts as
+    /// stagnant. Several stagnant iterations in a row end the solve.
+    pub stagnation_threshold: f64,
+    /// Wall-clock budget for a whole solve. When it runs out, the solve stops
+    /// and reports `MaxIterationsReached` for the unfinished clusters.
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-6,
+            max_iterations: 100,
+            initial_trust_radius: 1.0,
+            max_trust_radius: 10.0,
+            min_trust_radius: 1e-8,
+            stagnation_threshold: 1e-12,
+            time_budget: None,
+        }
+    }
+}
+
+/// The point in time a solve has to stop at, if any. Uses a clock that also
+/// works in the browser.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Start the clock for a solve with the given budget
+    pub fn start(budget: Option<Duration>) -> Self {
+        Self(budget.map(|budget| Instant::now() + budget))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// State of a solve after evaluating the residual of one iteration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveProgress {
+    /// Index of the cluster being solved, in solve order
+    pub cluster: usize,
+    pub iteration: usize,
+    pub residual_norm: f64,
+    /// Current trust radius, for solvers that have one
+    pub trust_radius: Option<f64>,
+}
+
+/// Receives progress during a solve and can cancel it. Both methods are
+/// called once per iteration.
+pub trait SolveObserver {
+    fn on_iteration(&mut self, _progress: &SolveProgress) {}
+
+    /// Return true to abort the solve. A cancelled solve fails with
+    /// `AcsError::Cancelled` and leaves the geometry as it was.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// No observer
+impl SolveObserver for () {}
+
+/// Cancellation flag that can be shared with another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl SolveObserver for CancellationToken {
+    fn is_cancelled(&self) -> bool {
+        CancellationToken::is_cancelled(self)
+    }
+}
+
 pub trait Solver {
     fn solve(
         &self,
         geometry: &mut GeometrySystem,
         constraint_graph: &ConstraintGraph,
-    ) -> Result<SolverResult, String>;
+        options: &SolverOptions,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<SolverResult, AcsError>;
+}
+
+/// The built-in `Solver` implementations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SolverKind {
+    #[default]
+    DogLeg,
+    LevenbergMarquardt,
+}
+
+impl SolverKind {
+    pub fn create(self) -> Box<dyn Solver> {
+        match self {
+            SolverKind::DogLeg => Box::new(ParametricDogLegSolver::new()),
+            SolverKind::LevenbergMarquardt => Box::new(LevenbergMarquardtSolver),
+        }
+    }
 }
 
 pub struct ConstraintSolver {
     geometry: GeometrySystem,
     constraint_graph: ConstraintGraph,
     solver: Box<dyn Solver>,
+    options: SolverOptions,
+    registry: ConstraintRegistry,
+    variables: VariableTable,
 }
 
 impl Default for ConstraintSolver {
@@ -62,13 +471,36 @@ impl ConstraintSolver {
     pub fn new() -> Self {
         Self {
             geometry: GeometrySystem::new(),
-            constraint_graph: ConstraintGraph {
-                constraints: Vec::new(),
-            },
-            solver: Box::new(ParametricDogLegSolver::new()),
+            constraint_graph: ConstraintGraph::new(),
+            solver: SolverKind::default().create(),
+            options: SolverOptions::default(),
+            registry: ConstraintRegistry::new(),
+            variables: VariableTable::new(),
         }
     }
 
+    /// An empty solver that solves with the given implementation
+    pub fn with_solver(solver: Box<dyn Solver>) -> Self {
+        Self {
+            solver,
+            ..Self::new()
+        }
+    }
+
+    /// Replace the solver implementation, keeping the sketch
+    pub fn set_solver(&mut self, solver: Box<dyn Solver>) {
+        self.solver = solver;
+    }
+
+    pub fn options(&self) -> &SolverOptions {
+        &self.options
+    }
+
+    /// Options used by later solves and drags
+    pub fn set_options(&mut self, options: SolverOptions) {
+        self.options = options;
+    }
+
     pub fn add_point(&mut self, point: crate::geometry::Point) -> String {
         self.geometry.add_point(point)
     }
@@ -77,7 +509,7 @@ impl ConstraintSolver {
         self.geometry.add_circle(circle)
     }
 
-    pub fn add_line(&mut self, line: crate::geometry::Line) -> String {
+    pub fn add_line(&mut self, line: crate::geometry::Line) -> Result<String, AcsError> {
         self.geometry.add_line(line)
     }
 
@@ -85,16 +517,533 @@ impl ConstraintSolver {
         self.geometry.add_arc(arc)
     }
 
-    pub fn add_constraint(&mut self, constraint_type: ConstraintType) -> Result<(), String> {
+    /// Add a standalone unknown that constraints such as
+    /// `ConstraintType::VariableDistance` can reference by its ID
+    pub fn add_scalar_variable(
+        &mut self,
+        variable: crate::geometry::ScalarVariable,
+    ) -> Result<String, AcsError> {
+        self.geometry.add_scalar_variable(variable)
+    }
+
+    /// Lock or unlock a single parameter of an entity, e.g. only the x of a
+    /// point, see `GeometrySystem::set_parameter_locked`
+    pub fn set_parameter_locked(
+        &mut self,
+        id: &str,
+        parameter: &str,
+        locked: bool,
+    ) -> Result<(), AcsError> {
+        self.geometry.set_parameter_locked(id, parameter, locked)
+    }
+
+    /// Add a constraint and return the ID it was assigned
+    pub fn add_constraint(&mut self, constraint_type: ConstraintType) -> Result<String, AcsError> {
+        self.add_constraint_with_options(constraint_type, ConstraintOptions::default())
+            .map(|entry| entry.id)
+    }
+
+    /// Add a constraint with a caller-supplied ID, name, tags or metadata and
+    /// return its stored entry
+    pub fn add_constraint_with_options(
+        &mut self,
+        constraint_type: ConstraintType,
+        options: ConstraintOptions,
+    ) -> Result<ConstraintEntry, AcsError> {
+        self.validate_constraint(&constraint_type, &options)?;
+        let label = Self::constraint_label(&constraint_type, &options);
+        let constraint_type =
+            self.apply_expression(constraint_type, options.expression.as_ref(), &label)?;
+
+        let resolved = constraint_type.clone().resolve_lines(&self.geometry)?;
+        let constraint = self.create(resolved.clone(), &options)?;
+
+        let mut entities: Vec<String> = Vec::new();
+        for reference in constraint_type
+            .references()
+            .into_iter()
+            .chain(resolved.references())
+            .chain(constraint.references())
+        {
+            if !entities.contains(&reference.id) {
+                entities.push(reference.id);
+            }
+        }
+
         self.constraint_graph
-            .constraints
-            .push(create_constraint(constraint_type)?);
+            .insert(constraint, constraint_type, entities, options)
+            .cloned()
+    }
+
+    /// Add an instance of a user-defined constraint. It is validated and
+    /// tracked through `Constraint::references`, and listed with its
+    /// `type_name` and no data.
+    pub fn add_custom_constraint(
+        &mut self,
+        constraint: Box<dyn Constraint>,
+        options: ConstraintOptions,
+    ) -> Result<ConstraintEntry, AcsError> {
+        let constraint_type = ConstraintType::Custom {
+            type_name: constraint.type_name().to_string(),
+            data: serde_json::Value::Null,
+        };
+        let label = Self::constraint_label(&constraint_type, &options);
+        self.check_id(&options, &label)?;
+        Self::check_weight(&options, &label)?;
+        self.apply_expression(constraint_type.clone(), options.expression.as_ref(), &label)?;
+        let references = constraint.references();
+        self.check_references(&references, &label)?;
+
+        let mut entities: Vec<String> = Vec::new();
+        for reference in references {
+            if !entities.contains(&reference.id) {
+                entities.push(reference.id);
+            }
+        }
+
+        self.constraint_graph
+            .insert(constraint, constraint_type, entities, options)
+            .cloned()
+    }
+
+    /// Register a custom constraint type, so `ConstraintType::Custom` with
+    /// that type name can be added. Fails for the names of built-in types.
+    pub fn register_constraint_type<F>(
+        &mut self,
+        type_name: &str,
+        factory: F,
+    ) -> Result<(), AcsError>
+    where
+        F: Fn(&serde_json::Value) -> Result<Box<dyn Constraint>, AcsError> + 'static,
+    {
+        self.registry.register(type_name, factory)
+    }
+
+    pub fn registry(&self) -> &ConstraintRegistry {
+        &self.registry
+    }
+
+    /// Replace the custom constraint types, e.g. with those of another solver
+    pub fn set_registry(&mut self, registry: ConstraintRegistry) {
+        self.registry = registry;
+    }
+
+    /// Check that every entity a constraint references exists and has a type
+    /// the constraint accepts, that a caller-supplied ID is not taken, that
+    /// the weight is positive and that an expression can be evaluated
+    pub fn validate_constraint(
+        &self,
+        constraint_type: &ConstraintType,
+        options: &ConstraintOptions,
+    ) -> Result<(), AcsError> {
+        let label = Self::constraint_label(constraint_type, options);
+        self.check_id(options, &label)?;
+        Self::check_weight(options, &label)?;
+        self.apply_expression(constraint_type.clone(), options.expression.as_ref(), &label)?;
+
+        let references = match constraint_type {
+            ConstraintType::Custom { .. } => {
+                self.create(constraint_type.clone(), options)?.references()
+            }
+            _ => constraint_type.references(),
+        };
+        self.check_references(&references, &label)
+    }
+
+    /// Build a constraint with line references already resolved, creating
+    /// custom ones through the registry
+    fn create(
+        &self,
+        constraint_type: ConstraintType,
+        options: &ConstraintOptions,
+    ) -> Result<Box<dyn Constraint>, AcsError> {
+        let label = Self::constraint_label(&constraint_type, options);
+        let constraint = match &constraint_type {
+            ConstraintType::Custom { type_name, data } => self.registry.create(type_name, data),
+            _ => create_constraint(constraint_type),
+        };
+
+        constraint.map_err(|error| match error {
+            AcsError::UnsupportedConstraint { message, .. } => AcsError::UnsupportedConstraint {
+                constraint: label,
+                message,
+            },
+            other => other,
+        })
+    }
+
+    fn check_id(&self, options: &ConstraintOptions, label: &str) -> Result<(), AcsError> {
+        if let Some(id) = &options.id
+            && self.constraint_graph.get_entry(id).is_some()
+        {
+            return Err(AcsError::DuplicateId {
+                constraint: label.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_weight(options: &ConstraintOptions, label: &str) -> Result<(), AcsError> {
+        if options.weight.is_finite() && options.weight > 0.0 {
+            return Ok(());
+        }
+        Err(AcsError::UnsupportedConstraint {
+            constraint: label.to_string(),
+            message: format!("Weight must be positive and finite, got {}", options.weight),
+        })
+    }
+
+    /// Set the dimension of a constraint to the value of its expression
+    fn apply_expression(
+        &self,
+        constraint_type: ConstraintType,
+        expression: Option<&Expression>,
+        label: &str,
+    ) -> Result<ConstraintType, AcsError> {
+        let Some(expression) = expression else {
+            return Ok(constraint_type);
+        };
+        let value = self.variables.evaluate(expression)?;
+        constraint_type
+            .with_value(value)
+            .ok_or_else(|| AcsError::UnsupportedConstraint {
+                constraint: label.to_string(),
+                message: "The constraint has no dimension to drive with an expression".into(),
+            })
+    }
+
+    fn check_references(&self, references: &[EntityRef], label: &str) -> Result<(), AcsError> {
+        for reference in references {
+            if reference
+                .accepts
+                .iter()
+                .any(|entity_type| self.geometry.contains_entity(&reference.id, entity_type))
+            {
+                continue;
+            }
+
+            return Err(match self.geometry.entity_type(&reference.id) {
+                Some(found) => AcsError::WrongEntityType {
+                    entity: reference.id.clone(),
+                    expected: reference.accepts.clone(),
+                    found,
+                    constraint: Some(label.to_string()),
+                },
+                None => AcsError::UnknownEntity {
+                    entity: reference.id.clone(),
+                    constraint: Some(label.to_string()),
+                },
+            });
+        }
+
         Ok(())
     }
 
-    pub fn solve(&mut self) -> Result<SolverResult, String> {
-        self.solver
-            .solve(&mut self.geometry, &self.constraint_graph)
+    fn constraint_label(constraint_type: &ConstraintType, options: &ConstraintOptions) -> String {
+        options
+            .id
+            .clone()
+            .unwrap_or_else(|| constraint_type.type_name().to_string())
+    }
+
+    /// The design variables that constraint expressions refer to
+    pub fn variables(&self) -> &VariableTable {
+        &self.variables
+    }
+
+    /// Define or redefine a design variable and update the dimensions of
+    /// the constraints that depend on it, without solving. On error nothing
+    /// changes.
+    pub fn define_variable(&mut self, name: &str, expression: Expression) -> Result<(), AcsError> {
+        let previous = self.variables.clone();
+        self.variables.set(name, expression)?;
+        self.update_dimensions()
+            .inspect_err(|_| self.variables = previous)
+    }
+
+    /// Change a design variable and re-solve with the updated dimensions
+    pub fn set_variable(
+        &mut self,
+        name: &str,
+        expression: Expression,
+    ) -> Result<SolverResult, AcsError> {
+        self.define_variable(name, expression)?;
+        self.solve()
+    }
+
+    /// Re-evaluate the expressions of expression-driven constraints and
+    /// rebuild those whose dimension changed. Nothing changes on error.
+    fn update_dimensions(&mut self) -> Result<(), AcsError> {
+        let mut updates = Vec::new();
+        for (index, entry) in self.constraint_graph.get_entries().iter().enumerate() {
+            let constraint_type = self.apply_expression(
+                entry.constraint_type.clone(),
+                entry.expression.as_ref(),
+                &entry.id,
+            )?;
+            if constraint_type == entry.constraint_type {
+                continue;
+            }
+
+            let options = ConstraintOptions {
+                id: Some(entry.id.clone()),
+                ..Default::default()
+            };
+            let resolved = constraint_type.clone().resolve_lines(&self.geometry)?;
+            updates.push((index, self.create(resolved, &options)?, constraint_type));
+        }
+
+        for (index, constraint, constraint_type) in updates {
+            self.constraint_graph
+                .replace(index, constraint, constraint_type);
+        }
+        Ok(())
+    }
+
+    pub fn remove_constraint(&mut self, id: &str) -> Result<(), AcsError> {
+        self.constraint_graph
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| AcsError::UnknownConstraint {
+                constraint: id.to_string(),
+            })
+    }
+
+    /// Remove a point. Lines using it as an endpoint, circles and arcs
+    /// centered on it and constraints referencing it are its dependents.
+    pub fn remove_point(
+        &mut self,
+        id: &str,
+        policy: RemovalPolicy,
+    ) -> Result<RemovedItems, AcsError> {
+        if self.geometry.get_point(id).is_none() {
+            return Err(AcsError::unknown_entity(id));
+        }
+
+        let mut removed = RemovedItems {
+            points: vec![id.to_string()],
+            lines: self.geometry.lines_using_point(id),
+            circles: self.geometry.circles_using_point(id),
+            arcs: self.geometry.arcs_using_point(id),
+            ..Default::default()
+        };
+        let mut entity_ids = vec![id.to_string()];
+        entity_ids.extend(removed.lines.iter().cloned());
+        entity_ids.extend(removed.circles.iter().cloned());
+        entity_ids.extend(removed.arcs.iter().cloned());
+        removed.constraints = self.constraint_graph.constraints_referencing(&entity_ids);
+
+        if policy == RemovalPolicy::Error {
+            Self::check_no_dependents(id, &removed)?;
+        }
+
+        self.apply_removal(&removed);
+        Ok(removed)
+    }
+
+    /// Remove a line. Constraints referencing the line are its dependents;
+    /// its endpoints are kept.
+    pub fn remove_line(
+        &mut self,
+        id: &str,
+        policy: RemovalPolicy,
+    ) -> Result<RemovedItems, AcsError> {
+        if self.geometry.get_line(id).is_none() {
+            return Err(AcsError::unknown_entity(id));
+        }
+
+        let removed = RemovedItems {
+            lines: vec![id.to_string()],
+            constraints: self
+                .constraint_graph
+                .constraints_referencing(&[id.to_string()]),
+            ..Default::default()
+        };
+
+        if policy == RemovalPolicy::Error {
+            Self::check_no_dependents(id, &removed)?;
+        }
+
+        self.apply_removal(&removed);
+        Ok(removed)
+    }
+
+    /// Remove a circle. Constraints referencing the circle are its
+    /// dependents; its center point is kept.
+    pub fn remove_circle(
+        &mut self,
+        id: &str,
+        policy: RemovalPolicy,
+    ) -> Result<RemovedItems, AcsError> {
+        if self.geometry.get_circle(id).is_none() {
+            return Err(AcsError::unknown_entity(id));
+        }
+
+        let removed = RemovedItems {
+            circles: vec![id.to_string()],
+            constraints: self
+                .constraint_graph
+                .constraints_referencing(&[id.to_string()]),
+            ..Default::default()
+        };
+
+        if policy == RemovalPolicy::Error {
+            Self::check_no_dependents(id, &removed)?;
+        }
+
+        self.apply_removal(&removed);
+        Ok(removed)
+    }
+
+    /// Remove an arc. Constraints referencing the arc are its dependents;
+    /// its center point is kept.
+    pub fn remove_arc(
+        &mut self,
+        id: &str,
+        policy: RemovalPolicy,
+    ) -> Result<RemovedItems, AcsError> {
+        if self.geometry.get_arc(id).is_none() {
+            return Err(AcsError::unknown_entity(id));
+        }
+
+        let removed = RemovedItems {
+            arcs: vec![id.to_string()],
+            constraints: self
+                .constraint_graph
+                .constraints_referencing(&[id.to_string()]),
+            ..Default::default()
+        };
+
+        if policy == RemovalPolicy::Error {
+            Self::check_no_dependents(id, &removed)?;
+        }
+
+        self.apply_removal(&removed);
+        Ok(removed)
+    }
+
+    /// Remove a scalar variable. Constraints referencing it are its dependents.
+    pub fn remove_scalar_variable(
+        &mut self,
+        id: &str,
+        policy: RemovalPolicy,
+    ) -> Result<RemovedItems, AcsError> {
+        if self.geometry.get_scalar_variable(id).is_none() {
+            return Err(AcsError::unknown_entity(id));
+        }
+
+        let removed = RemovedItems {
+            scalar_variables: vec![id.to_string()],
+            constraints: self
+                .constraint_graph
+                .constraints_referencing(&[id.to_string()]),
+            ..Default::default()
+        };
+
+        if policy == RemovalPolicy::Error {
+            Self::check_no_dependents(id, &removed)?;
+        }
+
+        self.apply_removal(&removed);
+        Ok(removed)
+    }
+
+    fn check_no_dependents(id: &str, removed: &RemovedItems) -> Result<(), AcsError> {
+        let dependents: Vec<String> = removed
+            .lines
+            .iter()
+            .chain(&removed.circles)
+            .chain(&removed.arcs)
+            .chain(&removed.constraints)
+            .filter(|dependent| dependent.as_str() != id)
+            .cloned()
+            .collect();
+
+        if dependents.is_empty() {
+            Ok(())
+        } else {
+            Err(AcsError::EntityInUse {
+                entity: id.to_string(),
+                dependents,
+            })
+        }
+    }
+
+    fn apply_removal(&mut self, removed: &RemovedItems) {
+        for id in &removed.constraints {
+            self.constraint_graph.remove(id);
+        }
+        for id in &removed.lines {
+            self.geometry.remove_line(id);
+        }
+        for id in &removed.circles {
+            self.geometry.remove_circle(id);
+        }
+        for id in &removed.arcs {
+            self.geometry.remove_arc(id);
+        }
+        for id in &removed.points {
+            self.geometry.remove_point(id);
+        }
+        for id in &removed.scalar_variables {
+            self.geometry.remove_scalar_variable(id);
+        }
+    }
+
+    pub fn solve(&mut self) -> Result<SolverResult, AcsError> {
+        self.solve_with_observer(&mut ())
+    }
+
+    /// Solve while reporting progress to an observer, which may cancel the
+    /// solve. A cancelled solve leaves the geometry unchanged.
+    pub fn solve_with_observer(
+        &mut self,
+        observer: &mut dyn SolveObserver,
+    ) -> Result<SolverResult, AcsError> {
+        self.solver.solve(
+            &mut self.geometry,
+            &self.constraint_graph,
+            &self.options,
+            observer,
+        )
+    }
+
+    /// Move a point to a target position and re-solve the part of the sketch
+    /// connected to it, keeping the other points as close to their current
+    /// positions as the constraints allow
+    pub fn drag_point(&mut self, id: &str, x: f64, y: f64) -> Result<SolverResult, AcsError> {
+        drag::drag_point(
+            &mut self.geometry,
+            &self.constraint_graph,
+            &self.options,
+            id,
+            x,
+            y,
+        )
+    }
+
+    /// Degrees-of-freedom analysis of the sketch at its current state, without solving.
+    /// Soft constraints do not take freedom away.
+    pub fn analyze_dof(&self) -> Result<DofAnalysis, AcsError> {
+        let param_manager = ParameterManager::from_geometry(&self.geometry);
+        let bound = self
+            .constraint_graph
+            .bind_hard_constraints(&param_manager)?;
+        Ok(analysis::analyze_bound_dof(&param_manager, &bound))
+    }
+
+    /// Find redundant and conflicting constraints at the current state.
+    /// Typically called after a solve that did not converge.
+    pub fn diagnose_constraints(&self) -> Result<ConstraintDiagnosis, AcsError> {
+        let param_manager = ParameterManager::from_geometry(&self.geometry);
+        diagnose_constraints(&param_manager, &self.constraint_graph)
+    }
+
+    /// Residual of every constraint at the current state, e.g. to find the
+    /// constraints a failed solve left violated
+    pub fn constraint_residuals(&self) -> Result<Vec<ConstraintResidual>, AcsError> {
+        let param_manager = ParameterManager::from_geometry(&self.geometry);
+        constraint_residuals(&param_manager, &self.constraint_graph)
     }
 
     pub fn get_point(&self, id: String) -> Option<&Point> {
@@ -129,14 +1078,28 @@ impl ConstraintSolver {
         self.geometry.get_all_arcs()
     }
 
+    /// A scalar variable, with its solved value after a solve
+    pub fn get_scalar_variable(&self, id: String) -> Option<&crate::geometry::ScalarVariable> {
+        self.geometry.get_scalar_variable(&id)
+    }
+
+    pub fn get_all_scalar_variables(
+        &self,
+    ) -> &std::collections::HashMap<String, crate::geometry::ScalarVariable> {
+        self.geometry.get_all_scalar_variables()
+    }
+
+    pub fn get_constraint_entries(&self) -> &[ConstraintEntry] {
+        self.constraint_graph.get_entries()
+    }
+
+    pub fn get_constraint_entry(&self, id: &str) -> Option<&ConstraintEntry> {
+        self.constraint_graph.get_entry(id)
+    }
+
+    /// Log the geometry state at info level
     pub fn print_state(&self) {
-        println!("Geometry System State:");
-        for (id, point) in self.geometry.get_all_points() {
-            println!("Point ID: {}, Position: ({}, {})", id, point.x, point.y);
-        }
-        for (id, line) in self.geometry.get_all_lines() {
-            println!("Line ID: {}, Start: {}, End: {}", id, line.start, line.end);
-        }
+        log::info!("{}", self.get_state_as_string());
     }
 
     pub fn get_state_as_string(&self) -> String {
@@ -154,6 +1117,12 @@ impl ConstraintSolver {
                 id, line.start, line.end
             ));
         }
+        for (id, variable) in self.geometry.get_all_scalar_variables() {
+            state.push_str(&format!(
+                "Scalar Variable ID: {}, Value: {}\n",
+                id, variable.value
+            ));
+        }
         state
     }
 }
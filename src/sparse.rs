@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+
+use nalgebra::DVector;
+use nalgebra_sparse::{CooMatrix, CscMatrix, CsrMatrix, factorization::CscCholesky};
+
+use crate::{AcsError, BoundConstraint, Constraint, ParameterManager};
+
+/// Relative damping added to the diagonal of JᵀJ. Keeps the normal equations
+/// positive definite when the sketch is under-constrained, which makes the
+/// step close to the minimum-norm least-squares step.
+const DAMPING: f64 = 1e-10;
+
+/// Mapping between global parameter indices and the columns of the reduced
+/// system. Fixed parameters do not get a column.
+pub struct FreeColumns {
+    columns: Vec<Option<usize>>,
+    parameters: Vec<usize>,
+}
+
+impl FreeColumns {
+    /// All free parameters of `param_manager`, in global order
+    pub fn new(param_manager: &ParameterManager) -> Self {
+        let parameters: Vec<usize> = param_manager
+            .get_parameter_info()
+            .iter()
+            .filter(|info| !info.is_fixed)
+            .map(|info| info.global_index)
+            .collect();
+        Self::from_parameters(param_manager.num_parameters(), parameters)
+    }
+
+    /// A chosen subset of free parameters, e.g. those of one cluster
+    pub fn from_parameters(num_parameters: usize, parameters: Vec<usize>) -> Self {
+        let mut columns = vec![None; num_parameters];
+        for (column, &index) in parameters.iter().enumerate() {
+            columns[index] = Some(column);
+        }
+
+        Self {
+            columns,
+            parameters,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.parameters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+
+    /// Column of a global parameter index, if the parameter is free
+    pub fn column(&self, global_index: usize) -> Option<usize> {
+        self.columns.get(global_index).copied().flatten()
+    }
+
+    /// Global parameter index of a column
+    pub fn parameter(&self, column: usize) -> usize {
+        self.parameters[column]
+    }
+}
+
+/// Binds every constraint to the parameter layout of `param_manager`
+pub fn bind_constraints(
+    param_manager: &ParameterManager,
+    constraints: &[Box<dyn Constraint>],
+) -> Result<Vec<Box<dyn BoundConstraint>>, AcsError> {
+    constraints.iter().map(|c| c.bind(param_manager)).collect()
+}
+
+/// Stacks the residuals of all constraints into one vector
+pub fn evaluate_residuals(
+    params: &[f64],
+    constraints: &[Box<dyn BoundConstraint>],
+) -> DVector<f64> {
+    let total_residuals: usize = constraints.iter().map(|c| c.num_residuals()).sum();
+    let mut residuals = DVector::<f64>::zeros(total_residuals);
+
+    let mut row_offset = 0;
+    for c in constraints {
+        let rows = c.num_residuals();
+        c.residual(
+            params,
+            &mut residuals.as_mut_slice()[row_offset..row_offset + rows],
+        );
+        row_offset += rows;
+    }
+
+    residuals
+}
+
+/// Assembles the sparse Jacobian of all constraints with respect to the free
+/// parameters. Entries on fixed parameters are dropped.
+pub fn assemble_jacobian(
+    params: &[f64],
+    constraints: &[Box<dyn BoundConstraint>],
+    columns: &FreeColumns,
+) -> CsrMatrix<f64> {
+    let total_residuals: usize = constraints.iter().map(|c| c.num_residuals()).sum();
+    let mut jacobian = CooMatrix::new(total_residuals, columns.len());
+    let mut entries = Vec::new();
+
+    let mut row_offset = 0;
+    for c in constraints {
+        entries.clear();
+        c.jacobian_entries(params, &mut entries);
+        for &(row, col, value) in &entries {
+            if let Some(column) = columns.column(col) {
+                jacobian.push(row_offset + row, column, value);
+            }
+        }
+        row_offset += c.num_residuals();
+    }
+
+    CsrMatrix::from(&jacobian)
+}
+
+/// Solves `(JᵀJ + λI) x = rhs` with a sparse Cholesky factorization, where
+/// λ is a tiny damping relative to the diagonal of JᵀJ.
+///
+/// The unknowns are reordered with reverse Cuthill-McKee first to limit
+/// fill-in, since parameter order follows the (unordered) geometry maps.
+pub fn solve_normal_equations(
+    jacobian: &CsrMatrix<f64>,
+    rhs: &DVector<f64>,
+) -> Result<DVector<f64>, AcsError> {
+    solve_damped_normal_equations(jacobian, rhs, 0.0)
+}
+
+/// Solves `(JᵀJ + (μ + λ)I) x = rhs`, as `solve_normal_equations` but with an
+/// additional damping `μ` chosen by the caller
+pub fn solve_damped_normal_equations(
+    jacobian: &CsrMatrix<f64>,
+    rhs: &DVector<f64>,
+    mu: f64,
+) -> Result<DVector<f64>, AcsError> {
+    Ok(NormalEquations::factor(jacobian, mu)?.solve(rhs))
+}
+
+/// Cholesky factorization of `JᵀJ + (μ + λ)I`, for solving the same normal
+/// equations with several right-hand sides
+pub struct NormalEquations {
+    /// None when `J` has no columns
+    cholesky: Option<CscCholesky<f64>>,
+    order: Vec<usize>,
+    position: Vec<usize>,
+}
+
+impl NormalEquations {
+    pub fn factor(jacobian: &CsrMatrix<f64>, mu: f64) -> Result<Self, AcsError> {
+        let n = jacobian.ncols();
+        if n == 0 {
+            return Ok(Self {
+                cholesky: None,
+                order: Vec::new(),
+                position: Vec::new(),
+            });
+        }
+
+        let jtj = &jacobian.transpose() * jacobian;
+        let order = reverse_cuthill_mckee(&jtj);
+        let mut position = vec![0; n];
+        for (new, &old) in order.iter().enumerate() {
+            position[old] = new;
+        }
+
+        let max_diagonal = jtj
+            .triplet_iter()
+            .filter(|(i, j, _)| i == j)
+            .fold(0.0f64, |max, (_, _, v)| max.max(v.abs()));
+        let mut damping = mu + DAMPING * max_diagonal.max(1.0);
+
+        // Rounding can still leave a tiny negative pivot on a nearly singular
+        // system, in which case the damping is raised and the factorization retried
+        for _ in 0..6 {
+            let mut system = CooMatrix::new(n, n);
+            for (i, j, &v) in jtj.triplet_iter() {
+                system.push(position[i], position[j], v);
+            }
+            for i in 0..n {
+                system.push(i, i, damping);
+            }
+
+            if let Ok(cholesky) = CscCholesky::factor(&CscMatrix::from(&system)) {
+                return Ok(Self {
+                    cholesky: Some(cholesky),
+                    order,
+                    position,
+                });
+            }
+            damping *= 1e3;
+        }
+
+        Err(AcsError::NumericalFailure {
+            message: "Normal equations are not positive definite".to_string(),
+        })
+    }
+
+    pub fn solve(&self, rhs: &DVector<f64>) -> DVector<f64> {
+        let Some(cholesky) = &self.cholesky else {
+            return DVector::zeros(0);
+        };
+        let n = self.order.len();
+        let permuted_rhs = DVector::from_fn(n, |i, _| rhs[self.order[i]]);
+        let solution = cholesky.solve(&permuted_rhs);
+        DVector::from_fn(n, |i, _| solution[self.position[i]])
+    }
+}
+
+/// Fill-reducing ordering of a symmetric matrix. Returns the original index
+/// of each new position.
+fn reverse_cuthill_mckee(matrix: &CsrMatrix<f64>) -> Vec<usize> {
+    let n = matrix.nrows();
+    let degree: Vec<usize> = matrix.row_iter().map(|row| row.nnz()).collect();
+
+    let mut visited = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut by_degree: Vec<usize> = (0..n).collect();
+    by_degree.sort_by_key(|&i| degree[i]);
+
+    // Each connected component starts from its lowest-degree node
+    for &start in &by_degree {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+
+            let mut neighbours: Vec<usize> = matrix
+                .row(node)
+                .col_indices()
+                .iter()
+                .copied()
+                .filter(|&j| !visited[j])
+                .collect();
+            neighbours.sort_by_key(|&j| degree[j]);
+
+            for j in neighbours {
+                visited[j] = true;
+                queue.push_back(j);
+            }
+        }
+    }
+
+    order.reverse();
+    order
+}
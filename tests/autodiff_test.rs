@@ -0,0 +1,129 @@
+use acs::{
+    AutodiffConstraint, BoundConstraint, Constraint, Dual, EntityType, ParameterManager, Point,
+    PointOnLineConstraint, Scalar,
+};
+
+fn points(coordinates: &[(&str, f64, f64)]) -> ParameterManager {
+    let mut param_manager = ParameterManager::new();
+    for &(id, x, y) in coordinates {
+        let point = Point::new(id.into(), x, y, false);
+        param_manager.register_entity(id.into(), EntityType::Point, &point);
+    }
+    param_manager
+}
+
+/// Central-difference Jacobian of a bound constraint, as a dense matrix
+fn numeric_jacobian(constraint: &dyn BoundConstraint, params: &[f64]) -> Vec<Vec<f64>> {
+    let h = 1e-6;
+    let rows = constraint.num_residuals();
+    let mut jacobian = vec![vec![0.0; params.len()]; rows];
+    for column in 0..params.len() {
+        let (mut plus, mut minus) = (params.to_vec(), params.to_vec());
+        plus[column] += h;
+        minus[column] -= h;
+        let (mut r_plus, mut r_minus) = (vec![0.0; rows], vec![0.0; rows]);
+        constraint.residual(&plus, &mut r_plus);
+        constraint.residual(&minus, &mut r_minus);
+        for row in 0..rows {
+            jacobian[row][column] = (r_plus[row] - r_minus[row]) / (2.0 * h);
+        }
+    }
+    jacobian
+}
+
+fn assert_jacobian_matches(constraint: &dyn BoundConstraint, params: &[f64]) {
+    let mut dense = vec![vec![0.0; params.len()]; constraint.num_residuals()];
+    let mut entries = Vec::new();
+    constraint.jacobian_entries(params, &mut entries);
+    for (row, column, value) in entries {
+        dense[row][column] += value;
+    }
+
+    let numeric = numeric_jacobian(constraint, params);
+    for (row, (exact, approximate)) in dense.iter().zip(&numeric).enumerate() {
+        for (column, (a, b)) in exact.iter().zip(approximate).enumerate() {
+            assert!(
+                (a - b).abs() < 1e-6,
+                "({row}, {column}): exact {a}, numeric {b}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_dual_numbers_give_exact_derivatives() {
+    let f = |x: Dual| x.sin() * x.sqrt() + x.atan2(Dual::constant(2.0)) - x.powi(3) / x.cos();
+    let df = |x: f64| {
+        x.cos() * x.sqrt() + x.sin() / (2.0 * x.sqrt()) + 2.0 / (x * x + 4.0)
+            - (3.0 * x * x * x.cos() + x.powi(3) * x.sin()) / x.cos().powi(2)
+    };
+
+    for x in [0.3, 1.0, 2.5] {
+        let result = f(Dual::variable(x));
+        assert!((result.value - f(Dual::constant(x)).value).abs() < 1e-15);
+        assert!((result.derivative - df(x)).abs() < 1e-12);
+    }
+
+    assert_eq!(Dual::variable(-2.0).abs(), Dual::new(2.0, -1.0));
+    assert_eq!(Dual::variable(3.0).clamp(0.0, 1.0), Dual::constant(1.0));
+}
+
+#[test]
+fn test_point_on_line_jacobian_includes_endpoints() {
+    // Projection inside the segment, then beyond its end
+    for target in [(1.0, 2.0), (5.0, 1.0)] {
+        let param_manager = points(&[("p", target.0, target.1), ("a", 0.0, 0.0), ("b", 3.0, 1.0)]);
+        let constraint = PointOnLineConstraint::new("p".into(), "a".into(), "b".into());
+        let bound = constraint.bind(&param_manager).unwrap();
+        let params = param_manager.get_parameters();
+        assert_jacobian_matches(bound.as_ref(), params);
+
+        // The line's endpoints move the closest point, so they have derivatives
+        let b_x = param_manager.resolve_index("b", 0).unwrap();
+        let jacobian = constraint.jacobian(&param_manager).unwrap();
+        assert!(jacobian[(0, b_x)].abs() > 1e-3);
+    }
+}
+
+/// Distance between two points equal to a value, written only as a residual
+struct Distance {
+    indices: [usize; 4],
+    value: f64,
+}
+
+impl AutodiffConstraint for Distance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.to_vec()
+    }
+
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]) {
+        let dx = x[2] - x[0];
+        let dy = x[3] - x[1];
+        out[0] = (dx * dx + dy * dy).sqrt() - S::constant(self.value);
+    }
+}
+
+#[test]
+fn test_residual_only_constraint_gets_a_jacobian() {
+    let constraint = Distance {
+        indices: [0, 1, 2, 3],
+        value: 5.0,
+    };
+    let params = [1.0, 1.0, 4.0, 5.0];
+
+    let mut residual = [0.0];
+    BoundConstraint::residual(&constraint, &params, &mut residual);
+    assert_eq!(residual, [0.0]);
+
+    let mut entries = Vec::new();
+    constraint.jacobian_entries(&params, &mut entries);
+    assert_eq!(
+        entries,
+        [(0, 0, -0.6), (0, 1, -0.8), (0, 2, 0.6), (0, 3, 0.8)]
+    );
+    assert_jacobian_matches(&constraint, &[2.0, -1.0, 0.5, 3.0]);
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the integration tests. Each test crate only uses some
+//! of them.
+#![allow(dead_code)]
+
+use acs::{Circle, ConstraintOptions, ConstraintSolver, ConstraintType, Line, Point};
+
+/// Add a constraint under the given ID
+pub fn add_named(solver: &mut ConstraintSolver, id: &str, constraint_type: ConstraintType) {
+    solver
+        .add_constraint_with_options(
+            constraint_type,
+            ConstraintOptions {
+                id: Some(id.into()),
+                ..Default::default()
+            },
+        )
+        .expect("Constraint should be added successfully");
+}
+
+/// Three points, line `l1` from `p1` to `p2` and circle `c1` around `p1`
+pub fn build_sketch() -> ConstraintSolver {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 3.0, 1.0, false));
+    solver.add_point(Point::new("p3".into(), 5.0, 5.0, false));
+    solver
+        .add_line(Line::new("l1".into(), "p1".into(), "p2".into()))
+        .expect("Line should be added successfully");
+    solver.add_circle(Circle::new("c1".into(), "p1".into(), 2.0, false));
+    solver
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{ConstraintOptions, ConstraintSolver, ConstraintType, Point};
+
+#[test]
+fn test_caller_supplied_id_and_metadata() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 1.0, 3.0, false));
+
+    let entry = solver
+        .add_constraint_with_options(
+            ConstraintType::Vertical("p1".into(), "p2".into()),
+            ConstraintOptions {
+                id: Some("wall-left".into()),
+                name: Some("Left wall".into()),
+                tags: vec!["walls".into()],
+                metadata: HashMap::from([("color".to_string(), "red".to_string())]),
+                ..Default::default()
+            },
+        )
+        .expect("Constraint should be added successfully");
+
+    assert_eq!(entry.id, "wall-left");
+    assert_eq!(entry.name.as_deref(), Some("Left wall"));
+    assert_eq!(entry.tags, vec!["walls".to_string()]);
+    assert_eq!(entry.metadata.get("color").map(String::as_str), Some("red"));
+
+    let stored = solver
+        .get_constraint_entry("wall-left")
+        .expect("Constraint should be stored under its ID");
+    assert_eq!(stored, &entry);
+}
+
+#[test]
+fn test_duplicate_constraint_id_is_rejected() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 1.0, 3.0, false));
+
+    let options = ConstraintOptions {
+        id: Some("c".into()),
+        ..Default::default()
+    };
+    solver
+        .add_constraint_with_options(
+            ConstraintType::Vertical("p1".into(), "p2".into()),
+            options.clone(),
+        )
+        .expect("Constraint should be added successfully");
+
+    let result = solver.add_constraint_with_options(
+        ConstraintType::Horizontal("p1".into(), "p2".into()),
+        options,
+    );
+    assert!(result.is_err(), "Duplicate constraint IDs should be rejected");
+    assert_eq!(solver.get_constraint_entries().len(), 1);
+}
+
+#[test]
+fn test_constraints_included_in_json_response() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 2.0, "y": 1.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Horizontal", "point_a": "p1", "point_b": "p2", "id": "h", "name": "Base", "tags": ["floor"]},
+            {"type": "EqualX", "point": "p2", "x": 4.0}
+        ]
+    }"#;
+
+    let response = solver
+        .solve_from_json(request.to_string())
+        .expect("Request should be solved");
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+
+    let constraints = response["constraints"].as_array().unwrap();
+    assert_eq!(constraints.len(), 2);
+    assert_eq!(constraints[0]["id"], "h");
+    assert_eq!(constraints[0]["name"], "Base");
+    assert_eq!(constraints[0]["tags"][0], "floor");
+    assert_eq!(constraints[0]["type"], "Horizontal");
+    assert_eq!(constraints[1]["type"], "EqualX");
+    assert!(constraints[1]["id"].is_string(), "Generated IDs should be reported");
+}
+
+#[test]
+fn test_add_constraints_json_returns_entries() {
+    let mut solver = WrappedConstraintSolver::new();
+    solver
+        .add_primitives_json(
+            r#"[{"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": false}]"#.to_string(),
+        )
+        .expect("Primitives should be added");
+
+    let added = solver
+        .add_constraints_json(r#"[{"type": "EqualY", "point": "p1", "y": 2.0}]"#.to_string())
+        .expect("Constraints should be added");
+    let added: serde_json::Value = serde_json::from_str(&added).unwrap();
+
+    let id = added[0]["id"].as_str().expect("Entry should have an ID");
+    let listed: serde_json::Value =
+        serde_json::from_str(&solver.get_constraints_json().unwrap()).unwrap();
+    assert_eq!(listed[0]["id"], id);
+}
@@ -0,0 +1,257 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    AcsError, AutodiffConstraint, BoundConstraint, Constraint, ConstraintOptions, ConstraintSolver,
+    ConstraintType, EntityRef, ParameterManager, Point, RemovalPolicy, Scalar, SolverResult,
+};
+use serde::Deserialize;
+
+/// Distance between two points, as a downstream crate would define it
+#[derive(Deserialize)]
+struct DistanceConstraint {
+    point_a: String,
+    point_b: String,
+    distance: f64,
+}
+
+struct BoundDistance {
+    indices: [usize; 4],
+    distance: f64,
+}
+
+impl Constraint for DistanceConstraint {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundDistance {
+            indices: [
+                param_manager.resolve_index(&self.point_a, 0)?,
+                param_manager.resolve_index(&self.point_a, 1)?,
+                param_manager.resolve_index(&self.point_b, 0)?,
+                param_manager.resolve_index(&self.point_b, 1)?,
+            ],
+            distance: self.distance,
+        }))
+    }
+
+    fn type_name(&self) -> &str {
+        "Distance"
+    }
+
+    fn references(&self) -> Vec<EntityRef> {
+        vec![
+            EntityRef::point(&self.point_a),
+            EntityRef::point(&self.point_b),
+        ]
+    }
+}
+
+impl AutodiffConstraint for BoundDistance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.to_vec()
+    }
+
+    fn evaluate<S: Scalar>(&self, x: &[S], out: &mut [S]) {
+        let dx = x[2] - x[0];
+        let dy = x[3] - x[1];
+        out[0] = (dx * dx + dy * dy).sqrt() - S::constant(self.distance);
+    }
+}
+
+fn distance_factory(data: &serde_json::Value) -> Result<Box<dyn Constraint>, AcsError> {
+    let constraint: DistanceConstraint =
+        serde_json::from_value(data.clone()).map_err(|e| AcsError::ParseError {
+            message: format!("Invalid Distance constraint: {e}"),
+        })?;
+    Ok(Box::new(constraint))
+}
+
+fn distance(solver: &ConstraintSolver, a: &str, b: &str) -> f64 {
+    let a = solver.get_point(a.into()).unwrap();
+    let b = solver.get_point(b.into()).unwrap();
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+#[test]
+fn test_add_custom_constraint_instance() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p2".into(), 1.0, 1.0, false));
+    solver
+        .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+        .unwrap();
+
+    let entry = solver
+        .add_custom_constraint(
+            Box::new(DistanceConstraint {
+                point_a: "p1".into(),
+                point_b: "p2".into(),
+                distance: 5.0,
+            }),
+            ConstraintOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(entry.constraint_type.type_name(), "Distance");
+    assert_eq!(entry.entities, ["p1", "p2"]);
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    assert!((distance(&solver, "p1", "p2") - 5.0).abs() < 1e-6);
+    assert!(solver.get_point("p2".into()).unwrap().y.abs() < 1e-6);
+
+    // References are validated and tracked like those of built-in constraints
+    let error = solver
+        .add_custom_constraint(
+            Box::new(DistanceConstraint {
+                point_a: "p1".into(),
+                point_b: "missing".into(),
+                distance: 1.0,
+            }),
+            ConstraintOptions::default(),
+        )
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnknownEntity { .. }));
+
+    let removed = solver.remove_point("p2", RemovalPolicy::Cascade).unwrap();
+    assert_eq!(removed.constraints.len(), 2);
+    assert!(solver.get_constraint_entries().is_empty());
+}
+
+#[test]
+fn test_non_finite_residuals_name_the_constraint() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 3.0, 4.0, false));
+    solver
+        .add_custom_constraint(
+            Box::new(DistanceConstraint {
+                point_a: "a".into(),
+                point_b: "b".into(),
+                distance: f64::NAN,
+            }),
+            ConstraintOptions {
+                id: Some("broken".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let error = solver.solve().unwrap_err();
+    assert_eq!(
+        error,
+        AcsError::NonFiniteResidual {
+            constraint: "broken".into()
+        }
+    );
+    let b = solver.get_point("b".into()).unwrap();
+    assert_eq!((b.x, b.y), (3.0, 4.0));
+}
+
+#[test]
+fn test_registered_type_through_constraint_type() {
+    let mut solver = ConstraintSolver::new();
+    solver
+        .register_constraint_type("Gap", distance_factory)
+        .unwrap();
+    assert!(solver.registry().contains("Gap"));
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 3.0, 0.0, false));
+
+    let custom = |distance: f64| ConstraintType::Custom {
+        type_name: "Gap".into(),
+        data: serde_json::json!({"point_a": "a", "point_b": "b", "distance": distance}),
+    };
+    solver.add_constraint(custom(2.0)).unwrap();
+    solver.solve().unwrap();
+    assert!((distance(&solver, "a", "b") - 2.0).abs() < 1e-6);
+
+    let error = solver
+        .add_constraint(ConstraintType::Custom {
+            type_name: "GearMesh".into(),
+            data: serde_json::Value::Null,
+        })
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnsupportedConstraint { .. }));
+}
+
+#[test]
+fn test_registered_type_in_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    // Distance is built in, so registering it fails instead of leaving the
+    // registered type unreachable from JSON
+    let error = solver
+        .register_constraint_type("Distance", distance_factory)
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnsupportedConstraint { .. }));
+
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "a", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "b", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Distance", "id": "d", "point_a": "a", "point_b": "b", "distance": 10.0},
+            {"type": "Vertical", "point_a": "a", "point_b": "b"}
+        ]
+    }"#;
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+    assert_eq!(
+        response["constraints"][0],
+        serde_json::json!({
+            "type": "Distance",
+            "id": "d",
+            "point_a": "a",
+            "point_b": "b",
+            "distance": 10.0
+        })
+    );
+    let b = solver.get_point("b").unwrap();
+    assert!(b.x.abs() < 1e-6 && (b.y.abs() - 10.0).abs() < 1e-6);
+
+    // Unknown types and malformed built-in constraints are rejected
+    let unknown = request.replace("\"Distance\"", "\"GearMesh\"");
+    let error = solver.solve_from_json(unknown).unwrap_err();
+    assert!(matches!(error, AcsError::UnsupportedConstraint { .. }));
+
+    let malformed = request.replace(
+        "\"point_a\": \"a\", \"point_b\": \"b\"}",
+        "\"point_a\": \"a\"}",
+    );
+    let error = solver.solve_from_json(malformed).unwrap_err();
+    assert!(matches!(error, AcsError::ParseError { .. }));
+
+    // The same constraint under a name of its own goes to the factory
+    solver
+        .register_constraint_type("Gap", distance_factory)
+        .unwrap();
+    let custom = request.replace("\"Distance\"", "\"Gap\"");
+    let response = solver.solve_from_json(custom).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+    assert_eq!(response["constraints"][0]["type"], "Gap");
+}
+
+#[test]
+fn test_every_built_in_name_is_rejected() {
+    let mut solver = WrappedConstraintSolver::new();
+    for &name in ConstraintType::BUILT_IN_NAMES {
+        let error = solver
+            .register_constraint_type(name, distance_factory)
+            .unwrap_err();
+        assert!(
+            matches!(error, AcsError::UnsupportedConstraint { .. }),
+            "{name} was accepted"
+        );
+    }
+
+    let vertical = ConstraintType::Vertical("a".into(), "b".into());
+    assert!(ConstraintType::BUILT_IN_NAMES.contains(&vertical.name()));
+    assert!(!ConstraintType::BUILT_IN_NAMES.contains(&"Custom"));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{ConstraintSolver, ConstraintType, Point, SolverResult};
+use common::add_named;
+
+#[test]
+fn test_independent_parts_are_solved_separately() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("a2".into(), 2.0, 1.0, false));
+    solver.add_point(Point::new("b1".into(), 5.0, 5.0, false));
+    solver.add_point(Point::new("b2".into(), 6.0, 8.0, false));
+    solver.add_point(Point::new("free".into(), 9.0, 9.0, false));
+
+    add_named(
+        &mut solver,
+        "ha",
+        ConstraintType::Horizontal("a1".into(), "a2".into()),
+    );
+    add_named(
+        &mut solver,
+        "vb",
+        ConstraintType::Vertical("b1".into(), "b2".into()),
+    );
+    add_named(&mut solver, "ya", ConstraintType::EqualY("a2".into(), 3.0));
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    // The unconstrained point forms no cluster
+    let clusters = result.clusters();
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0].constraints, vec!["ha", "ya"]);
+    assert_eq!(clusters[0].entities, vec!["a1", "a2"]);
+    assert_eq!(clusters[1].constraints, vec!["vb"]);
+    assert!(clusters.iter().all(|c| c.converged));
+
+    let free = solver.get_point("free".into()).unwrap();
+    assert_eq!((free.x, free.y), (9.0, 9.0));
+}
+
+#[test]
+fn test_each_cluster_reports_its_own_status() {
+    let mut solver = ConstraintSolver::new();
+    // A fixed point shared by both parts does not connect them
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p1".into(), 1.0, 2.0, false));
+    solver.add_point(Point::new("p2".into(), 3.0, 4.0, false));
+
+    add_named(
+        &mut solver,
+        "v",
+        ConstraintType::Vertical("origin".into(), "p1".into()),
+    );
+    add_named(
+        &mut solver,
+        "h",
+        ConstraintType::Horizontal("origin".into(), "p2".into()),
+    );
+    // Conflicting dimensions make the second part unsolvable
+    add_named(&mut solver, "y1", ConstraintType::EqualY("p2".into(), 1.0));
+    add_named(&mut solver, "y2", ConstraintType::EqualY("p2".into(), 2.0));
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    let clusters = result.clusters();
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0].constraints, vec!["v"]);
+    assert!(clusters[0].converged);
+    assert_eq!(clusters[1].constraints, vec!["h", "y1", "y2"]);
+    assert!(!clusters[1].converged);
+
+    // The solvable part is still solved
+    let p1 = solver.get_point("p1".into()).unwrap();
+    assert!(p1.x.abs() < 1e-6);
+}
+
+#[test]
+fn test_clusters_in_json_response() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": false},
+            {"type": "Point", "id": "p2", "x": 2.0, "y": 1.0, "fixed": false},
+            {"type": "Point", "id": "p3", "x": 4.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Horizontal", "point_a": "p1", "point_b": "p2", "id": "h"},
+            {"type": "EqualX", "point": "p3", "x": 1.0, "id": "x"}
+        ]
+    }"#;
+
+    let response = solver
+        .solve_from_json(request.to_string())
+        .expect("Request should be solved");
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+
+    let clusters = response["result"]["clusters"].as_array().unwrap();
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0]["constraints"][0], "h");
+    assert_eq!(clusters[1]["entities"][0], "p3");
+    assert_eq!(clusters[1]["converged"], true);
+
+    // A satisfied sketch converges at once, and the errors stay finite
+    let satisfied = request
+        .replace("\"x\": 2.0, \"y\": 1.0", "\"x\": 2.0, \"y\": 0.0")
+        .replace("\"x\": 4.0, \"y\": 4.0", "\"x\": 1.0, \"y\": 4.0");
+    let response = solver
+        .solve_from_json(satisfied)
+        .expect("Request should be solved");
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["iterations"], 0);
+    for cluster in response["result"]["clusters"].as_array().unwrap() {
+        let initial_error = cluster["initial_error"].as_f64().unwrap();
+        assert!(initial_error < 1e-6, "{cluster}");
+    }
+}
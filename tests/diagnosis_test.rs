@@ -0,0 +1,146 @@
+mod common;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{ConstraintSolver, ConstraintType, Point, SolverResult};
+use common::add_named;
+
+#[test]
+fn test_conflicting_dimensions_are_reported() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 1.0, 3.0, false));
+
+    add_named(&mut solver, "x", ConstraintType::EqualX("p1".into(), 2.0));
+    add_named(&mut solver, "y1", ConstraintType::EqualY("p2".into(), 1.0));
+    add_named(&mut solver, "y2", ConstraintType::EqualY("p2".into(), 2.0));
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    let diagnosis = solver.diagnose_constraints().unwrap();
+    assert!(!diagnosis.is_consistent());
+    assert!(diagnosis.redundant.is_empty());
+    assert_eq!(diagnosis.conflicts.len(), 1);
+    assert_eq!(diagnosis.conflicts[0].constraint, "y2");
+    assert_eq!(diagnosis.conflicts[0].index, 2);
+    assert_eq!(diagnosis.conflicts[0].depends_on, vec!["y1"]);
+    assert_eq!(diagnosis.conflicting_constraints(), vec!["y1", "y2"]);
+}
+
+#[test]
+fn test_redundant_constraints_are_reported() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 2.0, 1.0, false));
+
+    add_named(
+        &mut solver,
+        "h1",
+        ConstraintType::Horizontal("p1".into(), "p2".into()),
+    );
+    add_named(
+        &mut solver,
+        "h2",
+        ConstraintType::Horizontal("p2".into(), "p1".into()),
+    );
+
+    // Consistent before solving as well as after
+    let diagnosis = solver.diagnose_constraints().unwrap();
+    assert!(diagnosis.is_consistent());
+    assert_eq!(diagnosis.redundant.len(), 1);
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let diagnosis = solver.diagnose_constraints().unwrap();
+    assert!(diagnosis.is_consistent());
+    assert_eq!(diagnosis.redundant.len(), 1);
+    assert_eq!(diagnosis.redundant[0].constraint, "h2");
+    assert_eq!(diagnosis.redundant[0].depends_on, vec!["h1"]);
+}
+
+#[test]
+fn test_satisfied_point_on_line_is_not_redundant() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 10.0, 0.0, true));
+    solver.add_point(Point::new("p".into(), 3.0, 0.0, false));
+    add_named(
+        &mut solver,
+        "on",
+        ConstraintType::PointOnLine("p".into(), "a".into(), "b".into()),
+    );
+
+    // A zero distance between coincident points has no derivative, which
+    // is not evidence of redundancy either
+    solver.add_point(Point::new("q".into(), 0.0, 0.0, false));
+    add_named(
+        &mut solver,
+        "zero",
+        ConstraintType::Distance("a".into(), "q".into(), 0.0),
+    );
+
+    let diagnosis = solver.diagnose_constraints().unwrap();
+    assert!(diagnosis.is_consistent());
+    assert!(diagnosis.redundant.is_empty());
+}
+
+#[test]
+fn test_conflict_set_is_minimal() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("b".into(), 1.0, 1.0, false));
+    solver.add_point(Point::new("c".into(), 2.0, 3.0, false));
+
+    add_named(&mut solver, "ya", ConstraintType::EqualY("a".into(), 0.0));
+    add_named(
+        &mut solver,
+        "hab",
+        ConstraintType::Horizontal("a".into(), "b".into()),
+    );
+    add_named(
+        &mut solver,
+        "vbc",
+        ConstraintType::Vertical("b".into(), "c".into()),
+    );
+    add_named(&mut solver, "yb", ConstraintType::EqualY("b".into(), 1.0));
+
+    solver.solve().unwrap();
+    let diagnosis = solver.diagnose_constraints().unwrap();
+
+    // The vertical constraint plays no part in the conflict
+    assert_eq!(diagnosis.conflicts.len(), 1);
+    assert_eq!(diagnosis.conflicts[0].constraint, "yb");
+    assert_eq!(diagnosis.conflicts[0].depends_on, vec!["ya", "hab"]);
+}
+
+#[test]
+fn test_diagnosis_in_json_response() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 2.0, "y": 1.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Horizontal", "point_a": "p1", "point_b": "p2", "id": "h"},
+            {"type": "EqualY", "point": "p2", "y": 4.0, "id": "y"}
+        ]
+    }"#;
+
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], false);
+
+    let diagnosis = &response["diagnosis"];
+    assert_eq!(diagnosis["consistent"], false);
+    assert_eq!(diagnosis["conflicts"][0]["constraint"], "y");
+    assert_eq!(diagnosis["conflicting"], serde_json::json!(["h", "y"]));
+
+    // Converged solves carry no diagnosis
+    let request = request.replace("\"y\": 4.0", "\"y\": 0.0");
+    let response = solver.solve_from_json(request).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+    assert!(response.get("diagnosis").is_none());
+}
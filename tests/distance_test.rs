@@ -0,0 +1,24 @@
+use acs::{AcsError, ConstraintSolver, ConstraintType, Point, SolverResult};
+
+#[test]
+fn test_distance_constraint() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 3.0, 4.0, false));
+
+    solver
+        .add_constraint(ConstraintType::Distance("a".into(), "b".into(), 10.0))
+        .expect("Constraint should be added successfully");
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let b = solver.get_point("b".into()).unwrap();
+    let distance = (b.x * b.x + b.y * b.y).sqrt();
+    assert!((distance - 10.0).abs() < 1e-6, "distance = {distance}");
+
+    let error = solver
+        .add_constraint(ConstraintType::Distance("a".into(), "b".into(), -1.0))
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnsupportedConstraint { .. }));
+}
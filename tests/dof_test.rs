@@ -0,0 +1,149 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{Circle, ConstraintSolver, ConstraintType, DofStatus, Point};
+
+#[test]
+fn test_unconstrained_sketch_is_free() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 1.0, 1.0, false));
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+
+    let analysis = solver.analyze_dof().expect("Analysis should succeed");
+    assert_eq!(analysis.dof, 4);
+    assert_eq!(analysis.rank, 0);
+    assert_eq!(analysis.free_parameters, 4);
+    assert_eq!(analysis.entity("p1").unwrap().status, DofStatus::Free);
+    assert_eq!(analysis.entity("p2").unwrap().dof, 2);
+    assert_eq!(
+        analysis.entity("origin").unwrap().status,
+        DofStatus::FullyConstrained
+    );
+}
+
+#[test]
+fn test_point_with_one_locked_axis_is_free() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point {
+        fixed_x: true,
+        ..Point::new("guide".into(), 1.0, 2.0, false)
+    });
+
+    let analysis = solver.analyze_dof().expect("Analysis should succeed");
+    let guide = analysis.entity("guide").unwrap();
+    assert_eq!(guide.dof, 1);
+    assert_eq!(guide.parameters, 2);
+    assert_eq!(guide.status, DofStatus::Free);
+
+    // Constraining the remaining axis leaves nothing to move
+    solver
+        .add_constraint(ConstraintType::EqualY("guide".into(), 2.0))
+        .unwrap();
+    let analysis = solver.analyze_dof().expect("Analysis should succeed");
+    assert_eq!(
+        analysis.entity("guide").unwrap().status,
+        DofStatus::FullyConstrained
+    );
+}
+
+#[test]
+fn test_partially_and_fully_constrained_points() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p1".into(), 2.0, 1.0, false));
+
+    solver
+        .add_constraint(ConstraintType::Horizontal("origin".into(), "p1".into()))
+        .unwrap();
+
+    let analysis = solver.analyze_dof().unwrap();
+    assert_eq!(analysis.dof, 1);
+    assert!(!analysis.is_fully_constrained());
+    let p1 = analysis.entity("p1").unwrap();
+    assert_eq!(p1.dof, 1);
+    assert_eq!(p1.status, DofStatus::PartiallyConstrained);
+
+    solver
+        .add_constraint(ConstraintType::EqualX("p1".into(), 3.0))
+        .unwrap();
+
+    let analysis = solver.analyze_dof().unwrap();
+    assert_eq!(analysis.dof, 0);
+    assert_eq!(analysis.rank, 2);
+    assert!(analysis.is_fully_constrained());
+    assert_eq!(
+        analysis.entity("p1").unwrap().status,
+        DofStatus::FullyConstrained
+    );
+}
+
+#[test]
+fn test_redundant_constraints_do_not_add_rank() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 2.0, 1.0, false));
+    solver.add_circle(Circle::new("c1".into(), "p1".into(), 1.0, false));
+    solver.add_circle(Circle::new("c2".into(), "p2".into(), 2.0, false));
+
+    solver
+        .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::Horizontal("p2".into(), "p1".into()))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::EqualRadius("c1".into(), "c2".into()))
+        .unwrap();
+
+    let analysis = solver.analyze_dof().unwrap();
+    assert_eq!(analysis.free_parameters, 6);
+    assert_eq!(analysis.rank, 2);
+    assert_eq!(analysis.dof, 4);
+    assert_eq!(analysis.entity("c1").unwrap().status, DofStatus::Free);
+}
+
+#[test]
+fn test_satisfied_point_on_line_removes_one_dof() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 10.0, 0.0, true));
+    solver.add_point(Point::new("p".into(), 3.0, 0.0, false));
+    solver
+        .add_constraint(ConstraintType::PointOnLine(
+            "p".into(),
+            "a".into(),
+            "b".into(),
+        ))
+        .unwrap();
+
+    // The point already lies on the line and can only slide along it
+    let analysis = solver.analyze_dof().unwrap();
+    assert_eq!(analysis.rank, 1);
+    assert_eq!(analysis.dof, 1);
+    let p = analysis.entity("p").unwrap();
+    assert_eq!(p.dof, 1);
+    assert_eq!(p.status, DofStatus::PartiallyConstrained);
+}
+
+#[test]
+fn test_dof_analysis_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let primitives = r#"[
+        {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+        {"type": "Point", "id": "p2", "x": 2.0, "y": 1.0, "fixed": false}
+    ]"#;
+    solver.add_primitives_json(primitives.to_string()).unwrap();
+    let constraints = r#"[
+        {"type": "Vertical", "point_a": "p1", "point_b": "p2"}
+    ]"#;
+    solver.add_constraints_json(constraints.to_string()).unwrap();
+
+    let json = solver.analyze_dof_json().expect("Analysis should succeed");
+    let analysis: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(analysis["dof"], 1);
+    assert_eq!(analysis["fully_constrained"], false);
+    let entities = analysis["entities"].as_array().unwrap();
+    assert_eq!(entities[0]["id"], "p1");
+    assert_eq!(entities[0]["status"], "fully_constrained");
+    assert_eq!(entities[1]["status"], "partially_constrained");
+}
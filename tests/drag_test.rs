@@ -0,0 +1,112 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{AcsError, ConstraintSolver, ConstraintType, Point, SolverResult};
+
+#[test]
+fn test_drag_projects_onto_constraints() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p1".into(), 2.0, 0.0, false));
+    solver
+        .add_constraint(ConstraintType::Horizontal("origin".into(), "p1".into()))
+        .unwrap();
+
+    let result = solver.drag_point("p1", 3.0, 1.0).unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    // The point follows the pointer as far as the constraint allows
+    let p1 = solver.get_point("p1".into()).unwrap();
+    assert!((p1.x - 3.0).abs() < 1e-9);
+    assert!(p1.y.abs() < 1e-6);
+}
+
+#[test]
+fn test_drag_moves_the_rest_of_the_sketch_little() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 2.0, 0.0, false));
+    solver.add_point(Point::new("free".into(), 5.0, 5.0, false));
+    solver
+        .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+        .unwrap();
+
+    let result = solver.drag_point("p2", 2.0, 1.0).unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    assert_eq!(result.clusters().len(), 1);
+
+    // The other endpoint follows, and nothing else moves
+    let p1 = solver.get_point("p1".into()).unwrap().clone();
+    let p2 = solver.get_point("p2".into()).unwrap().clone();
+    assert!((p1.y - p2.y).abs() < 1e-6);
+    assert!(p2.y > 0.99);
+    assert_eq!(p1.x, 0.0);
+    let free = solver.get_point("free".into()).unwrap();
+    assert_eq!((free.x, free.y), (5.0, 5.0));
+}
+
+#[test]
+fn test_drag_keeps_nonlinear_constraints() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a1".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("a2".into(), 4.0, 0.0, true));
+    solver.add_point(Point::new("b1".into(), 0.0, 2.0, false));
+    solver.add_point(Point::new("b2".into(), 4.0, 2.0, false));
+    solver
+        .add_constraint(ConstraintType::Parallel(
+            "a1".into(),
+            "a2".into(),
+            "b1".into(),
+            "b2".into(),
+        ))
+        .unwrap();
+
+    let result = solver.drag_point("b2", 4.0, 3.0).unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    // b1 rises with b2 to keep the lines parallel
+    let b1 = solver.get_point("b1".into()).unwrap().clone();
+    let b2 = solver.get_point("b2".into()).unwrap().clone();
+    assert!((b1.y - b2.y).abs() < 1e-3);
+    assert!(b1.y > 2.5);
+}
+
+#[test]
+fn test_drag_rejects_fixed_and_unknown_points() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+
+    let error = solver.drag_point("origin", 1.0, 1.0).unwrap_err();
+    assert!(matches!(error, AcsError::FixedParameter { .. }));
+    let error = solver.drag_point("missing", 1.0, 1.0).unwrap_err();
+    assert!(matches!(error, AcsError::UnknownEntity { .. }));
+}
+
+#[test]
+fn test_drag_point_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 0.0, "y": 2.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Vertical", "point_a": "p1", "point_b": "p2", "id": "v"}
+        ]
+    }"#;
+    solver.solve_from_json(request.to_string()).unwrap();
+
+    let response = solver.drag_point("p2", 1.0, 5.0).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["converged"], true);
+    assert_eq!(response["clusters"].as_array().unwrap().len(), 1);
+    // Only the moved point is reported, and no diagnosis or residuals
+    let points = response["points"].as_array().unwrap();
+    assert_eq!(points.len(), 1);
+    assert_eq!(points[0]["id"], "p2");
+    assert_eq!(points[0]["y"], 5.0);
+    assert!(response.get("residuals").is_none());
+    assert!(response.get("diagnosis").is_none());
+
+    let p2 = solver.get_point("p2").unwrap();
+    assert!(p2.x.abs() < 1e-6);
+    assert_eq!(p2.y, 5.0);
+}
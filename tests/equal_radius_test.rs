@@ -24,7 +24,7 @@ fn test_equal_radius_constraint_residual() {
     let constraint = EqualRadiusConstraint::new("c1".to_string(), "c2".to_string());
 
     // Test residual calculation
-    let residual = constraint.residual(&param_manager);
+    let residual = constraint.residual(&param_manager).unwrap();
 
     // Expected residual: radius1 - radius2 = 5.0 - 3.0 = 2.0
     assert_eq!(residual.len(), 1);
@@ -56,7 +56,7 @@ fn test_equal_radius_constraint_jacobian() {
     let constraint = EqualRadiusConstraint::new("c1".to_string(), "c2".to_string());
 
     // Test jacobian calculation
-    let jacobian = constraint.jacobian(&param_manager);
+    let jacobian = constraint.jacobian(&param_manager).unwrap();
 
     // Should have 1 row (one constraint) and 6 columns (2 points × 2 parameters + 2 circles × 1 parameter each)
     assert_eq!(jacobian.nrows(), 1);
@@ -105,7 +105,7 @@ fn test_equal_radius_constraint_zero_residual() {
     let constraint = EqualRadiusConstraint::new("c1".to_string(), "c2".to_string());
 
     // Test residual calculation
-    let residual = constraint.residual(&param_manager);
+    let residual = constraint.residual(&param_manager).unwrap();
 
     // Expected residual: radius1 - radius2 = 4.0 - 4.0 = 0.0
     assert_eq!(residual.len(), 1);
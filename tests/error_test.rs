@@ -0,0 +1,42 @@
+use acs::{AcsError, ConstraintSolver, Line, Point, RemovalPolicy};
+
+#[test]
+fn test_error_serializes_with_kind_and_details() {
+    let error = AcsError::UnknownEntity {
+        entity: "p9".into(),
+        constraint: Some("Horizontal".into()),
+    };
+
+    let value = serde_json::to_value(&error).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "kind": "UnknownEntity",
+            "details": {"entity": "p9", "constraint": "Horizontal"}
+        })
+    );
+    assert_eq!(error.kind(), "UnknownEntity");
+    assert_eq!(
+        error.to_string(),
+        "Constraint Horizontal references unknown entity p9"
+    );
+}
+
+#[test]
+fn test_entity_in_use_lists_dependents() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 1.0, 0.0, false));
+    solver
+        .add_line(Line::new("l1".into(), "p1".into(), "p2".into()))
+        .expect("Line should be added successfully");
+
+    let result = solver.remove_point("p1", RemovalPolicy::Error);
+    assert_eq!(
+        result,
+        Err(AcsError::EntityInUse {
+            entity: "p1".into(),
+            dependents: vec!["l1".into()],
+        })
+    );
+}
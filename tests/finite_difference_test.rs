@@ -0,0 +1,109 @@
+use acs::{
+    AcsError, BoundConstraint, Constraint, ConstraintOptions, ConstraintSolver, EntityRef,
+    GeometrySystem, JacobianEntry, ParameterManager, Point, SolverResult, check_jacobian,
+    finite_difference_jacobian,
+};
+
+/// Distance between two points with only its residual written out, as a
+/// callback-defined constraint has
+struct NumericDistance {
+    point_a: String,
+    point_b: String,
+    distance: f64,
+}
+
+struct BoundNumericDistance {
+    indices: [usize; 4],
+    distance: f64,
+}
+
+impl Constraint for NumericDistance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn bind(&self, param_manager: &ParameterManager) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(BoundNumericDistance {
+            indices: [
+                param_manager.resolve_index(&self.point_a, 0)?,
+                param_manager.resolve_index(&self.point_a, 1)?,
+                param_manager.resolve_index(&self.point_b, 0)?,
+                param_manager.resolve_index(&self.point_b, 1)?,
+            ],
+            distance: self.distance,
+        }))
+    }
+
+    fn type_name(&self) -> &str {
+        "NumericDistance"
+    }
+
+    fn references(&self) -> Vec<EntityRef> {
+        vec![
+            EntityRef::point(&self.point_a),
+            EntityRef::point(&self.point_b),
+        ]
+    }
+}
+
+impl BoundConstraint for BoundNumericDistance {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.to_vec()
+    }
+
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        let [ax, ay, bx, by] = self.indices.map(|i| params[i]);
+        out[0] = (bx - ax).hypot(by - ay) - self.distance;
+    }
+
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>) {
+        finite_difference_jacobian(self, params, out);
+    }
+}
+
+fn constraint(distance: f64) -> NumericDistance {
+    NumericDistance {
+        point_a: "a".into(),
+        point_b: "b".into(),
+        distance,
+    }
+}
+
+#[test]
+fn test_finite_differences_match_analytic_jacobian() {
+    let mut geometry = GeometrySystem::new();
+    geometry.add_point(Point::new("a".into(), 1.0, -2.0, false));
+    geometry.add_point(Point::new("b".into(), 4.0, 2.0, false));
+    let param_manager = &ParameterManager::from_geometry(&geometry);
+
+    let check = check_jacobian(&constraint(1.0), param_manager).unwrap();
+    assert!(check.is_ok(), "{:?}", check.mismatches);
+    assert!(check.max_error < 1e-8, "{}", check.max_error);
+
+    // d/d(bx) of |b - a| is (bx - ax) / |b - a|
+    let bound = constraint(1.0).bind(param_manager).unwrap();
+    let mut entries = Vec::new();
+    bound.jacobian_entries(param_manager.get_parameters(), &mut entries);
+    let bx = param_manager.resolve_index("b", 0).unwrap();
+    let (_, _, derivative) = entries.iter().find(|entry| entry.1 == bx).unwrap();
+    assert!((derivative - 0.6).abs() < 1e-8);
+}
+
+#[test]
+fn test_solve_with_finite_difference_jacobian() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 3.0, 4.0, false));
+    solver
+        .add_custom_constraint(Box::new(constraint(10.0)), ConstraintOptions::default())
+        .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    let b = solver.get_point("b".into()).unwrap();
+    assert!((b.x.hypot(b.y) - 10.0).abs() < 1e-6);
+}
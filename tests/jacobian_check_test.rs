@@ -0,0 +1,140 @@
+use acs::{
+    AcsError, BoundConstraint, Circle, Constraint, ConstraintType, GeometrySystem, JacobianEntry,
+    ParameterManager, Point, check_jacobian, create_constraint,
+};
+
+fn sketch() -> ParameterManager {
+    let mut geometry = GeometrySystem::new();
+    for (id, x, y) in [
+        ("p1", 0.3, -1.2),
+        ("p2", 2.0, 1.0),
+        ("p3", 1.0, 3.5),
+        ("p4", 4.0, 2.0),
+    ] {
+        geometry.add_point(Point::new(id.into(), x, y, false));
+    }
+    geometry.add_circle(Circle::new("c1".into(), "p1".into(), 2.0, false));
+    geometry.add_circle(Circle::new("c2".into(), "p2".into(), 3.5, false));
+    ParameterManager::from_geometry(&geometry)
+}
+
+#[test]
+fn test_built_in_constraints_pass() {
+    let param_manager = sketch();
+    let constraint_types = [
+        ConstraintType::Vertical("p1".into(), "p2".into()),
+        ConstraintType::Horizontal("p1".into(), "p2".into()),
+        ConstraintType::Parallel("p1".into(), "p2".into(), "p3".into(), "p4".into()),
+        ConstraintType::EqualX("p3".into(), 1.5),
+        ConstraintType::EqualY("p3".into(), -0.5),
+        ConstraintType::Coincident("p1".into(), "p4".into()),
+        ConstraintType::PointOnLine("p3".into(), "p1".into(), "p2".into()),
+        ConstraintType::PointOnLine("p4".into(), "p1".into(), "p2".into()),
+        ConstraintType::EqualRadius("c1".into(), "c2".into()),
+    ];
+
+    for constraint_type in constraint_types {
+        let name = constraint_type.name();
+        let constraint = create_constraint(constraint_type).unwrap();
+        let check = check_jacobian(constraint.as_ref(), &param_manager).unwrap();
+        assert!(check.is_ok(), "{name}: {:?}", check.mismatches);
+        assert!(check.max_error < 1e-6, "{name}: {}", check.max_error);
+    }
+}
+
+/// Point on an infinite line through `a` and `b`, with the derivatives of
+/// the line's points left out, as the old point-on-line Jacobian did
+struct IncompletePointOnLine {
+    indices: [usize; 6],
+}
+
+impl Constraint for IncompletePointOnLine {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn bind(
+        &self,
+        _param_manager: &ParameterManager,
+    ) -> Result<Box<dyn BoundConstraint>, AcsError> {
+        Ok(Box::new(IncompletePointOnLine {
+            indices: self.indices,
+        }))
+    }
+}
+
+impl BoundConstraint for IncompletePointOnLine {
+    fn num_residuals(&self) -> usize {
+        1
+    }
+
+    fn parameters(&self) -> Vec<usize> {
+        self.indices.to_vec()
+    }
+
+    fn residual(&self, params: &[f64], out: &mut [f64]) {
+        let [x, y, ax, ay, bx, by] = self.indices.map(|i| params[i]);
+        out[0] = (bx - ax) * (y - ay) - (by - ay) * (x - ax);
+    }
+
+    fn jacobian_entries(&self, params: &[f64], out: &mut Vec<JacobianEntry>) {
+        let [_, _, ax, ay, bx, by] = self.indices.map(|i| params[i]);
+        out.push((0, self.indices[0], -(by - ay)));
+        out.push((0, self.indices[1], bx - ax));
+    }
+}
+
+#[test]
+fn test_incomplete_jacobian_is_reported() {
+    let param_manager = sketch();
+    let index = |id: &str, k| param_manager.resolve_index(id, k).unwrap();
+    let constraint: Box<dyn Constraint> = Box::new(IncompletePointOnLine {
+        indices: [
+            index("p3", 0),
+            index("p3", 1),
+            index("p1", 0),
+            index("p1", 1),
+            index("p2", 0),
+            index("p2", 1),
+        ],
+    });
+
+    let check = check_jacobian(constraint.as_ref(), &param_manager).unwrap();
+    assert!(!check.is_ok());
+
+    // The point's own derivatives are right; all four of the line's are missing
+    let mut names: Vec<&str> = check
+        .mismatches
+        .iter()
+        .map(|mismatch| mismatch.parameter.as_str())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["p1.x", "p1.y", "p2.x", "p2.y"]);
+
+    // Missing entries are off by 100%, smaller errors come after them
+    for mismatch in &check.mismatches {
+        let expected = match mismatch.parameter.as_str() {
+            "p1.x" => -2.5,
+            "p1.y" => -1.0,
+            "p2.x" => 4.7,
+            _ => -0.7,
+        };
+        assert_eq!(mismatch.analytic, 0.0);
+        assert!((mismatch.numeric - expected).abs() < 1e-6);
+    }
+    assert_eq!(check.mismatches[0].error, 1.0);
+    assert_eq!(check.mismatches[3].parameter, "p2.y");
+    assert_eq!(check.max_error, 1.0);
+}
+
+#[test]
+fn test_out_of_range_column_is_an_error() {
+    let param_manager = sketch();
+    let past_end = param_manager.num_parameters();
+    let constraint: Box<dyn Constraint> = Box::new(IncompletePointOnLine {
+        indices: [past_end, 1, 2, 3, 4, 5],
+    });
+
+    let error = check_jacobian(constraint.as_ref(), &param_manager).unwrap_err();
+    assert!(matches!(error, AcsError::InvalidParameter { .. }));
+}
@@ -0,0 +1,253 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    Circle, ConstraintSolver, ConstraintType, Point, SolverKind, SolverOptions, SolverResult,
+};
+
+/// The scenarios of the other solver tests, each building a sketch and
+/// checking the solution
+type Scenario = (
+    &'static str,
+    fn(&mut ConstraintSolver),
+    fn(&ConstraintSolver),
+);
+
+fn point(solver: &ConstraintSolver, id: &str) -> (f64, f64) {
+    let point = solver.get_point(id.into()).expect("Point should exist");
+    (point.x, point.y)
+}
+
+fn scenarios() -> Vec<Scenario> {
+    vec![
+        (
+            "horizontal",
+            |solver| {
+                solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+                solver.add_point(Point::new("p2".into(), 3.0, 4.0, false));
+                solver
+                    .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+                    .unwrap();
+            },
+            |solver| assert!((point(solver, "p1").1 - point(solver, "p2").1).abs() < 1e-6),
+        ),
+        (
+            "fixed_points",
+            |solver| {
+                solver.add_point(Point::new("fixed".into(), 0.0, 0.0, true));
+                solver.add_point(Point::new("movable".into(), 3.0, 4.0, false));
+                solver
+                    .add_constraint(ConstraintType::Vertical("fixed".into(), "movable".into()))
+                    .unwrap();
+            },
+            |solver| {
+                let (x, y) = point(solver, "movable");
+                assert!(x.abs() < 1e-6 && (y - 4.0).abs() < 1e-6);
+            },
+        ),
+        (
+            "coincident",
+            |solver| {
+                solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+                solver.add_point(Point::new("p2".into(), 3.0, 5.0, false));
+                solver
+                    .add_constraint(ConstraintType::Coincident("p1".into(), "p2".into()))
+                    .unwrap();
+            },
+            |solver| {
+                let (a, b) = (point(solver, "p1"), point(solver, "p2"));
+                assert!((a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6);
+            },
+        ),
+        (
+            "equal_x_and_y",
+            |solver| {
+                solver.add_point(Point::new("p1".into(), 1.0, 1.0, false));
+                solver
+                    .add_constraint(ConstraintType::EqualX("p1".into(), 7.0))
+                    .unwrap();
+                solver
+                    .add_constraint(ConstraintType::EqualY("p1".into(), -2.0))
+                    .unwrap();
+            },
+            |solver| {
+                let (x, y) = point(solver, "p1");
+                assert!((x - 7.0).abs() < 1e-6 && (y + 2.0).abs() < 1e-6);
+            },
+        ),
+        (
+            "parallel",
+            |solver| {
+                solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+                solver.add_point(Point::new("p2".into(), 1.0, 1.0, false));
+                solver.add_point(Point::new("p3".into(), 0.0, 1.0, false));
+                solver.add_point(Point::new("p4".into(), 1.0, 5.0, false));
+                solver
+                    .add_constraint(ConstraintType::Parallel(
+                        "p1".into(),
+                        "p2".into(),
+                        "p3".into(),
+                        "p4".into(),
+                    ))
+                    .unwrap();
+            },
+            |solver| {
+                let angle = |a: (f64, f64), b: (f64, f64)| (b.1 - a.1).atan2(b.0 - a.0);
+                let first = angle(point(solver, "p1"), point(solver, "p2"));
+                let second = angle(point(solver, "p3"), point(solver, "p4"));
+                assert!((first - second).abs() < 1e-4);
+            },
+        ),
+        (
+            "point_on_line",
+            |solver| {
+                solver.add_point(Point::new("1".into(), 0.0, 0.0, true));
+                solver.add_point(Point::new("2".into(), 0.0, 4.0, true));
+                solver.add_point(Point::new("3".into(), 1.0, 1.0, false));
+                solver
+                    .add_constraint(ConstraintType::PointOnLine(
+                        "3".into(),
+                        "1".into(),
+                        "2".into(),
+                    ))
+                    .unwrap();
+            },
+            |solver| assert!(point(solver, "3").0.abs() < 1e-3),
+        ),
+        (
+            "equal_radius",
+            |solver| {
+                solver.add_point(Point::new("center1".into(), 0.0, 0.0, false));
+                solver.add_point(Point::new("center2".into(), 10.0, 10.0, false));
+                solver.add_circle(Circle::new("c1".into(), "center1".into(), 10.0, false));
+                solver.add_circle(Circle::new("c2".into(), "center2".into(), 3.0, false));
+                solver
+                    .add_constraint(ConstraintType::EqualRadius("c1".into(), "c2".into()))
+                    .unwrap();
+            },
+            |solver| {
+                let c1 = solver.get_circle("c1".into()).unwrap().radius;
+                let c2 = solver.get_circle("c2".into()).unwrap().radius;
+                assert!((c1 - c2).abs() < 1e-6);
+            },
+        ),
+    ]
+}
+
+#[test]
+fn test_both_solvers_solve_the_existing_scenarios() {
+    for (name, build, check) in scenarios() {
+        for kind in [SolverKind::DogLeg, SolverKind::LevenbergMarquardt] {
+            let mut solver = ConstraintSolver::with_solver(kind.create());
+            build(&mut solver);
+
+            let result = solver.solve().expect("Solver should solve successfully");
+            assert!(
+                matches!(result, SolverResult::Converged { .. }),
+                "{name} with {kind:?} did not converge: {result:?}"
+            );
+            check(&solver);
+        }
+    }
+}
+
+#[test]
+fn test_both_solvers_count_iterations_alike() {
+    for kind in [SolverKind::DogLeg, SolverKind::LevenbergMarquardt] {
+        let mut solver = ConstraintSolver::with_solver(kind.create());
+        solver.add_point(Point::new("p".into(), 2.0, 0.0, false));
+        let id = solver
+            .add_constraint(ConstraintType::EqualX("p".into(), 2.0))
+            .unwrap();
+
+        // A satisfied sketch takes no steps
+        let result = solver.solve().unwrap();
+        assert_eq!(result.clusters()[0].iterations, 0, "{kind:?}");
+
+        // Otherwise every step counts, including the last one
+        solver.remove_constraint(&id).unwrap();
+        solver
+            .add_constraint(ConstraintType::EqualX("p".into(), 1e3))
+            .unwrap();
+        solver.set_options(SolverOptions {
+            max_iterations: 1,
+            ..Default::default()
+        });
+        let result = solver.solve().unwrap();
+        assert_eq!(result.clusters()[0].iterations, 1, "{kind:?}");
+    }
+}
+
+#[test]
+fn test_levenberg_marquardt_handles_badly_scaled_sketch() {
+    // A parallel constraint against a target far away: the Jacobian entries
+    // grow with the distance, and dog-leg's capped trust region crawls
+    let build = |solver: &mut ConstraintSolver| {
+        solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+        solver.add_point(Point::new("b".into(), 1.0, 1.0, true));
+        solver.add_point(Point::new("c".into(), 0.0, 5.0, false));
+        solver.add_point(Point::new("d".into(), 1.0, 5.0, false));
+        solver
+            .add_constraint(ConstraintType::Parallel(
+                "a".into(),
+                "b".into(),
+                "c".into(),
+                "d".into(),
+            ))
+            .unwrap();
+        solver
+            .add_constraint(ConstraintType::EqualX("d".into(), 5000.0))
+            .unwrap();
+    };
+
+    let mut dog_leg = ConstraintSolver::new();
+    build(&mut dog_leg);
+    let result = dog_leg.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    let mut levenberg_marquardt = ConstraintSolver::new();
+    levenberg_marquardt.set_solver(SolverKind::LevenbergMarquardt.create());
+    build(&mut levenberg_marquardt);
+    let result = levenberg_marquardt.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let (c, d) = (
+        point(&levenberg_marquardt, "c"),
+        point(&levenberg_marquardt, "d"),
+    );
+    assert!((d.0 - 5000.0).abs() < 1e-6);
+    assert!(((d.1 - c.1) - (d.0 - c.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_solver_selected_from_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "solver": "LevenbergMarquardt",
+        "primitives": [
+            {"type": "Point", "id": "a", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "b", "x": 1.0, "y": 1.0, "fixed": true},
+            {"type": "Point", "id": "c", "x": 0.0, "y": 5.0, "fixed": false},
+            {"type": "Point", "id": "d", "x": 1.0, "y": 5.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Parallel", "point_a": "a", "point_b": "b", "point_c": "c", "point_d": "d"},
+            {"type": "EqualX", "point": "d", "x": 5000.0}
+        ]
+    }"#;
+
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+
+    // The choice sticks for later requests, until changed
+    let request = request.replace("\"solver\": \"LevenbergMarquardt\",", "");
+    let response = solver.solve_from_json(request.clone()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+
+    solver.set_solver("DogLeg".into()).unwrap();
+    let response = solver.solve_from_json(request).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], false);
+
+    assert!(solver.set_solver("Newton".into()).is_err());
+}
@@ -0,0 +1,79 @@
+use acs::{ConstraintSolver, ConstraintType, Line, Point, SolverResult};
+
+fn add_line(solver: &mut ConstraintSolver, id: &str, start: (f64, f64), end: (f64, f64)) {
+    let start_id = format!("{id}_start");
+    let end_id = format!("{id}_end");
+    solver.add_point(Point::new(start_id.clone(), start.0, start.1, false));
+    solver.add_point(Point::new(end_id.clone(), end.0, end.1, false));
+    solver
+        .add_line(Line::new(id.to_string(), start_id, end_id))
+        .expect("Line should be added successfully");
+}
+
+#[test]
+fn test_line_requires_existing_endpoints() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+
+    let result = solver.add_line(Line::new("l1".into(), "p1".into(), "missing".into()));
+    assert!(result.is_err(), "Line with a missing endpoint should be rejected");
+    assert!(solver.get_line("l1".into()).is_none());
+
+    let result = solver.add_line(Line::new("l2".into(), "p1".into(), "p1".into()));
+    assert!(result.is_err(), "Line with identical endpoints should be rejected");
+}
+
+#[test]
+fn test_vertical_line_constraint() {
+    let mut solver = ConstraintSolver::new();
+    add_line(&mut solver, "l1", (0.0, 0.0), (2.0, 5.0));
+
+    solver
+        .add_constraint(ConstraintType::VerticalLine("l1".into()))
+        .expect("Constraint should be added successfully");
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let start = solver.get_point("l1_start".into()).expect("Start point should exist");
+    let end = solver.get_point("l1_end".into()).expect("End point should exist");
+    assert!(
+        (start.x - end.x).abs() < 1e-6,
+        "Line should be vertical, got x = {} and x = {}",
+        start.x,
+        end.x
+    );
+}
+
+#[test]
+fn test_parallel_lines_constraint() {
+    let mut solver = ConstraintSolver::new();
+    add_line(&mut solver, "l1", (0.0, 0.0), (1.0, 1.0));
+    add_line(&mut solver, "l2", (0.0, 1.0), (1.0, 5.0));
+
+    solver
+        .add_constraint(ConstraintType::ParallelLines("l1".into(), "l2".into()))
+        .expect("Constraint should be added successfully");
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let angle = |line: &str| {
+        let start = solver.get_point(format!("{line}_start")).unwrap();
+        let end = solver.get_point(format!("{line}_end")).unwrap();
+        (end.y - start.y).atan2(end.x - start.x)
+    };
+    assert!(
+        (angle("l1") - angle("l2")).abs() < 1e-4,
+        "Lines should be parallel"
+    );
+}
+
+#[test]
+fn test_line_constraint_with_unknown_line() {
+    let mut solver = ConstraintSolver::new();
+    add_line(&mut solver, "l1", (0.0, 0.0), (1.0, 1.0));
+
+    let result = solver.add_constraint(ConstraintType::HorizontalLine("l2".into()));
+    assert!(result.is_err(), "Constraint on an unknown line should be rejected");
+}
@@ -0,0 +1,72 @@
+use std::sync::Mutex;
+
+use acs::{ConstraintSolver, ConstraintType, Point, SolverKind, SolverResult};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Keeps every record so the test can inspect them
+struct Capture(Mutex<Vec<(Level, String)>>);
+
+impl Log for Capture {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+fn take_records() -> Vec<(Level, String)> {
+    std::mem::take(&mut *CAPTURE.0.lock().unwrap())
+}
+
+#[test]
+fn test_solver_diagnostics_go_through_the_logger() {
+    log::set_logger(&CAPTURE).unwrap();
+
+    let build = |kind: SolverKind| {
+        let mut solver = ConstraintSolver::with_solver(kind.create());
+        solver.add_point(Point::new("p1".into(), 0.0, 0.0, true));
+        solver.add_point(Point::new("p2".into(), 3.0, 4.0, false));
+        solver
+            .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+            .unwrap();
+        solver
+    };
+
+    // Silent until a level is chosen
+    let mut solver = build(SolverKind::DogLeg);
+    assert!(matches!(
+        solver.solve().unwrap(),
+        SolverResult::Converged { .. }
+    ));
+    solver.print_state();
+    assert!(take_records().is_empty());
+
+    log::set_max_level(LevelFilter::Debug);
+    for kind in [SolverKind::DogLeg, SolverKind::LevenbergMarquardt] {
+        let mut solver = build(kind);
+        solver.solve().unwrap();
+        let records = take_records();
+        assert!(
+            records
+                .iter()
+                .any(|(level, message)| *level == Level::Debug && message.contains("converged")),
+            "{kind:?} logged {records:?}"
+        );
+    }
+
+    log::set_max_level(LevelFilter::Info);
+    solver.print_state();
+    let records = take_records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].0, Level::Info);
+    assert!(records[0].1.contains("Point ID: p2"));
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{ConstraintSolver, ConstraintType, Point, SolverOptions, SolverResult};
+
+/// A line that has to turn parallel to a fixed one while one endpoint moves
+/// far away. Dog-leg needs large steps to solve it quickly.
+fn far_parallel(solver: &mut ConstraintSolver) {
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 1.0, 1.0, true));
+    solver.add_point(Point::new("c".into(), 0.0, 5.0, false));
+    solver.add_point(Point::new("d".into(), 1.0, 5.0, false));
+    solver
+        .add_constraint(ConstraintType::Parallel(
+            "a".into(),
+            "b".into(),
+            "c".into(),
+            "d".into(),
+        ))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::EqualX("d".into(), 5000.0))
+        .unwrap();
+}
+
+#[test]
+fn test_iteration_limit_and_tolerance() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p2".into(), 3.0, 4.0, false));
+    solver
+        .add_constraint(ConstraintType::Horizontal("p1".into(), "p2".into()))
+        .unwrap();
+
+    // Loose enough that the sketch already counts as solved
+    solver.set_options(SolverOptions {
+        tolerance: 10.0,
+        ..Default::default()
+    });
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    assert_eq!(solver.get_point("p2".into()).unwrap().y, 4.0);
+
+    solver.set_options(SolverOptions {
+        max_iterations: 0,
+        ..Default::default()
+    });
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    solver.set_options(SolverOptions::default());
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+}
+
+#[test]
+fn test_trust_radius_bounds() {
+    let mut solver = ConstraintSolver::new();
+    far_parallel(&mut solver);
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    let mut solver = ConstraintSolver::new();
+    far_parallel(&mut solver);
+    solver.set_options(SolverOptions {
+        initial_trust_radius: 100.0,
+        max_trust_radius: 1e4,
+        ..Default::default()
+    });
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+}
+
+#[test]
+fn test_exhausted_time_budget_stops_the_solve() {
+    let mut solver = ConstraintSolver::new();
+    far_parallel(&mut solver);
+    solver.set_options(SolverOptions {
+        time_budget: Some(Duration::ZERO),
+        ..Default::default()
+    });
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+    assert_eq!(result.clusters()[0].iterations, 0);
+    assert_eq!(solver.get_point("d".into()).unwrap().x, 1.0);
+
+    // The drag solver honours the budget as well
+    let result = solver.drag_point("c", 0.0, 6.0).unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+}
+
+#[test]
+fn test_options_in_json_request() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "options": {"max_iterations": 0},
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Vertical", "point_a": "p1", "point_b": "p2"}
+        ]
+    }"#;
+
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], false);
+
+    // Omitted fields fall back to their defaults
+    solver
+        .set_options_json(r#"{"tolerance": 1e-9, "time_budget_ms": 1000}"#.into())
+        .unwrap();
+    let response = solver.solve_and_get_state_json().unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+
+    assert!(
+        solver
+            .set_options_json(r#"{"time_budget_ms": -5}"#.into())
+            .is_err()
+    );
+}
@@ -0,0 +1,95 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{AcsError, Arc, Circle, ConstraintSolver, ConstraintType, Point, SolverResult};
+
+#[test]
+fn test_locked_coordinate_stays_put() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point {
+        fixed_y: true,
+        ..Point::new("guide".into(), 3.0, 2.0, false)
+    });
+    solver.add_point(Point::new("p".into(), 5.0, 5.0, false));
+    solver
+        .add_constraint(ConstraintType::Coincident("guide".into(), "p".into()))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::EqualX("p".into(), 7.0))
+        .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    let guide = solver.get_point("guide".into()).unwrap();
+    assert!((guide.x - 7.0).abs() < 1e-6);
+    assert_eq!(guide.y, 2.0);
+
+    // The guide slides along x only when dragged
+    solver.drag_point("guide", 1.0, 9.0).unwrap();
+    let guide = solver.get_point("guide".into()).unwrap();
+    assert_eq!(guide.y, 2.0);
+
+    // Locking x too makes it immovable
+    solver.set_parameter_locked("guide", "x", true).unwrap();
+    let error = solver.drag_point("guide", 1.0, 9.0).unwrap_err();
+    assert!(matches!(error, AcsError::FixedParameter { .. }));
+}
+
+#[test]
+fn test_lock_single_arc_parameter() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("c".into(), 0.0, 0.0, true));
+    solver.add_arc(Arc::new("a".into(), "c".into(), 2.0, 0.0, 1.0, false));
+    assert_eq!(solver.analyze_dof().unwrap().entity("a").unwrap().dof, 3);
+
+    solver.set_parameter_locked("a", "radius", true).unwrap();
+    let arc = solver.get_arc("a".into()).unwrap();
+    assert!(arc.fixed_radius && !arc.fixed_start_angle);
+    assert_eq!(solver.analyze_dof().unwrap().entity("a").unwrap().dof, 2);
+
+    let error = solver.set_parameter_locked("a", "x", true).unwrap_err();
+    assert!(matches!(error, AcsError::InvalidParameter { .. }));
+    let error = solver
+        .set_parameter_locked("missing", "x", true)
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnknownEntity { .. }));
+}
+
+#[test]
+fn test_lock_circle_radius() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("c".into(), 0.0, 0.0, false));
+    solver.add_circle(Circle::new("circle".into(), "c".into(), 2.0, false));
+
+    solver.set_parameter_locked("circle", "radius", true).unwrap();
+    let circle = solver.get_circle("circle".into()).unwrap();
+    assert!(circle.fixed_radius && !circle.fixed);
+    assert_eq!(solver.analyze_dof().unwrap().entity("circle").unwrap().dof, 0);
+    // The center still moves
+    assert_eq!(solver.analyze_dof().unwrap().entity("c").unwrap().dof, 2);
+
+    solver.set_parameter_locked("circle", "radius", false).unwrap();
+    assert_eq!(solver.analyze_dof().unwrap().entity("circle").unwrap().dof, 1);
+}
+
+#[test]
+fn test_parameter_locks_in_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "origin", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "guide", "x": 3.0, "y": 4.0, "fixed": false, "fixed_x": true}
+        ],
+        "constraints": [
+            {"type": "Horizontal", "point_a": "origin", "point_b": "guide"}
+        ]
+    }"#;
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+
+    let primitives = response["primitives"].as_array().unwrap();
+    let guide = primitives.iter().find(|p| p["id"] == "guide").unwrap();
+    assert_eq!(guide["x"], 3.0);
+    assert!(guide["y"].as_f64().unwrap().abs() < 1e-6);
+    assert_eq!(guide["fixed_x"], true);
+    assert_eq!(guide.get("fixed_y"), None);
+}
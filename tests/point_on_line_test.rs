@@ -40,3 +40,53 @@ fn test_point_on_line_constraint() {
         "Expected point to be on the vertical line at x = 0, got x = {x}"
     );
 }
+
+#[test]
+fn test_point_on_line_converges_to_tolerance() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 10.0, 0.0, true));
+    solver.add_point(Point::new("p".into(), 3.0, 2.0, false));
+    solver
+        .add_constraint(ConstraintType::PointOnLine(
+            "p".into(),
+            "a".into(),
+            "b".into(),
+        ))
+        .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    // The point moves straight onto the line
+    let p = solver.get_point("p".into()).unwrap();
+    assert!(p.y.abs() < 1e-6, "Expected y = 0, got y = {}", p.y);
+    assert!((p.x - 3.0).abs() < 1e-6, "Expected x = 3, got x = {}", p.x);
+}
+
+#[test]
+fn test_point_beyond_endpoint_moves_onto_segment() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 10.0, 0.0, true));
+    // On the line through a and b, but past b
+    solver.add_point(Point::new("p".into(), 15.0, 0.0, false));
+    solver
+        .add_constraint(ConstraintType::PointOnLine(
+            "p".into(),
+            "a".into(),
+            "b".into(),
+        ))
+        .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let p = solver.get_point("p".into()).unwrap();
+    assert!(p.y.abs() < 1e-6, "Expected y = 0, got y = {}", p.y);
+    assert!(
+        (0.0..=10.0 + 1e-6).contains(&p.x),
+        "Expected p on the segment, got x = {}",
+        p.x
+    );
+}
@@ -0,0 +1,104 @@
+use acs::{
+    AcsError, CancellationToken, ConstraintSolver, ConstraintType, Point, SolveObserver,
+    SolveProgress, SolverKind, SolverResult,
+};
+
+/// Records every progress report and cancels after `cancel_after` of them
+#[derive(Default)]
+struct Recorder {
+    progress: Vec<SolveProgress>,
+    cancel_after: Option<usize>,
+}
+
+impl SolveObserver for Recorder {
+    fn on_iteration(&mut self, progress: &SolveProgress) {
+        self.progress.push(progress.clone());
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel_after
+            .is_some_and(|limit| self.progress.len() >= limit)
+    }
+}
+
+fn build(solver: &mut ConstraintSolver) {
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 1.0, 1.0, true));
+    solver.add_point(Point::new("c".into(), 0.0, 5.0, false));
+    solver.add_point(Point::new("d".into(), 1.0, 5.0, false));
+    solver
+        .add_constraint(ConstraintType::Parallel(
+            "a".into(),
+            "b".into(),
+            "c".into(),
+            "d".into(),
+        ))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::EqualX("d".into(), 50.0))
+        .unwrap();
+}
+
+#[test]
+fn test_progress_is_reported_every_iteration() {
+    for kind in [SolverKind::DogLeg, SolverKind::LevenbergMarquardt] {
+        let mut solver = ConstraintSolver::with_solver(kind.create());
+        build(&mut solver);
+
+        let mut recorder = Recorder::default();
+        let result = solver.solve_with_observer(&mut recorder).unwrap();
+        assert!(matches!(result, SolverResult::Converged { .. }));
+
+        assert!(recorder.progress.len() > 1);
+        for (i, progress) in recorder.progress.iter().enumerate() {
+            assert_eq!(progress.iteration, i);
+            assert_eq!(progress.cluster, 0);
+        }
+        let last = recorder.progress.last().unwrap();
+        assert!(last.residual_norm < 1e-6);
+        assert!(recorder.progress[0].residual_norm > last.residual_norm);
+
+        // Only dog-leg has a trust region
+        let has_radius = recorder.progress.iter().all(|p| p.trust_radius.is_some());
+        assert_eq!(has_radius, kind == SolverKind::DogLeg);
+    }
+}
+
+#[test]
+fn test_cancelled_solve_restores_geometry() {
+    for kind in [SolverKind::DogLeg, SolverKind::LevenbergMarquardt] {
+        let mut solver = ConstraintSolver::with_solver(kind.create());
+        build(&mut solver);
+
+        let mut recorder = Recorder {
+            cancel_after: Some(2),
+            ..Default::default()
+        };
+        let result = solver.solve_with_observer(&mut recorder);
+        assert!(matches!(result, Err(AcsError::Cancelled)));
+        assert_eq!(recorder.progress.len(), 2);
+
+        let c = solver.get_point("c".into()).unwrap();
+        let d = solver.get_point("d".into()).unwrap();
+        assert_eq!((c.x, c.y, d.x, d.y), (0.0, 5.0, 1.0, 5.0));
+
+        // The sketch still solves afterwards
+        let result = solver.solve().unwrap();
+        assert!(matches!(result, SolverResult::Converged { .. }));
+    }
+}
+
+#[test]
+fn test_cancellation_token() {
+    let mut solver = ConstraintSolver::new();
+    build(&mut solver);
+
+    let token = CancellationToken::new();
+    let mut observer = token.clone();
+    token.cancel();
+    assert!(observer.is_cancelled());
+
+    let result = solver.solve_with_observer(&mut observer);
+    assert!(matches!(result, Err(AcsError::Cancelled)));
+    assert_eq!(solver.get_point("d".into()).unwrap().x, 1.0);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use acs::{ConstraintType, RemovalPolicy, SolverResult};
+use common::build_sketch;
+
+#[test]
+fn test_add_constraint_returns_unique_ids() {
+    let mut solver = build_sketch();
+
+    let first = solver
+        .add_constraint(ConstraintType::HorizontalLine("l1".into()))
+        .expect("Constraint should be added successfully");
+    let second = solver
+        .add_constraint(ConstraintType::Vertical("p2".into(), "p3".into()))
+        .expect("Constraint should be added successfully");
+
+    assert_ne!(first, second);
+    let ids: Vec<&str> = solver
+        .get_constraint_entries()
+        .iter()
+        .map(|entry| entry.id.as_str())
+        .collect();
+    assert_eq!(ids, vec![first.as_str(), second.as_str()]);
+}
+
+#[test]
+fn test_remove_constraint() {
+    let mut solver = build_sketch();
+    let id = solver
+        .add_constraint(ConstraintType::Vertical("p2".into(), "p3".into()))
+        .expect("Constraint should be added successfully");
+
+    solver.remove_constraint(&id).expect("Constraint should be removed");
+    assert!(solver.get_constraint_entries().is_empty());
+    assert!(solver.remove_constraint(&id).is_err());
+
+    // With nothing left to satisfy, the points stay where they are
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    let p2 = solver.get_point("p2".into()).expect("Point p2 should exist");
+    assert!((p2.x - 3.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_remove_point_with_dependents_errors() {
+    let mut solver = build_sketch();
+    solver
+        .add_constraint(ConstraintType::EqualX("p1".into(), 1.0))
+        .expect("Constraint should be added successfully");
+
+    let result = solver.remove_point("p1", RemovalPolicy::Error);
+    assert!(result.is_err(), "Point used by a line should not be removed");
+
+    // Nothing was removed
+    assert!(solver.get_point("p1".into()).is_some());
+    assert!(solver.get_line("l1".into()).is_some());
+    assert!(solver.get_circle("c1".into()).is_some());
+    assert_eq!(solver.get_constraint_entries().len(), 1);
+}
+
+#[test]
+fn test_remove_point_without_dependents() {
+    let mut solver = build_sketch();
+    let removed = solver
+        .remove_point("p3", RemovalPolicy::Error)
+        .expect("Unused point should be removed");
+
+    assert_eq!(removed.points, vec!["p3".to_string()]);
+    assert!(removed.constraints.is_empty());
+    assert!(solver.get_point("p3".into()).is_none());
+}
+
+#[test]
+fn test_remove_point_cascades() {
+    let mut solver = build_sketch();
+    let line_constraint = solver
+        .add_constraint(ConstraintType::HorizontalLine("l1".into()))
+        .expect("Constraint should be added successfully");
+    let point_constraint = solver
+        .add_constraint(ConstraintType::Vertical("p1".into(), "p3".into()))
+        .expect("Constraint should be added successfully");
+    let unrelated = solver
+        .add_constraint(ConstraintType::EqualY("p3".into(), 4.0))
+        .expect("Constraint should be added successfully");
+
+    let removed = solver
+        .remove_point("p1", RemovalPolicy::Cascade)
+        .expect("Point should be removed with its dependents");
+
+    assert_eq!(removed.lines, vec!["l1".to_string()]);
+    assert_eq!(removed.circles, vec!["c1".to_string()]);
+    assert_eq!(removed.constraints, vec![line_constraint, point_constraint]);
+
+    assert!(solver.get_line("l1".into()).is_none());
+    assert!(solver.get_circle("c1".into()).is_none());
+    assert!(solver.get_point("p2".into()).is_some());
+
+    let remaining: Vec<&str> = solver
+        .get_constraint_entries()
+        .iter()
+        .map(|entry| entry.id.as_str())
+        .collect();
+    assert_eq!(remaining, vec![unrelated.as_str()]);
+
+    // The remaining sketch still solves
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+}
+
+#[test]
+fn test_remove_line_keeps_endpoints() {
+    let mut solver = build_sketch();
+    solver
+        .add_constraint(ConstraintType::VerticalLine("l1".into()))
+        .expect("Constraint should be added successfully");
+
+    assert!(solver.remove_line("l1", RemovalPolicy::Error).is_err());
+
+    let removed = solver
+        .remove_line("l1", RemovalPolicy::Cascade)
+        .expect("Line should be removed");
+    assert_eq!(removed.constraints.len(), 1);
+    assert!(removed.points.is_empty());
+    assert!(solver.get_point("p1".into()).is_some());
+    assert!(solver.get_point("p2".into()).is_some());
+    assert!(solver.get_constraint_entries().is_empty());
+}
+
+#[test]
+fn test_remove_unknown_entity() {
+    let mut solver = build_sketch();
+    assert!(solver.remove_point("missing", RemovalPolicy::Cascade).is_err());
+    assert!(solver.remove_circle("missing", RemovalPolicy::Cascade).is_err());
+    assert!(solver.remove_arc("missing", RemovalPolicy::Cascade).is_err());
+}
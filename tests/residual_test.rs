@@ -0,0 +1,80 @@
+mod common;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{ConstraintSolver, ConstraintType, Point, SolverResult};
+use common::add_named;
+
+#[test]
+fn test_residuals_identify_violated_constraints() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p1".into(), 0.0, 0.0, false));
+    solver.add_point(Point::new("p2".into(), 3.0, 4.0, false));
+    solver.add_point(Point::new("q".into(), 5.0, 5.0, false));
+    add_named(
+        &mut solver,
+        "level",
+        ConstraintType::Horizontal("p1".into(), "p2".into()),
+    );
+    add_named(&mut solver, "left", ConstraintType::EqualX("q".into(), 1.0));
+    add_named(
+        &mut solver,
+        "right",
+        ConstraintType::EqualX("q".into(), 2.0),
+    );
+
+    // Before solving, every constraint is off
+    let residuals = solver.constraint_residuals().unwrap();
+    assert_eq!(residuals.len(), 3);
+    assert_eq!(residuals[0].constraint, "level");
+    assert_eq!(residuals[0].constraint_type, "Horizontal");
+    assert!((residuals[0].norm - 4.0).abs() < 1e-12);
+    assert!((residuals[2].norm - 3.0).abs() < 1e-12);
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::MaxIterationsReached { .. }));
+
+    // The least-squares compromise leaves both targets half a unit away
+    let residuals = solver.constraint_residuals().unwrap();
+    let violated: Vec<&str> = residuals
+        .iter()
+        .filter(|residual| residual.is_violated(1e-6))
+        .map(|residual| residual.constraint.as_str())
+        .collect();
+    assert_eq!(violated, ["left", "right"]);
+    assert_eq!(residuals[1].index, 1);
+    assert_eq!(residuals[1].residuals.len(), 1);
+    assert!((residuals[1].norm - 0.5).abs() < 1e-6);
+    assert!((residuals[2].norm - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn test_residuals_in_json_response() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Vertical", "point_a": "p1", "point_b": "p2", "id": "upright"},
+            {"type": "EqualX", "point": "p2", "x": 2.0, "id": "offset"}
+        ]
+    }"#;
+
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    let result = &response["result"];
+    assert_eq!(result["converged"], false);
+    assert_eq!(result["violated"], serde_json::json!(["upright", "offset"]));
+    assert_eq!(result["residuals"][1]["constraint"], "offset");
+    assert_eq!(result["residuals"][1]["constraint_type"], "EqualX");
+    assert!((result["residuals"][1]["norm"].as_f64().unwrap() - 1.0).abs() < 1e-6);
+
+    // A solved sketch reports its residuals but nothing violated
+    let request = request.replace("\"x\": 2.0", "\"x\": 0.0");
+    let response = solver.solve_from_json(request).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+    assert_eq!(response["result"]["violated"], serde_json::json!([]));
+    assert_eq!(response["result"]["residuals"].as_array().unwrap().len(), 2);
+}
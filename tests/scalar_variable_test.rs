@@ -0,0 +1,159 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    AcsError, ConstraintSolver, ConstraintType, EntityType, ParameterManager, Point, RemovalPolicy,
+    ScalarVariable, SolverResult,
+};
+
+/// Two horizontal segments whose common length `L` is only fixed by `b.x`
+fn shared_length_sketch() -> ConstraintSolver {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 5.0, 1.0, false));
+    solver.add_point(Point::new("c".into(), 0.0, 5.0, true));
+    solver.add_point(Point::new("d".into(), 3.0, 6.0, false));
+    solver
+        .add_scalar_variable(ScalarVariable::new("L".into(), 1.0, false))
+        .unwrap();
+
+    for constraint in [
+        ConstraintType::Horizontal("a".into(), "b".into()),
+        ConstraintType::Horizontal("c".into(), "d".into()),
+        ConstraintType::EqualX("b".into(), 7.0),
+        ConstraintType::VariableDistance("a".into(), "b".into(), "L".into()),
+        ConstraintType::VariableDistance("c".into(), "d".into(), "L".into()),
+    ] {
+        solver.add_constraint(constraint).unwrap();
+    }
+    solver
+}
+
+#[test]
+fn test_parameter_manager_registers_scalar_variables() {
+    let mut param_manager = ParameterManager::new();
+    param_manager.register_entity(
+        "p".into(),
+        EntityType::Point,
+        &Point::new("p".into(), 1.0, 2.0, false),
+    );
+    param_manager
+        .register_scalar_variable(&ScalarVariable::new("L".into(), 4.0, false))
+        .unwrap();
+
+    let index = param_manager.resolve_index("L", 0).unwrap();
+    assert_eq!(index, 2);
+    assert_eq!(param_manager.get_parameters()[index], 4.0);
+    let info = param_manager.get_parameter_info_by_index(index).unwrap();
+    assert_eq!(info.name, "L.value");
+    assert_eq!(info.entity_type, EntityType::ScalarVariable);
+    assert!(param_manager.resolve_index("L", 1).is_err());
+
+    // IDs of registered entities cannot be reused
+    let error = param_manager
+        .register_scalar_variable(&ScalarVariable::new("p".into(), 1.0, false))
+        .unwrap_err();
+    assert!(matches!(error, AcsError::InvalidGeometry { .. }));
+}
+
+#[test]
+fn test_scalar_variable_id_must_be_unused() {
+    let mut solver = shared_length_sketch();
+    let error = solver
+        .add_scalar_variable(ScalarVariable::new("a".into(), 1.0, false))
+        .unwrap_err();
+    assert!(matches!(error, AcsError::InvalidGeometry { .. }));
+    assert!(solver.get_scalar_variable("a".into()).is_none());
+    assert_eq!(solver.get_point("a".into()).unwrap().x, 0.0);
+
+    // Replacing a variable with one of the same ID is fine
+    solver
+        .add_scalar_variable(ScalarVariable::new("L".into(), 2.0, false))
+        .unwrap();
+    assert_eq!(solver.get_scalar_variable("L".into()).unwrap().value, 2.0);
+}
+
+#[test]
+fn test_solver_determines_shared_length() {
+    let mut solver = shared_length_sketch();
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let length = solver.get_scalar_variable("L".into()).unwrap().value;
+    assert!((length - 7.0).abs() < 1e-6, "L = {length}");
+    let d = solver.get_point("d".into()).unwrap();
+    assert!((d.x - 7.0).abs() < 1e-6 && (d.y - 5.0).abs() < 1e-6);
+    assert!(solver.analyze_dof().unwrap().is_fully_constrained());
+
+    // A fixed variable drives the segments instead
+    let mut solver = shared_length_sketch();
+    solver
+        .remove_constraint(&solver.get_constraint_entries()[2].id.clone())
+        .unwrap();
+    solver.set_parameter_locked("L", "value", true).unwrap();
+    solver.solve().unwrap();
+    let b = solver.get_point("b".into()).unwrap();
+    assert!((b.x - 1.0).abs() < 1e-6, "b.x = {}", b.x);
+}
+
+#[test]
+fn test_removing_scalar_variable() {
+    let mut solver = shared_length_sketch();
+    let error = solver
+        .remove_scalar_variable("L", RemovalPolicy::Error)
+        .unwrap_err();
+    assert!(matches!(error, AcsError::EntityInUse { .. }));
+
+    let removed = solver
+        .remove_scalar_variable("L", RemovalPolicy::Cascade)
+        .unwrap();
+    assert_eq!(removed.scalar_variables, vec!["L"]);
+    assert_eq!(removed.constraints.len(), 2);
+    assert!(solver.get_scalar_variable("L".into()).is_none());
+
+    let error = solver
+        .add_constraint(ConstraintType::VariableDistance(
+            "a".into(),
+            "b".into(),
+            "c".into(),
+        ))
+        .unwrap_err();
+    assert!(matches!(error, AcsError::WrongEntityType { .. }));
+}
+
+#[test]
+fn test_scalar_variables_in_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "a", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "b", "x": 0.0, "y": 3.0, "fixed": true},
+            {"type": "Point", "id": "c", "x": 4.0, "y": 1.0, "fixed": false},
+            {"type": "ScalarVariable", "id": "r", "value": 1.0}
+        ],
+        "constraints": [
+            {"type": "EqualX", "point": "c", "x": 4.0},
+            {"type": "VariableDistance", "point_a": "a", "point_b": "c", "variable": "r"},
+            {"type": "VariableDistance", "point_a": "b", "point_b": "c", "variable": "r"}
+        ]
+    }"#;
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["converged"], true);
+
+    // c is equidistant from a and b, so on y = 1.5
+    let primitives = response["primitives"].as_array().unwrap();
+    let r = primitives.iter().find(|p| p["id"] == "r").unwrap();
+    assert_eq!(r["type"], "ScalarVariable");
+    assert_eq!(r["fixed"], false);
+    let value = r["value"].as_f64().unwrap();
+    assert!((value - 4.0f64.hypot(1.5)).abs() < 1e-6, "r = {value}");
+    assert_eq!(
+        response["constraints"][1],
+        serde_json::json!({
+            "type": "VariableDistance",
+            "id": "constraint_1",
+            "point_a": "a",
+            "point_b": "c",
+            "variable": "r"
+        })
+    );
+}
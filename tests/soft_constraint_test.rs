@@ -0,0 +1,177 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    AcsError, CancellationToken, ConstraintOptions, ConstraintSolver, ConstraintType, Point,
+    SolverResult,
+};
+
+fn add_soft(
+    solver: &mut ConstraintSolver,
+    constraint_type: ConstraintType,
+    priority: u32,
+    weight: f64,
+) -> Result<(), AcsError> {
+    solver
+        .add_constraint_with_options(
+            constraint_type,
+            ConstraintOptions {
+                priority: Some(priority),
+                weight,
+                ..Default::default()
+            },
+        )
+        .map(|_| ())
+}
+
+fn position(solver: &ConstraintSolver, id: &str) -> (f64, f64) {
+    let point = solver.get_point(id.into()).unwrap();
+    (point.x, point.y)
+}
+
+#[test]
+fn test_soft_constraints_give_way_to_hard_ones() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("origin".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("p".into(), 3.0, 1.0, false));
+    solver
+        .add_constraint(ConstraintType::Horizontal("origin".into(), "p".into()))
+        .unwrap();
+
+    // Prefer p near (10, 10), which the hard constraint only allows in x
+    add_soft(
+        &mut solver,
+        ConstraintType::EqualX("p".into(), 10.0),
+        0,
+        1.0,
+    )
+    .unwrap();
+    add_soft(
+        &mut solver,
+        ConstraintType::EqualY("p".into(), 10.0),
+        0,
+        1.0,
+    )
+    .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    let (x, y) = position(&solver, "p");
+    assert!((x - 10.0).abs() < 1e-6, "x = {x}");
+    assert!(y.abs() < 1e-6, "y = {y}");
+
+    // Soft constraints neither take freedom away nor count as conflicts
+    assert_eq!(solver.analyze_dof().unwrap().dof, 1);
+    assert!(solver.diagnose_constraints().unwrap().conflicts.is_empty());
+}
+
+#[test]
+fn test_priorities_are_lexicographic() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p".into(), 0.0, 0.0, false));
+    add_soft(&mut solver, ConstraintType::EqualX("p".into(), 5.0), 1, 1.0).unwrap();
+    // No weight lets a later level pull on an earlier one
+    add_soft(
+        &mut solver,
+        ConstraintType::EqualX("p".into(), 8.0),
+        2,
+        100.0,
+    )
+    .unwrap();
+    add_soft(&mut solver, ConstraintType::EqualY("p".into(), 3.0), 2, 1.0).unwrap();
+
+    solver.solve().unwrap();
+    let (x, y) = position(&solver, "p");
+    assert!((x - 5.0).abs() < 1e-6, "x = {x}");
+    assert!((y - 3.0).abs() < 1e-6, "y = {y}");
+}
+
+#[test]
+fn test_weights_trade_off_within_a_level() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p".into(), 4.0, 0.0, false));
+    add_soft(&mut solver, ConstraintType::EqualX("p".into(), 0.0), 0, 1.0).unwrap();
+    add_soft(
+        &mut solver,
+        ConstraintType::EqualX("p".into(), 10.0),
+        0,
+        3.0,
+    )
+    .unwrap();
+
+    // Minimizes x² + 3²(x - 10)²
+    solver.solve().unwrap();
+    let (x, _) = position(&solver, "p");
+    assert!((x - 9.0).abs() < 1e-6, "x = {x}");
+
+    let error = add_soft(&mut solver, ConstraintType::EqualY("p".into(), 0.0), 0, 0.0);
+    assert!(matches!(error, Err(AcsError::UnsupportedConstraint { .. })));
+}
+
+#[test]
+fn test_soft_levels_scale_and_can_be_cancelled() {
+    // A long hard chain makes a cluster far too large for dense
+    // factorizations over all of its parameters
+    let mut solver = ConstraintSolver::new();
+    let size = 2_000;
+    for i in 0..size {
+        solver.add_point(Point::new(format!("p{i}"), i as f64, 0.0, false));
+    }
+    for i in 1..size {
+        solver
+            .add_constraint(ConstraintType::Horizontal(
+                format!("p{}", i - 1),
+                format!("p{i}"),
+            ))
+            .unwrap();
+    }
+    let last = format!("p{}", size - 1);
+    add_soft(
+        &mut solver,
+        ConstraintType::EqualY(last.clone(), 5.0),
+        0,
+        1.0,
+    )
+    .unwrap();
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    assert!((position(&solver, "p0").1 - 5.0).abs() < 1e-6);
+    assert!((position(&solver, &last).1 - 5.0).abs() < 1e-6);
+
+    // Soft levels check for cancellation like the hard solve does
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("p".into(), 0.0, 0.0, false));
+    add_soft(&mut solver, ConstraintType::EqualX("p".into(), 3.0), 0, 1.0).unwrap();
+    let mut token = CancellationToken::new();
+    token.cancel();
+    let error = solver.solve_with_observer(&mut token).unwrap_err();
+    assert_eq!(error, AcsError::Cancelled);
+    assert_eq!(position(&solver, "p"), (0.0, 0.0));
+}
+
+#[test]
+fn test_weight_and_priority_in_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "a", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "b", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Vertical", "id": "wall", "point_a": "a", "point_b": "b"},
+            {"type": "EqualX", "id": "prefer", "point": "b", "x": 2.0, "priority": 1, "weight": 0.5}
+        ]
+    }"#;
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+
+    assert_eq!(response["result"]["converged"], true);
+    assert_eq!(response["constraints"][0].get("priority"), None);
+    assert_eq!(response["constraints"][1]["priority"], 1);
+    assert_eq!(response["constraints"][1]["weight"], 0.5);
+    // The preference cannot hold, which is not a violation
+    assert_eq!(response["result"]["violated"], serde_json::json!([]));
+    assert_eq!(response["result"]["residuals"][1]["priority"], 1);
+
+    let b = solver.get_point("b").unwrap();
+    assert!(b.x.abs() < 1e-6 && (b.y - 4.0).abs() < 1e-6);
+}
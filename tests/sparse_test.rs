@@ -0,0 +1,92 @@
+use acs::constraints::parallel::ParallelConstraint;
+use acs::{
+    AcsError, Constraint, ConstraintSolver, ConstraintType, EntityType, ParameterManager, Point,
+    SolverResult, VerticalConstraint,
+};
+
+#[test]
+fn test_dense_jacobian_matches_entries() {
+    let mut param_manager = ParameterManager::new();
+    let points = [(0.0, 0.0), (2.0, 1.0), (1.0, 3.0), (4.0, 2.0)];
+    for (i, (x, y)) in points.into_iter().enumerate() {
+        let point = Point::new(format!("p{i}"), x, y, false);
+        param_manager.register_entity(format!("p{i}"), EntityType::Point, &point);
+    }
+
+    let constraint = ParallelConstraint::new("p0".into(), "p1".into(), "p2".into(), "p3".into());
+    let entries = constraint.jacobian_entries(&param_manager).unwrap();
+    let dense = constraint.jacobian(&param_manager).unwrap();
+
+    assert_eq!(dense.nrows(), 1);
+    assert_eq!(dense.ncols(), 8);
+    assert_eq!(entries.len(), 8);
+    for (row, col, value) in entries {
+        assert_eq!(dense[(row, col)], value);
+    }
+
+    // Missing entities are an error rather than a panic
+    let constraint = ParallelConstraint::new("p0".into(), "p1".into(), "p2".into(), "p9".into());
+    let error = constraint.jacobian(&param_manager).unwrap_err();
+    assert!(matches!(error, AcsError::UnknownEntity { .. }));
+    assert!(constraint.residual(&param_manager).is_err());
+}
+
+#[test]
+fn test_large_chain_solves() {
+    let mut solver = ConstraintSolver::new();
+    let size = 1_000;
+
+    for i in 0..size {
+        let x = i as f64 + (i as f64).sin() * 0.2;
+        let y = (i as f64).cos() * 0.5;
+        solver.add_point(Point::new(format!("p{i}"), x, y, i == 0));
+    }
+    for i in 1..size {
+        solver
+            .add_constraint(ConstraintType::Horizontal(
+                format!("p{}", i - 1),
+                format!("p{i}"),
+            ))
+            .expect("Constraint should be added successfully");
+    }
+
+    let result = solver.solve().expect("Solver should solve successfully");
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let first = solver.get_point("p0".into()).unwrap();
+    let last = solver.get_point(format!("p{}", size - 1)).unwrap();
+    assert!((last.y - first.y).abs() < 1e-6);
+    // The fixed point does not move
+    assert!((first.y - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn test_bound_constraint_evaluates_raw_parameters() {
+    let mut param_manager = ParameterManager::new();
+    for (id, x, y) in [("p1", 1.0, 0.0), ("p2", 4.0, 2.0)] {
+        let point = Point::new(id.into(), x, y, false);
+        param_manager.register_entity(id.into(), EntityType::Point, &point);
+    }
+
+    let constraint = VerticalConstraint::new("p1".into(), "p2".into());
+    let bound = constraint
+        .bind(&param_manager)
+        .expect("Constraint should bind");
+
+    let mut params = param_manager.get_parameters().to_vec();
+    let mut residual = [0.0];
+    bound.residual(&params, &mut residual);
+    assert_eq!(residual, [-3.0]);
+
+    // The bound constraint only holds indices, so it follows the new values
+    let p2_x = param_manager.resolve_index("p2", 0).unwrap();
+    params[p2_x] = 1.5;
+    bound.residual(&params, &mut residual);
+    assert_eq!(residual, [-0.5]);
+
+    let missing = VerticalConstraint::new("p1".into(), "p9".into());
+    assert_eq!(
+        missing.bind(&param_manager).err(),
+        Some(AcsError::unknown_entity("p9"))
+    );
+}
@@ -0,0 +1,102 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    ConstraintSolver, ConstraintType, ParametricDogLegSolver, Point, SolverResult, StepType,
+    TraceRecorder,
+};
+
+fn build(solver: &mut ConstraintSolver) {
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 1.0, 1.0, true));
+    solver.add_point(Point::new("c".into(), 0.0, 5.0, false));
+    solver.add_point(Point::new("d".into(), 1.0, 5.0, false));
+    solver
+        .add_constraint(ConstraintType::Parallel(
+            "a".into(),
+            "b".into(),
+            "c".into(),
+            "d".into(),
+        ))
+        .unwrap();
+    solver
+        .add_constraint(ConstraintType::EqualX("d".into(), 50.0))
+        .unwrap();
+}
+
+#[test]
+fn test_trace_records_every_iteration() {
+    let recorder = TraceRecorder::new();
+    let mut solver = ConstraintSolver::with_solver(Box::new(ParametricDogLegSolver::with_trace(
+        recorder.clone(),
+    )));
+    build(&mut solver);
+
+    let result = solver.solve().unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+
+    let trace = recorder.trace();
+    let mut names = trace.parameter_names.clone();
+    names.sort();
+    assert_eq!(
+        names,
+        ["a.x", "a.y", "b.x", "b.y", "c.x", "c.y", "d.x", "d.y"]
+    );
+    // Every iteration takes one step
+    assert_eq!(trace.steps.len(), result.clusters()[0].iterations);
+    let d_x = trace.parameter_names.iter().position(|name| name == "d.x");
+    assert_eq!(trace.steps[0].parameters[d_x.unwrap()], 1.0);
+    assert_eq!(trace.steps[0].parameters.len(), 8);
+    assert_eq!(trace.steps[0].trust_radius, 1.0);
+    assert!(
+        trace
+            .steps
+            .iter()
+            .any(|step| step.step_type != StepType::GaussNewton)
+    );
+
+    // Replaying the steps: a reverted step leaves the parameters as they were
+    for (i, pair) in trace.steps.windows(2).enumerate() {
+        assert_eq!(pair[0].iteration, i);
+        assert_eq!(pair[0].residuals.len(), 2);
+        if pair[0].reverted {
+            assert!(pair[0].rho < 0.1);
+            assert_eq!(pair[0].parameters, pair[1].parameters);
+        } else {
+            assert_ne!(pair[0].parameters, pair[1].parameters);
+        }
+    }
+
+    // A new solve replaces the trace; the sketch is solved already
+    solver.solve().unwrap();
+    assert!(recorder.trace().steps.is_empty());
+}
+
+#[test]
+fn test_trace_as_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "primitives": [
+            {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "p2", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Horizontal", "point_a": "p1", "point_b": "p2"}
+        ]
+    }"#;
+
+    solver.solve_from_json(request.to_string()).unwrap();
+    let trace: serde_json::Value = serde_json::from_str(&solver.trace_json().unwrap()).unwrap();
+    assert_eq!(trace["steps"], serde_json::json!([]));
+
+    solver.set_trace_enabled(true);
+    solver.solve_from_json(request.to_string()).unwrap();
+    let trace: serde_json::Value = serde_json::from_str(&solver.trace_json().unwrap()).unwrap();
+    let step = &trace["steps"][0];
+    let names = trace["parameter_names"].as_array().unwrap();
+    let p2_y = names.iter().position(|name| name == "p2.y").unwrap();
+    // The full step of length 4 does not fit the initial trust region
+    assert_eq!(step["step_type"], "Gradient");
+    assert_eq!(step["trust_radius"], 1.0);
+    assert_eq!(step["parameters"][p2_y], 4.0);
+    assert_eq!(step["residuals"][0].as_f64().unwrap().abs(), 4.0);
+    assert_eq!(step["reverted"], false);
+}
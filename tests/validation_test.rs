@@ -0,0 +1,119 @@
+mod common;
+
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{AcsError, ConstraintOptions, ConstraintType, EntityType};
+use common::build_sketch;
+
+#[test]
+fn test_unknown_point_is_rejected() {
+    let mut solver = build_sketch();
+
+    let result = solver.add_constraint(ConstraintType::Horizontal("p1".into(), "p9".into()));
+    assert_eq!(
+        result,
+        Err(AcsError::UnknownEntity {
+            entity: "p9".into(),
+            constraint: Some("Horizontal".into()),
+        })
+    );
+    assert!(solver.get_constraint_entries().is_empty());
+
+    // Solving still works, nothing panics
+    solver.solve().expect("Solver should solve successfully");
+}
+
+#[test]
+fn test_error_names_caller_supplied_id() {
+    let mut solver = build_sketch();
+
+    let result = solver.add_constraint_with_options(
+        ConstraintType::EqualX("missing".into(), 1.0),
+        ConstraintOptions {
+            id: Some("x-dim".into()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        result,
+        Err(AcsError::UnknownEntity {
+            entity: "missing".into(),
+            constraint: Some("x-dim".into()),
+        })
+    );
+}
+
+#[test]
+fn test_wrong_entity_type_is_rejected() {
+    let mut solver = build_sketch();
+
+    let result = solver.add_constraint(ConstraintType::EqualRadius("c1".into(), "p2".into()));
+    assert_eq!(
+        result,
+        Err(AcsError::WrongEntityType {
+            entity: "p2".into(),
+            expected: vec![EntityType::Circle, EntityType::Arc],
+            found: EntityType::Point,
+            constraint: Some("EqualRadius".into()),
+        })
+    );
+
+    let result = solver.add_constraint(ConstraintType::Vertical("l1".into(), "p2".into()));
+    assert!(matches!(
+        result,
+        Err(AcsError::WrongEntityType {
+            found: EntityType::Line,
+            ..
+        })
+    ));
+
+    let result = solver.add_constraint(ConstraintType::VerticalLine("p1".into()));
+    assert!(matches!(
+        result,
+        Err(AcsError::WrongEntityType {
+            found: EntityType::Point,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_unimplemented_constraint_is_reported() {
+    let mut solver = build_sketch();
+
+    let result = solver.add_constraint(ConstraintType::FixedRadius("c1".into(), 3.0));
+    assert!(matches!(result, Err(AcsError::UnsupportedConstraint { .. })));
+}
+
+#[test]
+fn test_json_batch_is_validated_before_adding() {
+    let mut solver = WrappedConstraintSolver::new();
+    solver
+        .add_primitives_json(
+            r#"[
+                {"type": "Point", "id": "p1", "x": 0.0, "y": 0.0, "fixed": false},
+                {"type": "Point", "id": "p2", "x": 1.0, "y": 1.0, "fixed": false}
+            ]"#
+            .to_string(),
+        )
+        .expect("Primitives should be added");
+
+    let error = solver
+        .add_constraints_json(
+            r#"[
+                {"type": "Vertical", "point_a": "p1", "point_b": "p2"},
+                {"type": "Horizontal", "point_a": "p1", "point_b": "typo", "id": "h1"}
+            ]"#
+            .to_string(),
+        )
+        .expect_err("Batch with an unknown point should be rejected");
+    assert_eq!(
+        error,
+        AcsError::UnknownEntity {
+            entity: "typo".into(),
+            constraint: Some("h1".into()),
+        }
+    );
+
+    let listed = solver.get_constraints_json().unwrap();
+    assert_eq!(listed, "[]", "No constraint from the batch should be added");
+}
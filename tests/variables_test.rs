@@ -0,0 +1,178 @@
+use acs::bindings::solver::WrappedConstraintSolver;
+use acs::{
+    AcsError, ConstraintOptions, ConstraintSolver, ConstraintType, Expression, Point, SolverResult,
+    VariableTable,
+};
+
+fn expr(source: &str) -> Expression {
+    Expression::parse(source).unwrap()
+}
+
+#[test]
+fn test_expressions_evaluate_over_variables() {
+    let mut variables = VariableTable::new();
+    variables.set("hole_r", expr("width / 8")).unwrap();
+    variables.set("width", expr("40")).unwrap();
+    variables.set("margin", expr("5")).unwrap();
+
+    assert_eq!(variables.value("hole_r").unwrap(), 5.0);
+    assert_eq!(variables.evaluate(&expr("width - 2*margin")).unwrap(), 30.0);
+    assert_eq!(
+        variables.evaluate(&expr("-2^2 + (1 + 2) * 3")).unwrap(),
+        5.0
+    );
+    assert_eq!(
+        variables.evaluate(&expr("sqrt(margin^2) + 1.5e1")).unwrap(),
+        20.0
+    );
+    assert!((variables.evaluate(&expr("cos(pi)")).unwrap() + 1.0).abs() < 1e-12);
+    assert_eq!(expr(" width - 2*margin ").to_string(), "width - 2*margin");
+    assert_eq!(expr("a * (b + a)").variables(), vec!["a", "b"]);
+
+    for source in ["2 +", "width (", "sqrt 2", "3 $ 4", ""] {
+        let error = Expression::parse(source).unwrap_err();
+        assert!(
+            matches!(error, AcsError::InvalidExpression { .. }),
+            "{source}: {error:?}"
+        );
+    }
+
+    // Deep nesting is an error rather than a stack overflow
+    let depth = 200_000;
+    let nested = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    for source in [nested, "-".repeat(depth) + "1", "1+".repeat(depth) + "1"] {
+        let error = Expression::parse(&source).unwrap_err();
+        assert!(matches!(error, AcsError::InvalidExpression { .. }));
+    }
+    assert!(Expression::parse(&format!("{}1{}", "(".repeat(100), ")".repeat(100))).is_ok());
+
+    let error = variables.evaluate(&expr("depth * 2")).unwrap_err();
+    assert_eq!(
+        error,
+        AcsError::UnknownVariable {
+            variable: "depth".into()
+        }
+    );
+    let error = variables
+        .evaluate(&expr("width / (margin - 5)"))
+        .unwrap_err();
+    assert!(matches!(error, AcsError::InvalidExpression { .. }));
+
+    // Cycles and bad names are rejected and leave the table unchanged
+    let error = variables.set("width", expr("hole_r * 8")).unwrap_err();
+    assert!(matches!(error, AcsError::InvalidExpression { .. }));
+    assert_eq!(variables.value("width").unwrap(), 40.0);
+    assert!(variables.set("pi", expr("3")).is_err());
+    assert!(variables.set("2x", expr("3")).is_err());
+}
+
+#[test]
+fn test_shared_dependencies_are_evaluated_once() {
+    // Each variable uses the previous one twice, which would take 2^200
+    // evaluations without reusing values
+    let mut variables = VariableTable::new();
+    variables.set("a0", expr("1")).unwrap();
+    for i in 1..=200 {
+        let previous = format!("a{}", i - 1);
+        let source = format!("{previous} / 2 + {previous} / 2");
+        variables.set(&format!("a{i}"), expr(&source)).unwrap();
+    }
+
+    assert_eq!(variables.value("a200").unwrap(), 1.0);
+    assert_eq!(variables.values().unwrap().len(), 201);
+    assert_eq!(variables.evaluate(&expr("a200 + a100")).unwrap(), 2.0);
+}
+
+#[test]
+fn test_changing_a_variable_resolves() {
+    let mut solver = ConstraintSolver::new();
+    solver.add_point(Point::new("a".into(), 0.0, 0.0, true));
+    solver.add_point(Point::new("b".into(), 20.0, 1.0, false));
+    solver.define_variable("width", expr("40")).unwrap();
+    solver.define_variable("margin", expr("5")).unwrap();
+
+    solver
+        .add_constraint(ConstraintType::Horizontal("a".into(), "b".into()))
+        .unwrap();
+    let entry = solver
+        .add_constraint_with_options(
+            ConstraintType::EqualX("b".into(), 0.0),
+            ConstraintOptions {
+                expression: Some(expr("width - 2*margin")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        entry.constraint_type,
+        ConstraintType::EqualX("b".into(), 30.0)
+    );
+
+    solver.solve().unwrap();
+    assert!((solver.get_point("b".into()).unwrap().x - 30.0).abs() < 1e-6);
+
+    let result = solver.set_variable("width", expr("60")).unwrap();
+    assert!(matches!(result, SolverResult::Converged { .. }));
+    assert!((solver.get_point("b".into()).unwrap().x - 50.0).abs() < 1e-6);
+    assert_eq!(
+        solver
+            .get_constraint_entry(&entry.id)
+            .unwrap()
+            .constraint_type,
+        ConstraintType::EqualX("b".into(), 50.0)
+    );
+
+    // A change that breaks a dimension is refused as a whole
+    let error = solver.set_variable("margin", expr("missing")).unwrap_err();
+    assert!(matches!(error, AcsError::UnknownVariable { .. }));
+    assert_eq!(solver.variables().value("margin").unwrap(), 5.0);
+
+    // Only constraints with a dimension take an expression
+    let error = solver
+        .add_constraint_with_options(
+            ConstraintType::Vertical("a".into(), "b".into()),
+            ConstraintOptions {
+                expression: Some(expr("width")),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert!(matches!(error, AcsError::UnsupportedConstraint { .. }));
+}
+
+#[test]
+fn test_variables_in_json() {
+    let mut solver = WrappedConstraintSolver::new();
+    let request = r#"{
+        "variables": {"width": 40, "margin": "width / 8"},
+        "primitives": [
+            {"type": "Point", "id": "a", "x": 0.0, "y": 0.0, "fixed": true},
+            {"type": "Point", "id": "b", "x": 3.0, "y": 4.0, "fixed": false}
+        ],
+        "constraints": [
+            {"type": "Distance", "id": "d", "point_a": "a", "point_b": "b",
+             "distance": "width - 2*margin"},
+            {"type": "EqualY", "point": "b", "y": 0.0}
+        ]
+    }"#;
+    let response = solver.solve_from_json(request.to_string()).unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+
+    assert_eq!(response["result"]["converged"], true);
+    assert_eq!(
+        response["variables"]["margin"],
+        serde_json::json!({"expression": "width / 8", "value": 5.0})
+    );
+    assert_eq!(response["constraints"][0]["distance"], "width - 2*margin");
+    assert_eq!(response["constraints"][1]["y"], 0.0);
+    assert!((solver.get_point("b").unwrap().x - 30.0).abs() < 1e-6);
+
+    let response = solver.set_variable("width", "48").unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["variables"]["margin"]["value"], 6.0);
+    assert!((solver.get_point("b").unwrap().x - 36.0).abs() < 1e-6);
+
+    let undefined = request.replace("width / 8", "depth / 8");
+    let error = solver.solve_from_json(undefined).unwrap_err();
+    assert!(matches!(error, AcsError::UnknownVariable { .. }));
+}